@@ -1,32 +1,59 @@
 use crate::error::ContractError;
 use crate::helpers::{
-    get_token_supply, query_app_exists, query_extended_pair_by_app, query_get_asset_data,
-    query_pool_by_app, query_surplus_reward, query_whitelisted_asset,
+    add_coin, advance_decay_point, allocate_emission, allocation_gauge_votes, cap_gauge_votes,
+    cw20_denom, early_unlock_penalty, epoch_emission, is_emission_executed, is_unlocked,
+    keeper_reward, load_app_config, position_weight, proposal_bribe_share, proposal_delegations,
+    proposal_status, proposal_voting_power, query_app_exists, query_app_gauges,
+    query_get_asset_data, query_gov_lock_denom, query_gov_token_supply, query_surplus_reward,
+    query_whitelisted_asset, total_voting_power, transfer_msg, voting_end_time, vtoken_end_time,
+    vtoken_slope, vtoken_voting_power, CW20_DENOM_PREFIX,
+};
+use crate::msg::{
+    Cw20HookMsg, DenomApp, ExecuteMsg, GaugeHookMsg, InstantiateMsg, MigrateMsg, QueryMsg, SudoMsg,
+};
+use crate::state::{
+    positions, AppConfig, BribeDeposit, CurveType, DecayPoint, DelegatedVote, Delegation,
+    DelegationInfo, EarlyUnlockConfig, Emission, EmissionFallback, EmissionSchedule, GaugeTarget,
+    LegacyEmissionVaultPool, LegacyProposal, LegacyVote, LockingPeriod, PenaltyDestination,
+    PeriodWeight, Position, ProposalStatus, ScheduledBribe, State, Status, StickyVote, TokenInfo,
+    TokenSupply, UserDelegationInfo, Vtoken, WeightCurve, APP_CONFIG, BRIBE_DEPOSITS,
+    CONTRACT_GAUGES, CW20_GOV_TOKENS, DELEGATED, DELEGATED_VOTES, DELEGATION_INFO, DELEGATORS,
+    EARLY_UNLOCK, EPOCH_ALIGNMENT, KEEPER_CONFIG, KILLED_GAUGES, LEGACY_BRIBES_BY_PROPOSAL,
+    LEGACY_EMISSION_REWARD, LEGACY_POOL_OFFSET, LEGACY_PROPOSAL, LEGACY_PROPOSALVOTE,
+    LEGACY_VOTERSPROPOSAL, PENDING_PENALTIES, POSITIONCOUNT, POSITION_OFFERS, SCHEDULED_BRIBES,
+    SCHEDULED_BRIBE_COUNT, SLOPE_CHANGES, STATE, STICKY_VOTES, SUPPLY, SUPPLY_DECAY, TOKENS,
+    VOTING_DECAY, VTOKENS, WEIGHT_CURVE,
 };
-use crate::msg::{ExecuteMsg, InstantiateMsg, MigrateMsg, QueryMsg, SudoMsg};
 use crate::state::{
     EmissionVaultPool, Proposal, Vote, VotePair, ADMIN, APPCURRENTPROPOSAL, BRIBES_BY_PROPOSAL,
     COMPLETEDPROPOSALS, CSWAP_ID, EMISSION, EMISSION_REWARD, PROPOSAL, PROPOSALCOUNT, PROPOSALVOTE,
     REBASE_CLAIMED, VOTERSPROPOSAL, VOTERS_CLAIM, VOTERS_CLAIMED_PROPOSALS, VOTERS_VOTE,
 };
-use crate::state::{
-    LockingPeriod, PeriodWeight, State, Status, TokenInfo, TokenSupply, Vtoken, STATE, SUPPLY,
-    TOKENS, VTOKENS,
-};
 
 use comdex_bindings::{ComdexMessages, ComdexQuery};
 #[cfg(not(feature = "library"))]
 use cosmwasm_std::entry_point;
 use cosmwasm_std::{
-    to_binary, Addr, Api, BankMsg, Coin, Decimal, Deps, DepsMut, Env, MessageInfo, QueryRequest,
-    Response, StdError, StdResult, Storage, Uint128, WasmQuery,
+    from_json, to_binary, to_json_binary, Addr, Api, BankMsg, Coin, Decimal, Deps, DepsMut, Env,
+    MessageInfo, Order, QueryRequest, Reply, Response, StdError, StdResult, Storage, SubMsg,
+    SubMsgResult, Timestamp, Uint128, WasmMsg, WasmQuery,
 };
 use cw2::set_contract_version;
+use cw20::{Cw20ExecuteMsg, Cw20ReceiveMsg};
 use std::ops::{Div, Mul};
 
 // version info for migration info
 const CONTRACT_NAME: &str = "crates.io:locking_contract";
 const CONTRACT_VERSION: &str = env!("CARGO_PKG_VERSION");
+// Maximum number of scheduled bribes pending for an app, all released when raising a proposal
+const MAX_SCHEDULED_BRIBES: usize = 50;
+// Maximum number of sticky votes of an app, all re-applied when raising a proposal
+const MAX_STICKY_VOTES: usize = 100;
+// Maximum number of delegators of a delegate, all loaded when it votes
+const MAX_DELEGATORS: usize = 20;
+// Bits of the reply id of the emission hook of a contract gauge holding the index of the
+// gauge in the proposal, the remaining ones holding the proposal id
+const GAUGE_INDEX_BITS: u32 = 16;
 
 #[cfg_attr(not(feature = "library"), entry_point)]
 pub fn instantiate(
@@ -63,21 +90,16 @@ pub fn instantiate(
             val: "Foundation Emission percentage cannot be greater than 100 %".to_string(),
         });
     }
-    if msg.emission.rewards_pending == 0 {
-        return Err(ContractError::CustomError {
-            val: "Pending rewards should not be zero %".to_string(),
-        });
-    }
-    if msg.emission.distributed_rewards != 0 {
-        return Err(ContractError::CustomError {
-            val: "Distributed rewards should be zero".to_string(),
-        });
-    }
+    validate_emission(&msg.emission)?;
 
-    if msg.emission.emission_rate > Decimal::one() {
-        return Err(ContractError::CustomError {
-            val: "Emission rate cannot be greater one".to_string(),
-        });
+    if let Some(weight_curve) = msg.weight_curve.clone() {
+        validate_weight_curve(&weight_curve)?;
+        WEIGHT_CURVE.save(deps.storage, &weight_curve)?;
+    }
+    VOTING_DECAY.save(deps.storage, &msg.voting_decay)?;
+    if let Some(early_unlock) = msg.early_unlock {
+        validate_early_unlock(&early_unlock)?;
+        EARLY_UNLOCK.save(deps.storage, &early_unlock)?;
     }
 
     // Set Contract version
@@ -87,6 +109,7 @@ pub fn instantiate(
     STATE.save(deps.storage, &state)?;
     EMISSION.save(deps.storage, msg.emission.app_id, &msg.emission)?;
     PROPOSALCOUNT.save(deps.storage, &0)?;
+    POSITIONCOUNT.save(deps.storage, &0)?;
     CSWAP_ID.save(deps.storage, &msg.cswap_id)?;
     ADMIN.set(deps, Some(msg.admin))?;
 
@@ -95,6 +118,56 @@ pub fn instantiate(
         .add_attribute("owner", info.sender))
 }
 
+/// Validates the emission of an app being registered.
+fn validate_emission(emission: &Emission) -> Result<(), ContractError> {
+    if emission.rewards_pending == 0 {
+        return Err(ContractError::CustomError {
+            val: "Pending rewards should not be zero %".to_string(),
+        });
+    }
+    if emission.distributed_rewards != 0 {
+        return Err(ContractError::CustomError {
+            val: "Distributed rewards should be zero".to_string(),
+        });
+    }
+
+    if emission.emission_rate > Decimal::one() {
+        return Err(ContractError::CustomError {
+            val: "Emission rate cannot be greater one".to_string(),
+        });
+    }
+    validate_emission_schedule(&emission.schedule)
+}
+
+/// Validates the parameters of an emission schedule.
+fn validate_emission_schedule(schedule: &EmissionSchedule) -> Result<(), ContractError> {
+    match schedule {
+        EmissionSchedule::Constant => {}
+        EmissionSchedule::Halving { epochs, .. } => {
+            if *epochs == 0 {
+                return Err(ContractError::CustomError {
+                    val: "Halving epochs should not be zero".to_string(),
+                });
+            }
+        }
+        EmissionSchedule::ExponentialDecay { decay_rate, .. } => {
+            if *decay_rate > Decimal::one() {
+                return Err(ContractError::CustomError {
+                    val: "Decay rate cannot be greater one".to_string(),
+                });
+            }
+        }
+        EmissionSchedule::Table { amounts } => {
+            if amounts.is_empty() {
+                return Err(ContractError::CustomError {
+                    val: "Emission table should not be empty".to_string(),
+                });
+            }
+        }
+    }
+    Ok(())
+}
+
 pub fn map_validate(api: &dyn Api, admins: &[String]) -> StdResult<Vec<Addr>> {
     admins.iter().map(|addr| api.addr_validate(addr)).collect()
 }
@@ -110,14 +183,15 @@ pub fn execute(
         ExecuteMsg::VoteProposal {
             app_id,
             proposal_id,
-            extended_pair,
+            gauges,
             ratio,
+            persistent,
         } => {
             //check if app exist
             let app_response = query_app_exists(deps.as_ref(), app_id)?;
 
-            //// get gov token denom for app
-            let gov_token_denom = query_get_asset_data(deps.as_ref(), app_response.gov_token_id)?;
+            //// get gov token denom for app, the CW20 one if set
+            let gov_token_denom = query_gov_lock_denom(deps.as_ref(), app_id)?;
 
             ////check if gov token exist
             if gov_token_denom.is_empty() || app_response.gov_token_id == 0 {
@@ -131,33 +205,45 @@ pub fn execute(
                 info,
                 app_id,
                 proposal_id,
-                extended_pair,
+                gauges,
                 gov_token_denom,
                 ratio,
+                persistent,
             )
         }
         ExecuteMsg::RaiseProposal { app_id } => {
             //check if app exist
             query_app_exists(deps.as_ref(), app_id)?;
 
-            let cswap_id = CSWAP_ID.load(deps.storage)?;
+            //// get gov token denom for app, to re-apply the sticky votes
+            let gov_token_denom = query_gov_lock_denom(deps.as_ref(), app_id)?;
 
-            let mut pools = query_pool_by_app(deps.as_ref(), cswap_id)?;
+            let gauges = query_app_gauges(deps.as_ref(), app_id)?;
 
-            for i in pools.iter_mut() {
-                *i += 1000000;
+            raise_proposal(deps, env, info, app_id, gauges, gov_token_denom)
+        }
+        ExecuteMsg::Bribe { proposal_id, gauge } => {
+            // CHECK IF BRIBE ASSET EXISTS ON-CHAIN
+            let bribe_coin = info.funds[0].clone();
+            let found = query_whitelisted_asset(deps.as_ref(), bribe_coin.denom.clone())?;
+            if !found {
+                return Err(ContractError::CustomError {
+                    val: String::from("Asset not whitelisted on chain"),
+                });
             }
 
-            ////get ext pairs vec from app
-            let mut ext_pairs = query_extended_pair_by_app(deps.as_ref(), app_id)?;
-            ext_pairs.append(&mut pools);
-
-            raise_proposal(deps, env, info, app_id, ext_pairs)
+            bribe_proposal(deps, env, info, proposal_id, gauge, bribe_coin)
         }
-        ExecuteMsg::Bribe {
-            proposal_id,
-            extended_pair,
+        ExecuteMsg::ScheduleBribe {
+            app_id,
+            gauge,
+            epochs,
         } => {
+            if info.funds.len() != 1 {
+                return Err(ContractError::CustomError {
+                    val: "Exactly one coin must be deposited".to_string(),
+                });
+            }
             // CHECK IF BRIBE ASSET EXISTS ON-CHAIN
             let bribe_coin = info.funds[0].clone();
             let found = query_whitelisted_asset(deps.as_ref(), bribe_coin.denom.clone())?;
@@ -167,8 +253,12 @@ pub fn execute(
                 });
             }
 
-            bribe_proposal(deps, env, info, proposal_id, extended_pair, bribe_coin)
+            schedule_bribe(deps, info, app_id, gauge, epochs, bribe_coin)
         }
+        ExecuteMsg::ReclaimScheduledBribe {
+            app_id,
+            scheduled_bribe_id,
+        } => handle_reclaim_scheduled_bribe(deps, info, app_id, scheduled_bribe_id),
         ExecuteMsg::ClaimReward {
             app_id,
             proposal_id,
@@ -178,10 +268,12 @@ pub fn execute(
             app_id,
             locking_period,
             recipient,
+            auto_relock,
         } => {
             let app_response = query_app_exists(deps.as_ref(), app_id)?;
             let gov_token_id = app_response.gov_token_id;
-            let gov_token_denom = query_get_asset_data(deps.as_ref(), gov_token_id)?;
+            // apps with a CW20 gov token lock it through the Receive hook only
+            let gov_token_denom = query_gov_lock_denom(deps.as_ref(), app_id)?;
             if gov_token_denom.is_empty() || gov_token_id == 0 {
                 return Err(ContractError::CustomError {
                     val: "Gov token not found".to_string(),
@@ -194,1433 +286,6724 @@ pub fn execute(
                 });
             }
 
-            handle_lock_nft(deps, env, info, app_id, locking_period, recipient)
+            handle_lock_nft(
+                deps,
+                env,
+                info,
+                app_id,
+                locking_period,
+                recipient,
+                auto_relock.unwrap_or_default(),
+            )
+        }
+        ExecuteMsg::Receive(msg) => handle_receive(deps, env, info, msg),
+        ExecuteMsg::Withdraw {
+            denom,
+            amount,
+            position_ids,
+            recipient,
+        } => handle_withdraw(deps, env, info, denom, amount, position_ids, recipient),
+        ExecuteMsg::WithdrawAll { recipient } => handle_withdraw_all(deps, env, info, recipient),
+        ExecuteMsg::TransferPosition {
+            position_id,
+            recipient,
+            require_acceptance,
+        } => handle_transfer(
+            deps,
+            env,
+            info,
+            recipient,
+            position_id,
+            require_acceptance.unwrap_or_default(),
+        ),
+        ExecuteMsg::AcceptPosition { position_id } => {
+            handle_accept_position(deps, env, info, position_id)
+        }
+        ExecuteMsg::CancelPositionOffer { position_id } => {
+            handle_cancel_position_offer(deps, info, position_id)
+        }
+        ExecuteMsg::StartUnlock { position_id } => {
+            handle_start_unlock(deps, env, info, position_id)
+        }
+        ExecuteMsg::EarlyWithdraw { position_id } => {
+            handle_early_withdraw(deps, env, info, position_id)
+        }
+        ExecuteMsg::ExtendLock {
+            position_id,
+            locking_period,
+        } => handle_extend_lock(deps, env, info, position_id, locking_period),
+        ExecuteMsg::SplitPosition {
+            position_id,
+            amount,
+        } => handle_split_position(deps, env, info, position_id, amount),
+        ExecuteMsg::MergePositions { position_ids } => {
+            handle_merge_positions(deps, env, info, position_ids)
         }
-        ExecuteMsg::Withdraw { denom } => handle_withdraw(deps, env, info, denom),
         ExecuteMsg::Rebase { proposal_id } => calculate_rebase_reward(deps, env, info, proposal_id),
+        ExecuteMsg::RegisterContractGauge { app_id, address } => {
+            handle_register_contract_gauge(deps, info, app_id, address)
+        }
+        ExecuteMsg::RemoveContractGauge { app_id, address } => {
+            handle_remove_contract_gauge(deps, info, app_id, address)
+        }
+        ExecuteMsg::Delegate {
+            app_id,
+            delegate,
+            amount,
+            delegation_period,
+        } => {
+            let gov_token_denom = query_gov_lock_denom(deps.as_ref(), app_id)?;
+            handle_delegate(
+                deps,
+                env,
+                info,
+                gov_token_denom,
+                delegate,
+                amount,
+                delegation_period,
+            )
+        }
+        ExecuteMsg::Undelegate { app_id, delegate } => {
+            let gov_token_denom = query_gov_lock_denom(deps.as_ref(), app_id)?;
+            handle_undelegate(deps, env, info, gov_token_denom, delegate)
+        }
+        ExecuteMsg::UpdateDelegationCommission { commission } => {
+            handle_update_delegation_commission(deps, env, info, commission)
+        }
+        ExecuteMsg::ClearStickyVote { app_id } => handle_clear_sticky_vote(deps, info, app_id),
+        ExecuteMsg::KillGauge { proposal_id, gauge } => {
+            handle_kill_gauge(deps, env, info, proposal_id, gauge)
+        }
+        ExecuteMsg::ReviveGauge { proposal_id, gauge } => {
+            handle_revive_gauge(deps, info, proposal_id, gauge)
+        }
+        ExecuteMsg::CancelProposal { proposal_id } => {
+            handle_cancel_proposal(deps, env, info, proposal_id)
+        }
+        ExecuteMsg::ReclaimBribes {
+            proposal_id,
+            roll_over,
+        } => handle_reclaim_bribes(deps, env, info, proposal_id, roll_over.unwrap_or(false)),
     }
 }
 
-pub fn emission_foundation(
+/// Delegates voting power of the vtokens of the governance token to the delegate, for the
+/// proposals raised until the end of the delegation period. A previous delegation to the
+/// same delegate is replaced.
+pub fn handle_delegate(
     deps: DepsMut<ComdexQuery>,
-    _env: Env,
+    env: Env,
     info: MessageInfo,
-    proposal_id: u64,
-) -> Result<Vec<ComdexMessages>, ContractError> {
-    ADMIN.assert_admin(deps.as_ref(), &info.sender)?;
-    //check if active proposal
-    let mut proposal = PROPOSAL.load(deps.storage, proposal_id)?;
-    // check emission already computed and executed
-    if !proposal.emission_completed {
+    denom: String,
+    delegate: String,
+    amount: Uint128,
+    delegation_period: u64,
+) -> Result<Response<ComdexMessages>, ContractError> {
+    if !info.funds.is_empty() {
+        return Err(ContractError::FundsNotAllowed {});
+    }
+    let delegate = deps.api.addr_validate(&delegate)?;
+    if delegate == info.sender {
         return Err(ContractError::CustomError {
-            val: "Emission calculation did not take place to initiate foundation calculation"
-                .to_string(),
+            val: "Cannot delegate to self".to_string(),
         });
     }
-
-    if !info.funds.is_empty() {
+    if amount.is_zero() || delegation_period == 0 {
         return Err(ContractError::CustomError {
-            val: "Funds not accepted".to_string(),
+            val: "Delegated amount and period must be non-zero".to_string(),
         });
     }
-
-    //check if foundation emission has not taken place for the proposal
-    if proposal.foundation_emission_completed {
+    if DELEGATION_INFO
+        .may_load(deps.storage, delegate.clone())?
+        .is_none()
+    {
         return Err(ContractError::CustomError {
-            val: "Emission already distributed".to_string(),
+            val: format!("{} does not accept delegations", delegate),
         });
     }
 
-    let state = STATE.load(deps.storage)?;
-
-    let foundation_addr = state.foundation_addr;
+    let mut delegations = active_delegations(deps.storage, &env, &info.sender, &denom)?;
+    delegations.retain(|delegation| delegation.delegated_to != delegate);
+    delegations.push(Delegation {
+        delegated_to: delegate.clone(),
+        delegated_at: env.block.time,
+        delegation_end_at: env.block.time.plus_seconds(delegation_period),
+        delegated: amount.u128(),
+    });
 
-    if foundation_addr.is_empty() {
+    let voting_power: u128 = VTOKENS
+        .may_load(deps.storage, (info.sender.clone(), &denom))?
+        .unwrap_or_default()
+        .iter()
+        .map(|vtoken| vtoken_voting_power(vtoken, env.block.time).u128())
+        .sum();
+    let total_delegated: u128 = delegations
+        .iter()
+        .map(|delegation| delegation.delegated)
+        .sum();
+    if total_delegated > voting_power {
         return Err(ContractError::CustomError {
-            val: "No foundation address found".to_string(),
+            val: "Delegated amount exceeds the voting power".to_string(),
         });
     }
-    let foundation_emission = proposal.foundation_distributed;
+    save_delegations(deps.storage, &env, &info.sender, &denom, delegations)?;
 
-    //// message to send tokens to foundation_address
-    let emission_msg = ComdexMessages::MsgFoundationEmission {
-        app_id: proposal.app_id,
-        amount: Uint128::from(foundation_emission),
-        foundation_address: foundation_addr,
-    };
-    proposal.foundation_emission_completed = true;
+    Ok(Response::new()
+        .add_attribute("method", "delegate")
+        .add_attribute("delegate", delegate)
+        .add_attribute("amount", amount))
+}
 
-    PROPOSAL.save(deps.storage, proposal_id, &proposal)?;
+/// Removes the delegation of the sender to the delegate, for the proposals raised afterwards.
+pub fn handle_undelegate(
+    deps: DepsMut<ComdexQuery>,
+    env: Env,
+    info: MessageInfo,
+    denom: String,
+    delegate: String,
+) -> Result<Response<ComdexMessages>, ContractError> {
+    if !info.funds.is_empty() {
+        return Err(ContractError::FundsNotAllowed {});
+    }
+    let delegate = deps.api.addr_validate(&delegate)?;
 
-    Ok(vec![emission_msg])
+    let mut delegations = active_delegations(deps.storage, &env, &info.sender, &denom)?;
+    let count = delegations.len();
+    delegations.retain(|delegation| delegation.delegated_to != delegate);
+    if delegations.len() == count {
+        return Err(ContractError::NotFound {
+            msg: format!("No active delegation to {}", delegate),
+        });
+    }
+    save_delegations(deps.storage, &env, &info.sender, &denom, delegations)?;
+
+    Ok(Response::new()
+        .add_attribute("method", "undelegate")
+        .add_attribute("delegate", delegate))
 }
 
-fn lock_funds(
+/// Sets the commission charged by the sender on the bribes earned by the voting power
+/// delegated to it, from the next proposal raised. Delegations are accepted once set.
+pub fn handle_update_delegation_commission(
     deps: DepsMut<ComdexQuery>,
     env: Env,
-    _app_id: u64,
-    sender: Addr,
-    funds: Coin,
-    locking_period: LockingPeriod,
-) -> Result<(), ContractError> {
-    // Load internal state containing locking period details.
-    let mut state = STATE.load(deps.storage)?;
-    if state.min_lock_amount > funds.amount {
+    info: MessageInfo,
+    commission: Decimal,
+) -> Result<Response<ComdexMessages>, ContractError> {
+    if !info.funds.is_empty() {
+        return Err(ContractError::FundsNotAllowed {});
+    }
+    if commission > Decimal::one() {
         return Err(ContractError::CustomError {
-            val: "Lock amount less than minimum lock amount".to_string(),
+            val: "Commission cannot be more than 100 %".to_string(),
         });
     }
-    // Load the locking period and weight
-    let PeriodWeight { period, weight } = get_period(state.clone(), locking_period.clone())?;
-
-    // Create a new Vtoken
-    let new_vtoken = create_vtoken(
+    DELEGATION_INFO.save(
         deps.storage,
-        env.clone(),
-        locking_period,
-        period,
-        weight,
-        funds.clone(),
+        info.sender,
+        &DelegationInfo { commission },
+        env.block.height,
     )?;
 
-    // Loads the NFT, if present.
-    let nft = TOKENS.may_load(deps.storage, sender.clone())?;
-
-    match nft {
-        // NFT already exists
-        Some(_) => {}
-
-        // Create a new NFT
-        None => {
-            state.num_tokens += 1;
-
-            let new_nft = TokenInfo {
-                owner: sender.clone(),
-                token_id: state.num_tokens,
-            };
+    Ok(Response::new()
+        .add_attribute("method", "update_delegation_commission")
+        .add_attribute("commission", commission.to_string()))
+}
 
-            STATE.save(deps.storage, &state)?;
+/// Delegations of the delegator which have not ended yet.
+fn active_delegations(
+    storage: &dyn Storage,
+    env: &Env,
+    delegator: &Addr,
+    denom: &str,
+) -> StdResult<Vec<Delegation>> {
+    Ok(DELEGATED
+        .may_load(storage, (delegator.clone(), denom))?
+        .map(|info| info.delegations)
+        .unwrap_or_default()
+        .into_iter()
+        .filter(|delegation| delegation.delegation_end_at > env.block.time)
+        .collect())
+}
 
-            TOKENS.save(deps.storage, sender.clone(), &new_nft)?;
+/// Saves the delegations of the delegator, keeping the delegators of each delegate in sync.
+fn save_delegations(
+    storage: &mut dyn Storage,
+    env: &Env,
+    delegator: &Addr,
+    denom: &str,
+    delegations: Vec<Delegation>,
+) -> Result<(), ContractError> {
+    let previous = DELEGATED
+        .may_load(storage, (delegator.clone(), denom))?
+        .map(|info| info.delegations)
+        .unwrap_or_default();
+    for delegation in previous.iter() {
+        if delegations
+            .iter()
+            .all(|d| d.delegated_to != delegation.delegated_to)
+        {
+            let key = (delegation.delegated_to.clone(), denom);
+            let mut delegators = DELEGATORS
+                .may_load(storage, key.clone())?
+                .unwrap_or_default();
+            delegators.retain(|address| address != delegator);
+            if delegators.is_empty() {
+                DELEGATORS.remove(storage, key, env.block.height)?;
+            } else {
+                DELEGATORS.save(storage, key, &delegators, env.block.height)?;
+            }
         }
-    };
-
-    // Update VTOKENS
-    VTOKENS.update(
-        deps.storage,
-        (sender, &funds.denom),
-        env.block.height,
-        |el| -> StdResult<Vec<Vtoken>> {
-            // If value exists for given key, then update else create.
-            match el {
-                Some(mut val) => {
-                    val.push(new_vtoken);
-                    Ok(val)
-                }
-
-                None => Ok(vec![new_vtoken]),
+    }
+    for delegation in delegations.iter() {
+        let key = (delegation.delegated_to.clone(), denom);
+        let mut delegators = DELEGATORS
+            .may_load(storage, key.clone())?
+            .unwrap_or_default();
+        if !delegators.contains(delegator) {
+            if delegators.len() >= MAX_DELEGATORS {
+                return Err(ContractError::CustomError {
+                    val: "Too many delegators for the delegate".to_string(),
+                });
             }
-        },
-    )?;
+            delegators.push(delegator.clone());
+            DELEGATORS.save(storage, key, &delegators, env.block.height)?;
+        }
+    }
 
+    if delegations.is_empty() {
+        DELEGATED.remove(storage, (delegator.clone(), denom), env.block.height)?;
+    } else {
+        let total_casted = delegations
+            .iter()
+            .map(|delegation| delegation.delegated)
+            .sum();
+        DELEGATED.save(
+            storage,
+            (delegator.clone(), denom),
+            &UserDelegationInfo {
+                total_casted,
+                delegations,
+            },
+            env.block.height,
+        )?;
+    }
     Ok(())
 }
 
-/// Lock the sent tokens and create corresponding vtokens
-pub fn handle_lock_nft(
+/// Cancels a proposal before its emission, so that a new proposal may be raised
+/// for the app right away. Bribes may then be reclaimed by their depositors.
+pub fn handle_cancel_proposal(
     deps: DepsMut<ComdexQuery>,
     env: Env,
     info: MessageInfo,
-    app_id: u64,
-    locking_period: LockingPeriod,
-    recipient: Option<Addr>,
+    proposal_id: u64,
 ) -> Result<Response<ComdexMessages>, ContractError> {
-    // Only allow a single denomination
-    if info.funds.is_empty() {
-        return Err(ContractError::InsufficientFunds { funds: 0 });
-    } else if info.funds.len() > 1 {
-        return Err(ContractError::CustomError {
-            val: String::from("Multiple denominations are not supported as yet."),
-        });
+    ADMIN.assert_admin(deps.as_ref(), &info.sender)?;
+    if !info.funds.is_empty() {
+        return Err(ContractError::FundsNotAllowed {});
     }
 
-    if info.funds[0].amount.is_zero() {
-        return Err(ContractError::InsufficientFunds { funds: 0 });
-    }
-    if let Some(recipient_address) = recipient {
-        deps.api.addr_validate(recipient_address.as_str())?;
-        lock_funds(
-            deps,
-            env,
-            app_id,
-            recipient_address,
-            info.funds[0].clone(),
-            locking_period,
-        )?;
-    } else {
-        lock_funds(
-            deps,
-            env,
-            app_id,
-            info.sender.clone(),
-            info.funds[0].clone(),
-            locking_period,
-        )?;
+    let mut proposal = PROPOSAL.load(deps.storage, proposal_id)?;
+    transition_proposal(&mut proposal, ProposalStatus::Cancelled, env.block.time)?;
+    PROPOSAL.save(deps.storage, proposal_id, &proposal)?;
+    if APPCURRENTPROPOSAL.may_load(deps.storage, proposal.app_id)? == Some(proposal_id) {
+        APPCURRENTPROPOSAL.remove(deps.storage, proposal.app_id);
     }
 
     Ok(Response::new()
-        .add_attribute("action", "lock")
-        .add_attribute("from", info.sender))
+        .add_attribute("method", "cancel_proposal")
+        .add_attribute("proposal_id", proposal_id.to_string()))
 }
 
-/// Create a new Vtoken with the given period, weight, funds.
-fn create_vtoken(
-    storage: &mut dyn Storage,
+/// Kills a gauge of a proposal before its emission. The votes cast on the gauge are left
+/// out of the emission, its bribes become refundable and the new proposals of the app
+/// leave it out until it is revived.
+pub fn handle_kill_gauge(
+    deps: DepsMut<ComdexQuery>,
     env: Env,
-    locking_period: LockingPeriod,
-    period: u64,
-    weight: Decimal,
-    funds: Coin,
-) -> Result<Vtoken, ContractError> {
-    // Create the vtoken
-    let mut vdenom = String::from("v");
-    vdenom.push_str(&funds.denom);
-
-    let amount = weight * funds.amount;
-
-    update_denom_supply(
-        storage,
-        env.clone(),
-        &funds.denom,
-        amount.u128(),
-        funds.amount.u128(),
-        true,
-    )?;
-
-    Ok(Vtoken {
-        token: funds.clone(),
-        vtoken: Coin {
-            denom: vdenom,
-            amount,
-        },
-        period: locking_period,
-        start_time: env.block.time,
-        end_time: env.block.time.plus_seconds(period),
-        status: Status::Locked,
-    })
-}
+    info: MessageInfo,
+    proposal_id: u64,
+    gauge: GaugeTarget,
+) -> Result<Response<ComdexMessages>, ContractError> {
+    ADMIN.assert_admin(deps.as_ref(), &info.sender)?;
+    if !info.funds.is_empty() {
+        return Err(ContractError::FundsNotAllowed {});
+    }
 
-/// Update the SUPPLY map for the total supply for vtokens and the corresponding
-/// tokens locked.
-fn update_denom_supply(
-    storage: &mut dyn Storage,
-    env: Env,
-    denom: &str,
-    vquantity: u128,
-    quantity: u128,
-    add: bool,
-) -> Result<(), ContractError> {
-    // Load the total supply in the for the given denom
-    let denom_supply = SUPPLY.may_load(storage, denom)?;
-    if denom_supply.is_none() && !add {
-        return Err(ContractError::NotFound {
-            msg: "vTokens don't exist for the given denom".to_string(),
+    let mut proposal = PROPOSAL.load(deps.storage, proposal_id)?;
+    if !matches!(
+        proposal_status(&proposal, env.block.time),
+        ProposalStatus::Active | ProposalStatus::VotingEnded
+    ) {
+        return Err(ContractError::CustomError {
+            val: "Gauges can only be killed before the emission of the proposal".to_string(),
         });
     }
-    // Create new struct if not present in SUPPLY
-    let mut denom_supply_struct = denom_supply.unwrap_or(TokenSupply {
-        token: 0,
-        vtoken: 0,
-    });
-
-    if add {
-        denom_supply_struct.vtoken += vquantity;
-        denom_supply_struct.token += quantity;
-    } else {
-        if denom_supply_struct.vtoken < vquantity {
-            return Err(ContractError::InsufficientFunds {
-                funds: denom_supply_struct.vtoken,
-            });
-        } else if denom_supply_struct.token < quantity {
-            return Err(ContractError::InsufficientFunds {
-                funds: denom_supply_struct.token,
-            });
-        }
-
-        denom_supply_struct.vtoken -= vquantity;
-        denom_supply_struct.token -= quantity;
+    if !proposal.gauges.contains(&gauge) {
+        return Err(ContractError::CustomError {
+            val: "Invalid gauge".to_string(),
+        });
     }
+    if proposal.killed_gauges.contains(&gauge) {
+        return Err(ContractError::CustomError {
+            val: "Gauge is already killed".to_string(),
+        });
+    }
+    proposal.killed_gauges.push(gauge.clone());
+    PROPOSAL.save(deps.storage, proposal_id, &proposal)?;
+    KILLED_GAUGES.save(deps.storage, (proposal.app_id, gauge), &true)?;
 
-    SUPPLY.save(storage, denom, &denom_supply_struct, env.block.height)?;
-
-    Ok(())
+    Ok(Response::new()
+        .add_attribute("method", "kill_gauge")
+        .add_attribute("proposal_id", proposal_id.to_string()))
 }
 
-/// Handles the withdrawal of tokens after completion of locking period.
-pub fn handle_withdraw(
+/// Revives a killed gauge, for the new proposals of the app and for the proposal it was
+/// killed on if its emission is still to be executed.
+pub fn handle_revive_gauge(
     deps: DepsMut<ComdexQuery>,
-    env: Env,
     info: MessageInfo,
-    denom: String,
+    proposal_id: u64,
+    gauge: GaugeTarget,
 ) -> Result<Response<ComdexMessages>, ContractError> {
+    ADMIN.assert_admin(deps.as_ref(), &info.sender)?;
     if !info.funds.is_empty() {
         return Err(ContractError::FundsNotAllowed {});
     }
-    // Load the token
-    let vtokens = VTOKENS.may_load(deps.storage, (info.sender.clone(), &denom))?;
-    if vtokens.is_none() {
-        return Err(ContractError::NotFound {
-            msg: format!("No tokens found for {:?}", denom),
-        });
-    }
-
-    let mut vtokens_denom = vtokens.unwrap();
-
-    // Retrieve unlocked tokens with the given locking period
-    let vtokens: Vec<(usize, &Vtoken)> = vtokens_denom
-        .iter()
-        .enumerate()
-        .filter(|s| s.1.end_time < env.block.time)
-        .collect();
 
-    // No unlocked tokens
-    if vtokens.is_empty() {
+    let mut proposal = PROPOSAL.load(deps.storage, proposal_id)?;
+    if !KILLED_GAUGES.has(deps.storage, (proposal.app_id, gauge.clone())) {
         return Err(ContractError::NotFound {
-            msg: format!("No unlocked tokens found for {:?}", denom),
+            msg: "Gauge is not killed".to_string(),
         });
     }
-
-    // Calculate total withdrawable amount and remove the corresponding VToken
-    let mut withdrawable = 0u128;
-
-    let mut vwithdrawable = 0u128;
-    let mut indices: Vec<usize> = vec![];
-    for (index, vtoken) in vtokens {
-        withdrawable += vtoken.token.amount.u128();
-        vwithdrawable += vtoken.vtoken.amount.u128();
-        indices.push(index);
-    }
-    for index in indices.into_iter().rev() {
-        vtokens_denom.remove(index);
+    KILLED_GAUGES.remove(deps.storage, (proposal.app_id, gauge.clone()));
+    if !is_emission_executed(&proposal) && proposal.status != ProposalStatus::Cancelled {
+        proposal.killed_gauges.retain(|killed| *killed != gauge);
+        PROPOSAL.save(deps.storage, proposal_id, &proposal)?;
     }
 
-    // Update VTOKENS
-    if vtokens_denom.is_empty() {
-        VTOKENS.remove(
-            deps.storage,
-            (info.sender.clone(), &denom),
-            env.block.height,
-        )?;
-    } else {
-        VTOKENS.save(
-            deps.storage,
-            (info.sender.clone(), &denom),
-            &vtokens_denom,
-            env.block.height,
-        )?;
-    };
-
-    // Reduce the total supply
-    update_denom_supply(
-        deps.storage,
-        env.clone(),
-        &denom,
-        vwithdrawable,
-        withdrawable,
-        false,
-    )?;
-
     Ok(Response::new()
-        .add_message(BankMsg::Send {
-            to_address: info.sender.to_string(),
-            amount: vec![Coin {
-                denom,
-                amount: Uint128::from(withdrawable),
-            }],
-        })
-        .add_attribute("action", "Withdraw")
-        .add_attribute("from", info.sender))
-}
-
-/// Given the locking period, retrieves the `period` and `weight`.
-fn get_period(state: State, locking_period: LockingPeriod) -> Result<PeriodWeight, ContractError> {
-    Ok(match locking_period {
-        LockingPeriod::T1 => state.t1,
-        LockingPeriod::T2 => state.t2,
-    })
+        .add_attribute("method", "revive_gauge")
+        .add_attribute("proposal_id", proposal_id.to_string()))
 }
 
-/// Handles the transfer of vtokens between users
-pub fn handle_transfer(
+/// Refunds the bribes deposited by the sender on a cancelled proposal, or on the gauges
+/// of a proposal that received no votes once its emission is executed. The bribes may
+/// instead be rolled over to the same gauges of the active proposal of the app.
+pub fn handle_reclaim_bribes(
     deps: DepsMut<ComdexQuery>,
     env: Env,
     info: MessageInfo,
-    recipient: String,
-    locking_period: LockingPeriod,
-    denom: String,
+    proposal_id: u64,
+    roll_over: bool,
 ) -> Result<Response<ComdexMessages>, ContractError> {
     if !info.funds.is_empty() {
         return Err(ContractError::FundsNotAllowed {});
     }
-    let recipient = deps.api.addr_validate(&recipient)?;
 
-    // Load the sender denom that needs to be transferred
-    let sender_vtokens = VTOKENS.may_load(deps.storage, (info.sender.clone(), &denom))?;
+    let proposal = PROPOSAL.load(deps.storage, proposal_id)?;
+    let cancelled = proposal.status == ProposalStatus::Cancelled;
+    let emission_executed = is_emission_executed(&proposal);
 
-    if sender_vtokens.is_none() {
-        return Err(ContractError::NotFound {
-            msg: format!("No tokens found for {:?}", denom),
-        });
+    // bribes on gauges with votes belong to their voters, unless the gauge was killed
+    let deposits = BRIBE_DEPOSITS
+        .may_load(deps.storage, (proposal_id, info.sender.clone()))?
+        .unwrap_or_default();
+    let mut reclaimable: Vec<BribeDeposit> = vec![];
+    let mut kept: Vec<BribeDeposit> = vec![];
+    for deposit in deposits {
+        let votes = PROPOSALVOTE
+            .may_load(deps.storage, (proposal_id, deposit.gauge.clone()))?
+            .unwrap_or_default();
+        if cancelled
+            || proposal.killed_gauges.contains(&deposit.gauge)
+            || (emission_executed && votes.is_zero())
+        {
+            reclaimable.push(deposit);
+        } else {
+            kept.push(deposit);
+        }
     }
-    let sender_denom_vtokens = sender_vtokens.unwrap();
-
-    // Load tokens with given locking period
-    let sender_vtokens_to_transfer: Vec<&Vtoken> = sender_denom_vtokens
-        .iter()
-        .filter(|s| s.period == locking_period)
-        .collect();
-
-    if sender_vtokens_to_transfer.is_empty() {
+    if reclaimable.is_empty() {
+        if !cancelled && !emission_executed {
+            return Err(ContractError::CustomError {
+                val: "Bribes are only reclaimable once the proposal is cancelled or its emission executed"
+                    .to_string(),
+            });
+        }
         return Err(ContractError::NotFound {
-            msg: format!(
-                "No tokens found for denom: {:?} and locking period: {:?}",
-                denom, locking_period
-            ),
+            msg: "No reclaimable bribes deposited on the proposal".to_string(),
         });
     }
-
-    {
-        // Load the recipients VTOKENS
-        let recipient_vtokens =
-            VTOKENS.may_load(deps.as_ref().storage, (recipient.clone(), &denom))?;
-
-        let mut recipient_vtokens = if let Some(val) = recipient_vtokens {
-            val
-        } else {
-            vec![]
-        };
-
-        // Extend the recipient vtokens with the sender vtokens
-        for vtoken in sender_vtokens_to_transfer {
-            recipient_vtokens.push(vtoken.to_owned())
-        }
-
-        VTOKENS.save(
-            deps.storage,
-            (recipient.clone(), &denom),
-            &recipient_vtokens,
-            env.block.height,
-        )?;
+    if kept.is_empty() {
+        BRIBE_DEPOSITS.remove(deps.storage, (proposal_id, info.sender.clone()));
+    } else {
+        BRIBE_DEPOSITS.save(deps.storage, (proposal_id, info.sender.clone()), &kept)?;
     }
 
-    {
-        // Remaining vtokens are saved to sender's VTOKENS
-        let sender_vtokens_remaining: Vec<Vtoken> = sender_denom_vtokens
-            .into_iter()
-            .filter(|el| !(el.period == locking_period))
-            .collect();
-
-        if sender_vtokens_remaining.is_empty() {
-            VTOKENS.remove(
-                deps.storage,
-                (info.sender.clone(), &denom),
-                env.block.height,
-            )?;
-        } else {
-            VTOKENS.save(
-                deps.storage,
-                (info.sender.clone(), &denom),
-                &sender_vtokens_remaining,
-                env.block.height,
-            )?;
+    // active proposal of the app receiving the rolled over bribes
+    let next_proposal = if roll_over {
+        let next_proposal_id = APPCURRENTPROPOSAL
+            .may_load(deps.storage, proposal.app_id)?
+            .unwrap_or(0);
+        match PROPOSAL.may_load(deps.storage, next_proposal_id)? {
+            Some(next_proposal)
+                if next_proposal_id != proposal_id
+                    && proposal_status(&next_proposal, env.block.time)
+                        == ProposalStatus::Active =>
+            {
+                Some((next_proposal_id, next_proposal))
+            }
+            _ => {
+                return Err(ContractError::CustomError {
+                    val: "No active proposal to roll the bribes over to".to_string(),
+                })
+            }
         }
-    }
+    } else {
+        None
+    };
 
-    // Load the recipients nft
-    let recipient_nft = TOKENS.may_load(deps.as_ref().storage, recipient.clone())?;
+    let mut refund: Vec<Coin> = vec![];
+    for deposit in reclaimable {
+        let mut bribes = BRIBES_BY_PROPOSAL
+            .may_load(deps.storage, (proposal_id, deposit.gauge.clone()))?
+            .unwrap_or_default();
+        for coin in deposit.coins.iter() {
+            if let Some(bribe) = bribes.iter_mut().find(|bribe| bribe.denom == coin.denom) {
+                bribe.amount -= coin.amount;
+            }
+        }
+        bribes.retain(|bribe| !bribe.amount.is_zero());
+        BRIBES_BY_PROPOSAL.save(deps.storage, (proposal_id, deposit.gauge.clone()), &bribes)?;
 
-    match recipient_nft {
-        Some(_) => {}
-        None => {
-            // Crate a new NFT
-            let mut state = STATE.load(deps.as_ref().storage)?;
-            state.num_tokens += 1;
-            STATE.save(deps.storage, &state)?;
-            TOKENS.save(
-                deps.storage,
-                recipient.clone(),
-                &TokenInfo {
-                    owner: recipient.clone(),
-                    token_id: state.num_tokens,
-                },
-            )?;
+        match &next_proposal {
+            // gauges missing from the active proposal are refunded
+            Some((next_proposal_id, next_proposal))
+                if next_proposal.gauges.contains(&deposit.gauge) =>
+            {
+                for coin in deposit.coins {
+                    deposit_bribe(
+                        deps.storage,
+                        *next_proposal_id,
+                        &info.sender,
+                        &deposit.gauge,
+                        coin,
+                    )?;
+                }
+            }
+            _ => {
+                for coin in deposit.coins {
+                    add_coin(&mut refund, coin);
+                }
+            }
         }
-    };
+    }
 
-    Ok(Response::new()
-        .add_attribute("action", "transfer")
-        .add_attribute("from", info.sender)
-        .add_attribute("to", recipient))
+    let mut response = Response::new()
+        .add_attribute("method", "reclaim_bribes")
+        .add_attribute("proposal_id", proposal_id.to_string());
+    if !refund.is_empty() {
+        response = response.add_message(BankMsg::Send {
+            to_address: info.sender.to_string(),
+            amount: refund,
+        });
+    }
+    Ok(response)
 }
 
-pub fn bribe_proposal(
+/// Registers a contract as a gauge of the app, voted upon from the next proposal on.
+pub fn handle_register_contract_gauge(
     deps: DepsMut<ComdexQuery>,
-    env: Env,
     info: MessageInfo,
-    proposal_id: u64,
-    extended_pair: u64,
-    bribe_coin: Coin,
+    app_id: u64,
+    address: String,
 ) -> Result<Response<ComdexMessages>, ContractError> {
-    // bribe denom should be a single coin
-    if info.funds.is_empty() {
-        return Err(ContractError::InsufficientFunds { funds: 0 });
-    } else if info.funds.len() > 1 {
+    ADMIN.assert_admin(deps.as_ref(), &info.sender)?;
+    if !info.funds.is_empty() {
+        return Err(ContractError::FundsNotAllowed {});
+    }
+    query_app_exists(deps.as_ref(), app_id)?;
+
+    let address = deps.api.addr_validate(&address)?;
+    let mut contract_gauges = CONTRACT_GAUGES
+        .may_load(deps.storage, app_id)?
+        .unwrap_or_default();
+    if contract_gauges.contains(&address) {
         return Err(ContractError::CustomError {
-            val: String::from("Multiple denominations are not supported"),
+            val: "Contract gauge already registered".to_string(),
         });
     }
+    contract_gauges.push(address.clone());
+    CONTRACT_GAUGES.save(deps.storage, app_id, &contract_gauges)?;
 
-    // bribe coin should not have zero amount
-    if info.funds[0].amount.is_zero() {
-        return Err(ContractError::InsufficientFunds { funds: 0 });
+    Ok(Response::new()
+        .add_attribute("method", "register_contract_gauge")
+        .add_attribute("app_id", app_id.to_string())
+        .add_attribute("address", address))
+}
+
+/// Removes a contract gauge of the app. Proposals already raised keep the gauge.
+pub fn handle_remove_contract_gauge(
+    deps: DepsMut<ComdexQuery>,
+    info: MessageInfo,
+    app_id: u64,
+    address: String,
+) -> Result<Response<ComdexMessages>, ContractError> {
+    ADMIN.assert_admin(deps.as_ref(), &info.sender)?;
+    if !info.funds.is_empty() {
+        return Err(ContractError::FundsNotAllowed {});
     }
 
-    //check if active proposal
-    let proposal = PROPOSAL.load(deps.storage, proposal_id)?;
-    if proposal.voting_end_time < env.block.time {
-        return Err(ContractError::CustomError {
-            val: "Proposal Bribing Period Ended".to_string(),
+    let address = deps.api.addr_validate(&address)?;
+    let mut contract_gauges = CONTRACT_GAUGES
+        .may_load(deps.storage, app_id)?
+        .unwrap_or_default();
+    if !contract_gauges.contains(&address) {
+        return Err(ContractError::NotFound {
+            msg: "Contract gauge not found".to_string(),
         });
     }
+    contract_gauges.retain(|gauge| *gauge != address);
+    CONTRACT_GAUGES.save(deps.storage, app_id, &contract_gauges)?;
 
-    // check if ext_pair param exist in extended pair list to vote for
+    Ok(Response::new()
+        .add_attribute("method", "remove_contract_gauge")
+        .add_attribute("app_id", app_id.to_string())
+        .add_attribute("address", address))
+}
 
-    let extended_pairs = proposal.extended_pair;
+/// Foundation share of the emission of a proposal, distributed as part of `emission`.
+pub fn emission_foundation(
+    deps: DepsMut<ComdexQuery>,
+    _env: Env,
+    info: MessageInfo,
+    proposal_id: u64,
+) -> Result<Vec<ComdexMessages>, ContractError> {
+    //check if active proposal
+    let proposal = PROPOSAL.load(deps.storage, proposal_id)?;
+    // check emission already computed and executed
+    if !is_emission_executed(&proposal) {
+        return Err(ContractError::CustomError {
+            val: "Emission calculation did not take place to initiate foundation calculation"
+                .to_string(),
+        });
+    }
 
-    match extended_pairs.binary_search(&extended_pair) {
-        Ok(_) => (),
-        Err(_) => {
-            return Err(ContractError::CustomError {
-                val: "Invalid Extended pair".to_string(),
-            })
-        }
+    if !info.funds.is_empty() {
+        return Err(ContractError::CustomError {
+            val: "Funds not accepted".to_string(),
+        });
     }
 
-    // UPDATE BRIBE FOR PROPOSAL (IF EXISTS THEN UPDATE ELSE APPEND)
-    let mut existing_bribes = BRIBES_BY_PROPOSAL
-        .may_load(deps.storage, (proposal_id, extended_pair))?
-        .unwrap_or_default();
+    let state = STATE.load(deps.storage)?;
 
-    if let Some(coin) = existing_bribes
-        .iter_mut()
-        .find(|c| c.denom == bribe_coin.denom)
-    {
-        coin.amount += bribe_coin.amount;
-    } else {
-        existing_bribes.push(bribe_coin);
+    let foundation_addr = state.foundation_addr;
+
+    if foundation_addr.is_empty() {
+        return Err(ContractError::CustomError {
+            val: "No foundation address found".to_string(),
+        });
     }
+    let foundation_emission = proposal.foundation_distributed;
 
-    BRIBES_BY_PROPOSAL.save(deps.storage, (proposal_id, extended_pair), &existing_bribes)?;
-    Ok(Response::new().add_attribute("method", "bribe"))
+    //// message to send tokens to foundation_address
+    let emission_msg = ComdexMessages::MsgFoundationEmission {
+        app_id: proposal.app_id,
+        amount: Uint128::from(foundation_emission),
+        foundation_address: foundation_addr,
+    };
+
+    Ok(vec![emission_msg])
 }
 
-pub fn claim_rewards(
+fn lock_funds(
     deps: DepsMut<ComdexQuery>,
     env: Env,
-    info: MessageInfo,
     app_id: u64,
-    proposal_id: Option<u64>,
-) -> Result<Response<ComdexMessages>, ContractError> {
-    if !info.funds.is_empty() {
-        return Err(ContractError::FundsNotAllowed {});
+    sender: Addr,
+    funds: Coin,
+    locking_period: LockingPeriod,
+    auto_relock: bool,
+) -> Result<Vtoken, ContractError> {
+    // Load the app config containing locking period details.
+    let app_config = load_app_config(deps.storage, app_id)?;
+    if app_config.min_lock_amount > funds.amount {
+        return Err(ContractError::CustomError {
+            val: "Lock amount less than minimum lock amount".to_string(),
+        });
     }
+    // Load the locking period and weight
+    let period_weight = get_period(deps.storage, app_config, locking_period.clone())?;
 
-    let all_proposals = match COMPLETEDPROPOSALS.may_load(deps.storage, app_id)? {
-        Some(val) => val,
-        None => vec![],
-    };
-    if let Some(proposal_id) = proposal_id {
-        if !all_proposals.contains(&proposal_id) {
-            return Err(ContractError::CustomError {
-                val: String::from("Proposal not completed"),
-            });
-        }
-
-        let voters_claimed = VOTERS_CLAIM
-            .load(deps.storage, (info.sender.clone(), proposal_id))
-            .unwrap_or_default();
-
-        if voters_claimed {
-            return Err(ContractError::CustomError {
-                val: String::from("Already claimed"),
-            });
-        }
-
-        let mut bribe_coin =
-            calculate_bribe_reward_proposal(deps.as_ref(), env, info.clone(), proposal_id)?;
-        VOTERS_CLAIM.save(deps.storage, (info.sender.clone(), proposal_id), &true)?;
-        let mut claimed_proposal =
-            match VOTERS_CLAIMED_PROPOSALS.may_load(deps.storage, info.sender.clone())? {
-                Some(val) => val,
-                None => vec![],
-            };
-        claimed_proposal.push(proposal_id);
-        claimed_proposal.sort();
-        VOTERS_CLAIMED_PROPOSALS.save(deps.storage, info.sender.clone(), &claimed_proposal)?;
-        bribe_coin.sort_by_key(|element| element.denom.clone());
-        bribe_coin.retain(|coin| !coin.amount.is_zero());
-
-        return Ok(Response::new()
-            .add_attribute("method", "External Incentive Claimed")
-            .add_message(BankMsg::Send {
-                to_address: info.sender.to_string(),
-                amount: bribe_coin,
-            }));
-    }
+    // Create a new Vtoken
+    let new_vtoken = create_vtoken(
+        deps.storage,
+        env.clone(),
+        app_id,
+        locking_period,
+        period_weight,
+        funds,
+        auto_relock,
+    )?;
+    open_position(deps.storage, env, &sender, &new_vtoken)?;
 
-    let (mut bribe_coins, claimed_proposals) =
-        calculate_bribe_reward(deps.as_ref(), env, info.clone(), all_proposals, app_id)?;
+    Ok(new_vtoken)
+}
 
-    VOTERS_CLAIMED_PROPOSALS.save(deps.storage, info.sender.clone(), &claimed_proposals)?;
-    for proposal in claimed_proposals {
-        VOTERS_CLAIM.save(deps.storage, (info.sender.clone(), proposal), &true)?;
+/// Locks the funds in a new position with the locking period and weight of an
+/// existing position, without re-checking them against the current weight curve.
+fn relock_funds(
+    deps: DepsMut<ComdexQuery>,
+    env: Env,
+    sender: Addr,
+    funds: Coin,
+    position: &Vtoken,
+) -> Result<Vtoken, ContractError> {
+    let app_config = load_app_config(deps.storage, position.app_id)?;
+    if app_config.min_lock_amount > funds.amount {
+        return Err(ContractError::CustomError {
+            val: "Lock amount less than minimum lock amount".to_string(),
+        });
     }
+    let period_weight = match position.period {
+        LockingPeriod::Custom { duration } => PeriodWeight {
+            period: duration,
+            weight: position_weight(position),
+        },
+        _ => get_period(deps.storage, app_config, position.period.clone())?,
+    };
 
-    if !bribe_coins.is_empty() {
-        bribe_coins.sort_by_key(|element| element.denom.clone());
-
-        // remove all coin having amount as 0
-        bribe_coins.retain(|coin| !coin.amount.is_zero());
+    let new_vtoken = create_vtoken(
+        deps.storage,
+        env.clone(),
+        position.app_id,
+        position.period.clone(),
+        period_weight,
+        funds,
+        position.auto_relock,
+    )?;
+    open_position(deps.storage, env, &sender, &new_vtoken)?;
 
-        Ok(Response::new()
-            .add_attribute("method", "External Incentive Claimed")
-            .add_message(BankMsg::Send {
-                to_address: info.sender.to_string(),
-                amount: bribe_coins,
-            }))
-    } else {
-        Err(ContractError::CustomError {
-            val: String::from("No rewards to claim."),
-        })
-    }
+    Ok(new_vtoken)
 }
 
-pub fn calculate_bribe_reward(
-    deps: Deps<ComdexQuery>,
-    _env: Env,
-    info: MessageInfo,
-    all_proposals: Vec<u64>,
-    _app_id: u64,
-) -> Result<(Vec<Coin>, Vec<u64>), ContractError> {
-    let mut bribe_coins: Vec<Coin> = vec![];
-    let mut claimed_proposal =
-        match VOTERS_CLAIMED_PROPOSALS.may_load(deps.storage, info.sender.clone())? {
-            Some(val) => val,
-            None => vec![],
-        };
-    for proposalid in all_proposals {
-        if claimed_proposal.contains(&proposalid) {
-            continue;
-        }
-        let vote = match VOTERSPROPOSAL.may_load(deps.storage, (info.sender.clone(), proposalid))? {
-            Some(val) => val,
-            None => continue,
-        };
+/// Saves the new position of the sender, creating their NFT if needed.
+fn open_position(
+    storage: &mut dyn Storage,
+    env: Env,
+    sender: &Addr,
+    new_vtoken: &Vtoken,
+) -> Result<(), ContractError> {
+    // Loads the NFT, if present.
+    let nft = TOKENS.may_load(storage, sender.clone())?;
 
-        for pair in vote.votes {
-            let total_vote_weight = PROPOSALVOTE
-                .load(deps.storage, (proposalid, pair.extended_pair))?
-                .u128();
+    match nft {
+        // NFT already exists
+        Some(_) => {}
 
-            let total_bribe = match BRIBES_BY_PROPOSAL
-                .may_load(deps.storage, (proposalid, pair.extended_pair))?
-            {
-                Some(val) => val,
-                None => vec![],
+        // Create a new NFT
+        None => {
+            let mut state = STATE.load(storage)?;
+            state.num_tokens += 1;
+
+            let new_nft = TokenInfo {
+                owner: sender.clone(),
+                token_id: state.num_tokens,
             };
 
-            let claimable_bribe: Vec<Coin> = total_bribe
-                .iter()
-                .map(|coin| {
-                    let claimable_amount = (Decimal::new(Uint128::from(pair.vote_weight))
-                        .div(Decimal::new(Uint128::from(total_vote_weight))))
-                    .mul(coin.amount);
-                    Coin {
-                        amount: claimable_amount,
-                        denom: coin.denom.clone(),
-                    }
-                })
-                .collect();
+            STATE.save(storage, &state)?;
 
-            for bribe_deposited in claimable_bribe {
-                if let Some(pivot) = bribe_coins
-                    .iter_mut()
-                    .find(|p| bribe_deposited.denom == p.denom)
-                {
-                    pivot.amount += bribe_deposited.amount;
-                } else {
-                    bribe_coins.push(bribe_deposited);
-                }
-            }
+            TOKENS.save(storage, sender.clone(), &new_nft)?;
         }
-        claimed_proposal.push(proposalid);
-        claimed_proposal.sort();
-    }
-
-    //// send bank message to band
-
-    Ok((bribe_coins, claimed_proposal))
-}
-
-pub fn calculate_bribe_reward_proposal(
-    deps: Deps<ComdexQuery>,
-    _env: Env,
-    info: MessageInfo,
-    proposal_id: u64,
-) -> Result<Vec<Coin>, ContractError> {
-    let mut bribe_coins: Vec<Coin> = vec![];
-
-    let vote = VOTERSPROPOSAL.load(deps.storage, (info.sender, proposal_id))?;
-
-    for pair in vote.votes {
-        let total_vote_weight = PROPOSALVOTE
-            .load(deps.storage, (proposal_id, pair.extended_pair))?
-            .u128();
-
-        let total_bribe = BRIBES_BY_PROPOSAL
-            .may_load(deps.storage, (proposal_id, pair.extended_pair))?
-            .unwrap_or_default();
+    };
 
-        let claimable_bribe: Vec<Coin> = total_bribe
-            .iter()
-            .map(|coin| {
-                let claimable_amount = (Decimal::new(Uint128::from(pair.vote_weight))
-                    .div(Decimal::new(Uint128::from(total_vote_weight))))
-                .mul(coin.amount);
-                Coin {
-                    amount: claimable_amount,
-                    denom: coin.denom.clone(),
-                }
-            })
-            .collect();
+    // Save the new position
+    save_position(storage, env, sender, new_vtoken)?;
 
-        for bribe_deposited in claimable_bribe {
-            if let Some(pivot) = bribe_coins
-                .iter_mut()
-                .find(|p| bribe_deposited.denom == p.denom)
-            {
-                pivot.amount += bribe_deposited.amount;
-            } else {
-                bribe_coins.push(bribe_deposited);
-            }
-        }
-    }
-    Ok(bribe_coins)
+    Ok(())
 }
 
-pub fn calculate_rebase_reward(
-    mut deps: DepsMut<ComdexQuery>,
+/// Lock the sent tokens and create corresponding vtokens
+pub fn handle_lock_nft(
+    deps: DepsMut<ComdexQuery>,
     env: Env,
     info: MessageInfo,
-    proposal_id: u64,
+    app_id: u64,
+    locking_period: LockingPeriod,
+    recipient: Option<Addr>,
+    auto_relock: bool,
 ) -> Result<Response<ComdexMessages>, ContractError> {
-    if !info.funds.is_empty() {
-        return Err(ContractError::FundsNotAllowed {});
-    }
-    let proposal = PROPOSAL.load(deps.storage, proposal_id)?;
-    if !proposal.emission_completed {
+    // Only allow a single denomination
+    if info.funds.is_empty() {
+        return Err(ContractError::InsufficientFunds { funds: 0 });
+    } else if info.funds.len() > 1 {
         return Err(ContractError::CustomError {
-            val: "Emission for proposal not completed".to_string(),
+            val: String::from("Multiple denominations are not supported as yet."),
         });
     }
-    let has_rebased = REBASE_CLAIMED
-        .may_load(deps.storage, (info.sender.clone(), proposal_id))?
-        .unwrap_or_default();
 
-    if has_rebased {
-        return Err(ContractError::CustomError {
-            val: "Already claimed rebase".to_string(),
-        });
+    if info.funds[0].amount.is_zero() {
+        return Err(ContractError::InsufficientFunds { funds: 0 });
     }
-    let total_rebase_amount: u128 = proposal.rebase_distributed;
-
-    let app_response = query_app_exists(deps.as_ref(), proposal.app_id)?;
-
-    let gov_token_denom = query_get_asset_data(deps.as_ref(), app_response.gov_token_id)?;
-
+    let vtoken = if let Some(recipient_address) = recipient {
+        deps.api.addr_validate(recipient_address.as_str())?;
+        lock_funds(
+            deps,
+            env,
+            app_id,
+            recipient_address,
+            info.funds[0].clone(),
+            locking_period,
+            auto_relock,
+        )?
+    } else {
+        lock_funds(
+            deps,
+            env,
+            app_id,
+            info.sender.clone(),
+            info.funds[0].clone(),
+            locking_period,
+            auto_relock,
+        )?
+    };
+
+    Ok(Response::new()
+        .add_attribute("action", "lock")
+        .add_attribute("from", info.sender)
+        .add_attribute("position_id", vtoken.id.to_string()))
+}
+
+/// Lock the CW20 tokens sent through the `Receive` hook and create corresponding vtokens
+pub fn handle_receive(
+    deps: DepsMut<ComdexQuery>,
+    env: Env,
+    info: MessageInfo,
+    cw20_msg: Cw20ReceiveMsg,
+) -> Result<Response<ComdexMessages>, ContractError> {
+    match from_json(&cw20_msg.msg)? {
+        Cw20HookMsg::Lock {
+            app_id,
+            locking_period,
+            recipient,
+            auto_relock,
+        } => {
+            query_app_exists(deps.as_ref(), app_id)?;
+
+            // Only the CW20 gov token of the app may be locked
+            match CW20_GOV_TOKENS.may_load(deps.storage, app_id)? {
+                Some(address) if address == info.sender => {}
+                _ => {
+                    return Err(ContractError::CustomError {
+                        val: "Wrong Deposit token".to_string(),
+                    })
+                }
+            }
+            if cw20_msg.amount.is_zero() {
+                return Err(ContractError::InsufficientFunds { funds: 0 });
+            }
+
+            let sender = deps.api.addr_validate(&cw20_msg.sender)?;
+            let owner = match recipient {
+                Some(recipient_address) => {
+                    deps.api.addr_validate(recipient_address.as_str())?;
+                    recipient_address
+                }
+                None => sender.clone(),
+            };
+            let funds = Coin {
+                denom: cw20_denom(&info.sender),
+                amount: cw20_msg.amount,
+            };
+            let vtoken = lock_funds(
+                deps,
+                env,
+                app_id,
+                owner,
+                funds,
+                locking_period,
+                auto_relock.unwrap_or_default(),
+            )?;
+
+            Ok(Response::new()
+                .add_attribute("action", "lock")
+                .add_attribute("from", sender)
+                .add_attribute("position_id", vtoken.id.to_string()))
+        }
+    }
+}
+
+/// Create a new Vtoken with the given period, weight, funds.
+fn create_vtoken(
+    storage: &mut dyn Storage,
+    env: Env,
+    app_id: u64,
+    locking_period: LockingPeriod,
+    period_weight: PeriodWeight,
+    funds: Coin,
+    auto_relock: bool,
+) -> Result<Vtoken, ContractError> {
+    let PeriodWeight { period, weight } = period_weight;
+
+    // Create the vtoken
+    let mut vdenom = String::from("v");
+    vdenom.push_str(&funds.denom);
+
+    let amount = weight * funds.amount;
+
+    update_denom_supply(
+        storage,
+        env.clone(),
+        &funds.denom,
+        amount.u128(),
+        funds.amount.u128(),
+        true,
+    )?;
+
+    let id = POSITIONCOUNT.may_load(storage)?.unwrap_or_default() + 1;
+    POSITIONCOUNT.save(storage, &id)?;
+
+    let vtoken = Vtoken {
+        id,
+        app_id,
+        token: funds.clone(),
+        vtoken: Coin {
+            denom: vdenom,
+            amount,
+        },
+        period: locking_period,
+        start_time: env.block.time,
+        end_time: env.block.time.plus_seconds(period),
+        status: Status::Locked,
+        // Auto-relock positions keep their full weight
+        decay: !auto_relock && VOTING_DECAY.may_load(storage)?.unwrap_or_default(),
+        auto_relock,
+        weight: Some(weight),
+    };
+
+    update_supply_decay(storage, env, &funds.denom, None, Some(&vtoken))?;
+
+    Ok(vtoken)
+}
+
+/// Update the SUPPLY_DECAY map when a decaying vtoken is removed and/or added.
+fn update_supply_decay(
+    storage: &mut dyn Storage,
+    env: Env,
+    denom: &str,
+    removed: Option<&Vtoken>,
+    added: Option<&Vtoken>,
+) -> Result<(), ContractError> {
+    let removed = removed.filter(|vtoken| vtoken.decay);
+    let added = added.filter(|vtoken| vtoken.decay);
+    if removed.is_none() && added.is_none() {
+        return Ok(());
+    }
+
+    // Bring the decay up to the current block time
+    let mut point = SUPPLY_DECAY
+        .may_load(storage, denom)?
+        .unwrap_or(DecayPoint {
+            bias: Uint128::zero(),
+            slope: Decimal::zero(),
+            vtoken: 0,
+            timestamp: env.block.time,
+        });
+    advance_decay_point(storage, denom, &mut point, env.block.time)?;
+
+    if let Some(vtoken) = removed {
+        point.bias = point
+            .bias
+            .saturating_sub(vtoken_voting_power(vtoken, env.block.time));
+        point.vtoken -= vtoken.vtoken.amount.u128();
+
+        // The slope of expired vtokens has already been removed
+        if vtoken.end_time > env.block.time {
+            let slope = vtoken_slope(vtoken);
+            point.slope = if slope < point.slope {
+                point.slope - slope
+            } else {
+                Decimal::zero()
+            };
+            let key = (denom, vtoken.end_time.seconds());
+            let slope_change = SLOPE_CHANGES.load(storage, key)?;
+            if slope_change > slope {
+                SLOPE_CHANGES.save(storage, key, &(slope_change - slope))?;
+            } else {
+                SLOPE_CHANGES.remove(storage, key);
+            }
+        }
+    }
+
+    if let Some(vtoken) = added {
+        point.bias += vtoken_voting_power(vtoken, env.block.time);
+        point.vtoken += vtoken.vtoken.amount.u128();
+
+        if vtoken.end_time > env.block.time {
+            let slope = vtoken_slope(vtoken);
+            point.slope += slope;
+            SLOPE_CHANGES.update(
+                storage,
+                (denom, vtoken.end_time.seconds()),
+                |slope_change| -> StdResult<Decimal> {
+                    Ok(slope_change.unwrap_or_default() + slope)
+                },
+            )?;
+        }
+    }
+
+    SUPPLY_DECAY.save(storage, denom, &point, env.block.height)?;
+
+    Ok(())
+}
+
+/// Update the SUPPLY map for the total supply for vtokens and the corresponding
+/// tokens locked.
+fn update_denom_supply(
+    storage: &mut dyn Storage,
+    env: Env,
+    denom: &str,
+    vquantity: u128,
+    quantity: u128,
+    add: bool,
+) -> Result<(), ContractError> {
+    // Load the total supply in the for the given denom
+    let denom_supply = SUPPLY.may_load(storage, denom)?;
+    if denom_supply.is_none() && !add {
+        return Err(ContractError::NotFound {
+            msg: "vTokens don't exist for the given denom".to_string(),
+        });
+    }
+    // Create new struct if not present in SUPPLY
+    let mut denom_supply_struct = denom_supply.unwrap_or(TokenSupply {
+        token: 0,
+        vtoken: 0,
+    });
+
+    if add {
+        denom_supply_struct.vtoken += vquantity;
+        denom_supply_struct.token += quantity;
+    } else {
+        if denom_supply_struct.vtoken < vquantity {
+            return Err(ContractError::InsufficientFunds {
+                funds: denom_supply_struct.vtoken,
+            });
+        } else if denom_supply_struct.token < quantity {
+            return Err(ContractError::InsufficientFunds {
+                funds: denom_supply_struct.token,
+            });
+        }
+
+        denom_supply_struct.vtoken -= vquantity;
+        denom_supply_struct.token -= quantity;
+    }
+
+    SUPPLY.save(storage, denom, &denom_supply_struct, env.block.height)?;
+
+    Ok(())
+}
+
+/// Saves the position and updates the VTOKENS snapshot of its owner.
+fn save_position(
+    storage: &mut dyn Storage,
+    env: Env,
+    owner: &Addr,
+    vtoken: &Vtoken,
+) -> Result<(), ContractError> {
+    let position = Position {
+        owner: owner.clone(),
+        vtoken: vtoken.clone(),
+    };
+    positions().save(storage, vtoken.id, &position)?;
+    sync_vtokens(storage, env, owner, &vtoken.token.denom)
+}
+
+/// Removes the position and updates the VTOKENS snapshot of its owner.
+fn remove_position(
+    storage: &mut dyn Storage,
+    env: Env,
+    owner: &Addr,
+    vtoken: &Vtoken,
+) -> Result<(), ContractError> {
+    positions().remove(storage, vtoken.id)?;
+    POSITION_OFFERS.remove(storage, vtoken.id);
+    sync_vtokens(storage, env, owner, &vtoken.token.denom)
+}
+
+/// Rebuilds the VTOKENS snapshot of the (owner, denom) from their positions.
+fn sync_vtokens(
+    storage: &mut dyn Storage,
+    env: Env,
+    owner: &Addr,
+    denom: &str,
+) -> Result<(), ContractError> {
+    let vtokens = positions()
+        .idx
+        .owner
+        .prefix((owner.clone(), denom.to_string()))
+        .range(storage, None, None, Order::Ascending)
+        .map(|item| item.map(|(_, position)| position.vtoken))
+        .collect::<StdResult<Vec<Vtoken>>>()?;
+
+    if vtokens.is_empty() {
+        VTOKENS.remove(storage, (owner.clone(), denom), env.block.height)?;
+    } else {
+        VTOKENS.save(storage, (owner.clone(), denom), &vtokens, env.block.height)?;
+    }
+
+    Ok(())
+}
+
+/// Loads the position with the given id, checking that it is owned by the sender.
+fn load_owned_position(
+    storage: &dyn Storage,
+    sender: &Addr,
+    position_id: u64,
+) -> Result<Vtoken, ContractError> {
+    match positions().may_load(storage, position_id)? {
+        Some(position) if position.owner != *sender => Err(ContractError::Unauthorized {}),
+        // positions are all assigned an app by the migration
+        Some(position) if position.vtoken.app_id == 0 => Err(ContractError::CustomError {
+            val: format!("Lock position {} has no app", position_id),
+        }),
+        Some(position) => Ok(position.vtoken),
+        None => Err(ContractError::NotFound {
+            msg: format!("No lock position found for id {}", position_id),
+        }),
+    }
+}
+
+/// Withdraws up to `amount` tokens (all if not specified) from the given unlocked
+/// vtokens of the owner, in order. A partially withdrawn position keeps the
+/// remaining tokens. Returns the amount withdrawn.
+fn withdraw_vtokens(
+    storage: &mut dyn Storage,
+    env: Env,
+    owner: &Addr,
+    denom: &str,
+    vtokens: Vec<Vtoken>,
+    amount: Option<Uint128>,
+) -> Result<Uint128, ContractError> {
+    let mut withdrawable = Uint128::zero();
+    let mut vwithdrawable = Uint128::zero();
+    for vtoken in vtokens {
+        let remaining = match amount {
+            Some(amount) => amount - withdrawable,
+            None => vtoken.token.amount,
+        };
+        if remaining.is_zero() {
+            break;
+        }
+
+        if remaining >= vtoken.token.amount {
+            withdrawable += vtoken.token.amount;
+            vwithdrawable += vtoken.vtoken.amount;
+            remove_position(storage, env.clone(), owner, &vtoken)?;
+            update_supply_decay(storage, env.clone(), denom, Some(&vtoken), None)?;
+        } else {
+            // Vtokens are withdrawn in proportion to the tokens
+            let vamount = vtoken
+                .vtoken
+                .amount
+                .multiply_ratio(remaining, vtoken.token.amount);
+            let mut partial = vtoken.clone();
+            partial.token.amount -= remaining;
+            partial.vtoken.amount -= vamount;
+            withdrawable += remaining;
+            vwithdrawable += vamount;
+            POSITION_OFFERS.remove(storage, partial.id);
+            save_position(storage, env.clone(), owner, &partial)?;
+            update_supply_decay(storage, env.clone(), denom, Some(&vtoken), Some(&partial))?;
+        }
+    }
+
+    if let Some(amount) = amount {
+        if withdrawable < amount {
+            return Err(ContractError::CustomError {
+                val: format!(
+                    "Only {} unlocked tokens available for {:?}",
+                    withdrawable, denom
+                ),
+            });
+        }
+    }
+
+    // Reduce the total supply
+    update_denom_supply(
+        storage,
+        env,
+        denom,
+        vwithdrawable.u128(),
+        withdrawable.u128(),
+        false,
+    )?;
+
+    Ok(withdrawable)
+}
+
+/// Handles the withdrawal of tokens after completion of locking period.
+pub fn handle_withdraw(
+    deps: DepsMut<ComdexQuery>,
+    env: Env,
+    info: MessageInfo,
+    denom: String,
+    amount: Option<Uint128>,
+    position_ids: Option<Vec<u64>>,
+    recipient: Option<String>,
+) -> Result<Response<ComdexMessages>, ContractError> {
+    if !info.funds.is_empty() {
+        return Err(ContractError::FundsNotAllowed {});
+    }
+    if let Some(amount) = amount {
+        if amount.is_zero() {
+            return Err(ContractError::CustomError {
+                val: "Withdraw amount should be greater than zero".to_string(),
+            });
+        }
+    }
+    let recipient = match recipient {
+        Some(recipient) => deps.api.addr_validate(&recipient)?,
+        None => info.sender.clone(),
+    };
+
+    // Load the positions to withdraw
+    let vtokens: Vec<Vtoken> = match position_ids {
+        Some(position_ids) => {
+            if has_duplicate_elements(&position_ids) {
+                return Err(ContractError::CustomError {
+                    val: "Position ids has duplicate elements".to_string(),
+                });
+            }
+            let mut vtokens = vec![];
+            for position_id in position_ids {
+                let vtoken = load_owned_position(deps.storage, &info.sender, position_id)?;
+                if vtoken.token.denom != denom {
+                    return Err(ContractError::CustomError {
+                        val: format!("Lock position {} is not of {:?}", position_id, denom),
+                    });
+                }
+                if !is_unlocked(&vtoken, env.block.time) {
+                    return Err(ContractError::CustomError {
+                        val: format!("Lock position {} is still locked", position_id),
+                    });
+                }
+                vtokens.push(vtoken);
+            }
+            vtokens
+        }
+        None => match VTOKENS.may_load(deps.storage, (info.sender.clone(), &denom))? {
+            Some(val) => val
+                .into_iter()
+                .filter(|s| is_unlocked(s, env.block.time))
+                .collect(),
+            None => {
+                return Err(ContractError::NotFound {
+                    msg: format!("No tokens found for {:?}", denom),
+                })
+            }
+        },
+    };
+
+    // No unlocked tokens
+    if vtokens.is_empty() {
+        return Err(ContractError::NotFound {
+            msg: format!("No unlocked tokens found for {:?}", denom),
+        });
+    }
+
+    let withdrawable = withdraw_vtokens(deps.storage, env, &info.sender, &denom, vtokens, amount)?;
+
+    Ok(Response::new()
+        .add_message(transfer_msg(
+            Coin {
+                denom,
+                amount: withdrawable,
+            },
+            &recipient,
+        )?)
+        .add_attribute("action", "Withdraw")
+        .add_attribute("from", info.sender)
+        .add_attribute("recipient", recipient))
+}
+
+/// Handles the withdrawal of the unlocked tokens of every denomination.
+pub fn handle_withdraw_all(
+    deps: DepsMut<ComdexQuery>,
+    env: Env,
+    info: MessageInfo,
+    recipient: Option<String>,
+) -> Result<Response<ComdexMessages>, ContractError> {
+    if !info.funds.is_empty() {
+        return Err(ContractError::FundsNotAllowed {});
+    }
+    let recipient = match recipient {
+        Some(recipient) => deps.api.addr_validate(&recipient)?,
+        None => info.sender.clone(),
+    };
+
+    // Unlocked positions of the sender, ordered by denom
+    let unlocked: Vec<Vtoken> = positions()
+        .idx
+        .owner
+        .sub_prefix(info.sender.clone())
+        .range(deps.storage, None, None, Order::Ascending)
+        .map(|item| item.map(|(_, position)| position.vtoken))
+        .collect::<StdResult<Vec<Vtoken>>>()?
+        .into_iter()
+        .filter(|s| is_unlocked(s, env.block.time))
+        .collect();
+
+    if unlocked.is_empty() {
+        return Err(ContractError::NotFound {
+            msg: "No unlocked tokens found".to_string(),
+        });
+    }
+
+    let mut by_denom: Vec<(String, Vec<Vtoken>)> = vec![];
+    for vtoken in unlocked {
+        match by_denom.last_mut() {
+            Some((denom, vtokens)) if *denom == vtoken.token.denom => vtokens.push(vtoken),
+            _ => by_denom.push((vtoken.token.denom.clone(), vec![vtoken])),
+        }
+    }
+
+    // Native tokens are sent together, CW20 tokens with a transfer each
+    let mut withdrawn: Vec<Coin> = vec![];
+    let mut response = Response::new();
+    for (denom, vtokens) in by_denom {
+        let amount = withdraw_vtokens(
+            deps.storage,
+            env.clone(),
+            &info.sender,
+            &denom,
+            vtokens,
+            None,
+        )?;
+        if denom.starts_with(CW20_DENOM_PREFIX) {
+            response = response.add_message(transfer_msg(Coin { denom, amount }, &recipient)?);
+        } else {
+            withdrawn.push(Coin { denom, amount });
+        }
+    }
+    if !withdrawn.is_empty() {
+        response = response.add_message(BankMsg::Send {
+            to_address: recipient.to_string(),
+            amount: withdrawn,
+        });
+    }
+
+    Ok(response
+        .add_attribute("action", "WithdrawAll")
+        .add_attribute("from", info.sender)
+        .add_attribute("recipient", recipient))
+}
+
+/// Starts the unlock of an auto-relock position. The position stops relocking
+/// and unlocks after a cooldown equal to its locking period.
+pub fn handle_start_unlock(
+    deps: DepsMut<ComdexQuery>,
+    env: Env,
+    info: MessageInfo,
+    position_id: u64,
+) -> Result<Response<ComdexMessages>, ContractError> {
+    if !info.funds.is_empty() {
+        return Err(ContractError::FundsNotAllowed {});
+    }
+
+    let vtoken = load_owned_position(deps.storage, &info.sender, position_id)?;
+    if !vtoken.auto_relock {
+        return Err(ContractError::CustomError {
+            val: "Lock position is not auto-relocking".to_string(),
+        });
+    }
+
+    let mut unlocking = vtoken.clone();
+    unlocking.auto_relock = false;
+    unlocking.start_time = env.block.time;
+    unlocking.end_time = vtoken_end_time(&vtoken, env.block.time);
+    unlocking.decay = VOTING_DECAY.may_load(deps.storage)?.unwrap_or_default();
+
+    update_supply_decay(
+        deps.storage,
+        env.clone(),
+        &vtoken.token.denom,
+        Some(&vtoken),
+        Some(&unlocking),
+    )?;
+
+    // The offered position changed, the offer needs to be renewed
+    POSITION_OFFERS.remove(deps.storage, position_id);
+    save_position(deps.storage, env, &info.sender, &unlocking)?;
+
+    Ok(Response::new()
+        .add_attribute("action", "start_unlock")
+        .add_attribute("from", info.sender)
+        .add_attribute("position_id", position_id.to_string())
+        .add_attribute("end_time", unlocking.end_time.to_string()))
+}
+
+/// Handles the withdrawal of a position before the end of its locking period.
+/// A penalty, decreasing as the end of the locking period approaches, is
+/// deducted and sent to the configured destination.
+pub fn handle_early_withdraw(
+    deps: DepsMut<ComdexQuery>,
+    env: Env,
+    info: MessageInfo,
+    position_id: u64,
+) -> Result<Response<ComdexMessages>, ContractError> {
+    if !info.funds.is_empty() {
+        return Err(ContractError::FundsNotAllowed {});
+    }
+
+    let config = match EARLY_UNLOCK.may_load(deps.storage)? {
+        Some(val) => val,
+        None => {
+            return Err(ContractError::CustomError {
+                val: "Early unlock is not enabled".to_string(),
+            })
+        }
+    };
+
+    let vtoken = load_owned_position(deps.storage, &info.sender, position_id)?;
+    let denom = vtoken.token.denom.clone();
+    let app_id = vtoken.app_id;
+
+    let app_response = query_app_exists(deps.as_ref(), app_id)?;
+    let gov_token_denom = query_get_asset_data(deps.as_ref(), app_response.gov_token_id)?;
+    let cw20_gov_token = CW20_GOV_TOKENS.may_load(deps.storage, app_id)?;
+    if gov_token_denom != denom && cw20_gov_token.as_ref().map(cw20_denom) != Some(denom.clone()) {
+        return Err(ContractError::CustomError {
+            val: format!("Lock position {} is not of the app gov token", position_id),
+        });
+    }
+
+    let penalty = early_unlock_penalty(&vtoken, config.max_penalty, env.block.time);
+    let withdrawable = vtoken.token.amount - penalty;
+
+    remove_position(deps.storage, env.clone(), &info.sender, &vtoken)?;
+    update_supply_decay(deps.storage, env.clone(), &denom, Some(&vtoken), None)?;
+    update_denom_supply(
+        deps.storage,
+        env.clone(),
+        &denom,
+        vtoken.vtoken.amount.u128(),
+        vtoken.token.amount.u128(),
+        false,
+    )?;
+
+    let mut response = Response::new();
+    if !withdrawable.is_zero() {
+        response = response.add_message(transfer_msg(
+            Coin {
+                denom: denom.clone(),
+                amount: withdrawable,
+            },
+            &info.sender,
+        )?);
+    }
+
+    if !penalty.is_zero() {
+        match config.destination {
+            PenaltyDestination::Foundation => {
+                let state = STATE.load(deps.storage)?;
+                if state.foundation_addr.is_empty() {
+                    return Err(ContractError::CustomError {
+                        val: "No foundation address found".to_string(),
+                    });
+                }
+                //// split the penalty equally, the remainder goes to the first address
+                let share = penalty.u128() / state.foundation_addr.len() as u128;
+                let remainder = penalty.u128() - share * state.foundation_addr.len() as u128;
+                for (i, address) in state.foundation_addr.iter().enumerate() {
+                    let amount = if i == 0 { share + remainder } else { share };
+                    if amount == 0 {
+                        continue;
+                    }
+                    response = response.add_message(transfer_msg(
+                        Coin {
+                            denom: denom.clone(),
+                            amount: Uint128::from(amount),
+                        },
+                        &Addr::unchecked(address),
+                    )?);
+                }
+            }
+            PenaltyDestination::Redistribute => {
+                // The rebase is paid out in the native gov token
+                if denom != gov_token_denom {
+                    return Err(ContractError::CustomError {
+                        val: "Penalties can only be redistributed for the native gov token"
+                            .to_string(),
+                    });
+                }
+                let pending = PENDING_PENALTIES
+                    .may_load(deps.storage, app_id)?
+                    .unwrap_or_default();
+                PENDING_PENALTIES.save(deps.storage, app_id, &(pending + penalty.u128()))?;
+            }
+            PenaltyDestination::Burn => match cw20_gov_token {
+                Some(address) if denom != gov_token_denom => {
+                    response = response.add_message(WasmMsg::Execute {
+                        contract_addr: address.to_string(),
+                        msg: to_json_binary(&Cw20ExecuteMsg::Burn { amount: penalty })?,
+                        funds: vec![],
+                    });
+                }
+                _ => {
+                    response = response.add_message(ComdexMessages::MsgBurnGovTokensForApp {
+                        app_id,
+                        amount: Coin {
+                            denom: denom.clone(),
+                            amount: penalty,
+                        },
+                        from: env.contract.address.to_string(),
+                    });
+                }
+            },
+        }
+    }
+
+    Ok(response
+        .add_attribute("action", "EarlyWithdraw")
+        .add_attribute("from", info.sender)
+        .add_attribute("position_id", position_id.to_string())
+        .add_attribute("penalty", penalty.to_string()))
+}
+
+/// Extends a lock by relocking it from the current time for the given locking
+/// period. The new end time and vtoken amount cannot be lower than the existing ones.
+pub fn handle_extend_lock(
+    deps: DepsMut<ComdexQuery>,
+    env: Env,
+    info: MessageInfo,
+    position_id: u64,
+    locking_period: LockingPeriod,
+) -> Result<Response<ComdexMessages>, ContractError> {
+    if !info.funds.is_empty() {
+        return Err(ContractError::FundsNotAllowed {});
+    }
+
+    let vtoken = load_owned_position(deps.storage, &info.sender, position_id)?;
+    let denom = vtoken.token.denom.clone();
+
+    let app_config = load_app_config(deps.storage, vtoken.app_id)?;
+    let PeriodWeight { period, weight } =
+        get_period(deps.storage, app_config, locking_period.clone())?;
+
+    let mut extended = vtoken.clone();
+    extended.vtoken.amount = weight * vtoken.token.amount;
+    extended.period = locking_period;
+    extended.weight = Some(weight);
+    extended.start_time = env.block.time;
+    extended.end_time = env.block.time.plus_seconds(period);
+    extended.status = Status::Locked;
+
+    if extended.end_time < vtoken_end_time(&vtoken, env.block.time) {
+        return Err(ContractError::CustomError {
+            val: "Lock cannot end earlier than the existing lock".to_string(),
+        });
+    }
+    if extended.vtoken.amount < vtoken.vtoken.amount {
+        return Err(ContractError::CustomError {
+            val: "Lock cannot be extended to a lower weight".to_string(),
+        });
+    }
+
+    // Re-weight the vtoken supply
+    update_denom_supply(
+        deps.storage,
+        env.clone(),
+        &denom,
+        vtoken.vtoken.amount.u128(),
+        0,
+        false,
+    )?;
+    update_denom_supply(
+        deps.storage,
+        env.clone(),
+        &denom,
+        extended.vtoken.amount.u128(),
+        0,
+        true,
+    )?;
+    update_supply_decay(
+        deps.storage,
+        env.clone(),
+        &denom,
+        Some(&vtoken),
+        Some(&extended),
+    )?;
+
+    // The offered position changed, the offer needs to be renewed
+    POSITION_OFFERS.remove(deps.storage, position_id);
+    save_position(deps.storage, env, &info.sender, &extended)?;
+
+    Ok(Response::new()
+        .add_attribute("action", "extend_lock")
+        .add_attribute("from", info.sender)
+        .add_attribute("end_time", extended.end_time.to_string())
+        .add_attribute("vtoken", extended.vtoken.amount))
+}
+
+/// Splits `amount` tokens off a lock position into a new position with the
+/// same locking period and end time.
+pub fn handle_split_position(
+    deps: DepsMut<ComdexQuery>,
+    env: Env,
+    info: MessageInfo,
+    position_id: u64,
+    amount: Uint128,
+) -> Result<Response<ComdexMessages>, ContractError> {
+    if !info.funds.is_empty() {
+        return Err(ContractError::FundsNotAllowed {});
+    }
+
+    let vtoken = load_owned_position(deps.storage, &info.sender, position_id)?;
+    if amount.is_zero() || amount >= vtoken.token.amount {
+        return Err(ContractError::CustomError {
+            val: "Split amount should be between zero and the locked amount".to_string(),
+        });
+    }
+    let app_config = load_app_config(deps.storage, vtoken.app_id)?;
+    if app_config.min_lock_amount > amount
+        || app_config.min_lock_amount > vtoken.token.amount - amount
+    {
+        return Err(ContractError::CustomError {
+            val: "Lock amount less than minimum lock amount".to_string(),
+        });
+    }
+
+    // Vtokens are split in proportion to the tokens
+    let split_vtoken_amount = vtoken
+        .vtoken
+        .amount
+        .multiply_ratio(amount, vtoken.token.amount);
+
+    let mut remaining = vtoken.clone();
+    remaining.token.amount -= amount;
+    remaining.vtoken.amount -= split_vtoken_amount;
+
+    let mut split = vtoken.clone();
+    split.id = POSITIONCOUNT.load(deps.storage)? + 1;
+    POSITIONCOUNT.save(deps.storage, &split.id)?;
+    split.token.amount = amount;
+    split.vtoken.amount = split_vtoken_amount;
+
+    // SUPPLY is unchanged, only the decay of the positions needs updating
+    update_supply_decay(
+        deps.storage,
+        env.clone(),
+        &vtoken.token.denom,
+        Some(&vtoken),
+        Some(&remaining),
+    )?;
+    update_supply_decay(
+        deps.storage,
+        env.clone(),
+        &vtoken.token.denom,
+        None,
+        Some(&split),
+    )?;
+
+    // The offered position changed, the offer needs to be renewed
+    POSITION_OFFERS.remove(deps.storage, position_id);
+    save_position(deps.storage, env.clone(), &info.sender, &remaining)?;
+    save_position(deps.storage, env, &info.sender, &split)?;
+
+    Ok(Response::new()
+        .add_attribute("action", "split_position")
+        .add_attribute("from", info.sender)
+        .add_attribute("position_id", position_id.to_string())
+        .add_attribute("new_position_id", split.id.to_string()))
+}
+
+/// Merges lock positions of the same denom and locking period into the first
+/// position, which takes the latest end time among them.
+pub fn handle_merge_positions(
+    deps: DepsMut<ComdexQuery>,
+    env: Env,
+    info: MessageInfo,
+    position_ids: Vec<u64>,
+) -> Result<Response<ComdexMessages>, ContractError> {
+    if !info.funds.is_empty() {
+        return Err(ContractError::FundsNotAllowed {});
+    }
+    if position_ids.len() < 2 {
+        return Err(ContractError::CustomError {
+            val: "At least two positions are required to merge".to_string(),
+        });
+    }
+    if has_duplicate_elements(&position_ids) {
+        return Err(ContractError::CustomError {
+            val: "Position ids has duplicate elements".to_string(),
+        });
+    }
+
+    let mut vtokens: Vec<Vtoken> = vec![];
+    for position_id in position_ids.iter() {
+        vtokens.push(load_owned_position(
+            deps.storage,
+            &info.sender,
+            *position_id,
+        )?);
+    }
+
+    let mut merged = vtokens[0].clone();
+    for vtoken in vtokens.iter().skip(1) {
+        if vtoken.token.denom != merged.token.denom
+            || vtoken.period != merged.period
+            || vtoken.decay != merged.decay
+            || vtoken.auto_relock != merged.auto_relock
+            || vtoken.app_id != merged.app_id
+            || position_weight(vtoken) != position_weight(&merged)
+        {
+            return Err(ContractError::CustomError {
+                val: "Only positions of the same denom and locking period can be merged"
+                    .to_string(),
+            });
+        }
+        merged.token.amount += vtoken.token.amount;
+        merged.vtoken.amount += vtoken.vtoken.amount;
+        // The merged position keeps the locking window of the latest lock
+        if vtoken.end_time > merged.end_time {
+            merged.start_time = vtoken.start_time;
+            merged.end_time = vtoken.end_time;
+            merged.status = vtoken.status.clone();
+        }
+    }
+
+    let denom = merged.token.denom.clone();
+    for vtoken in vtokens.iter() {
+        update_supply_decay(deps.storage, env.clone(), &denom, Some(vtoken), None)?;
+        positions().remove(deps.storage, vtoken.id)?;
+        POSITION_OFFERS.remove(deps.storage, vtoken.id);
+    }
+    update_supply_decay(deps.storage, env.clone(), &denom, None, Some(&merged))?;
+    save_position(deps.storage, env, &info.sender, &merged)?;
+
+    Ok(Response::new()
+        .add_attribute("action", "merge_positions")
+        .add_attribute("from", info.sender)
+        .add_attribute("position_id", merged.id.to_string()))
+}
+
+/// Given the locking period, retrieves the `period` and `weight`.
+fn get_period(
+    storage: &dyn Storage,
+    app_config: AppConfig,
+    locking_period: LockingPeriod,
+) -> Result<PeriodWeight, ContractError> {
+    Ok(match locking_period {
+        LockingPeriod::T1 => app_config.t1,
+        LockingPeriod::T2 => app_config.t2,
+        LockingPeriod::Custom { duration } => {
+            let weight_curve = match WEIGHT_CURVE.may_load(storage)? {
+                Some(val) => val,
+                None => {
+                    return Err(ContractError::CustomError {
+                        val: "Custom locking durations are not enabled".to_string(),
+                    })
+                }
+            };
+            if duration < weight_curve.min_period || duration > weight_curve.max_period {
+                return Err(ContractError::CustomError {
+                    val: format!(
+                        "Locking duration should be between {} and {} seconds",
+                        weight_curve.min_period, weight_curve.max_period
+                    ),
+                });
+            }
+            PeriodWeight {
+                period: duration,
+                weight: curve_weight(&weight_curve, duration),
+            }
+        }
+    })
+}
+
+/// Calculates the weight of the given duration on the weight curve.
+fn curve_weight(weight_curve: &WeightCurve, duration: u64) -> Decimal {
+    // index of the last point whose period does not exceed the duration
+    let index = weight_curve
+        .points
+        .iter()
+        .rposition(|point| point.period <= duration)
+        .unwrap_or(0);
+    let lower = &weight_curve.points[index];
+
+    match weight_curve.curve_type {
+        CurveType::Stepped => lower.weight,
+        CurveType::Linear => match weight_curve.points.get(index + 1) {
+            Some(upper) if duration > lower.period => {
+                lower.weight
+                    + (upper.weight - lower.weight)
+                        * Decimal::from_ratio(duration - lower.period, upper.period - lower.period)
+            }
+            _ => lower.weight,
+        },
+    }
+}
+
+/// Checks that the weight curve covers its locking range and never decreases.
+fn validate_weight_curve(weight_curve: &WeightCurve) -> Result<(), ContractError> {
+    if weight_curve.min_period == 0 || weight_curve.min_period > weight_curve.max_period {
+        return Err(ContractError::CustomError {
+            val: "Invalid weight curve locking range".to_string(),
+        });
+    }
+    match weight_curve.points.first() {
+        Some(point) if point.period <= weight_curve.min_period => {}
+        _ => {
+            return Err(ContractError::CustomError {
+                val: "Weight curve should start at or below the minimum period".to_string(),
+            })
+        }
+    }
+    if weight_curve
+        .points
+        .windows(2)
+        .any(|pair| pair[0].period >= pair[1].period || pair[0].weight > pair[1].weight)
+    {
+        return Err(ContractError::CustomError {
+            val: "Weight curve points should be sorted by period with non-decreasing weights"
+                .to_string(),
+        });
+    }
+
+    Ok(())
+}
+
+fn validate_early_unlock(early_unlock: &EarlyUnlockConfig) -> Result<(), ContractError> {
+    if early_unlock.max_penalty > Decimal::one() {
+        return Err(ContractError::CustomError {
+            val: "Early unlock penalty cannot be greater than 100 %".to_string(),
+        });
+    }
+    Ok(())
+}
+
+/// Moves a lock position to the recipient. SUPPLY and the voting power decay
+/// are unchanged, only the owners snapshots are updated.
+fn transfer_position(
+    storage: &mut dyn Storage,
+    env: Env,
+    owner: &Addr,
+    recipient: &Addr,
+    vtoken: &Vtoken,
+) -> Result<(), ContractError> {
+    POSITION_OFFERS.remove(storage, vtoken.id);
+
+    // Move the position to the recipient, updating the snapshots of both
+    save_position(storage, env.clone(), recipient, vtoken)?;
+    sync_vtokens(storage, env, owner, &vtoken.token.denom)?;
+
+    // Load the recipients nft
+    let recipient_nft = TOKENS.may_load(storage, recipient.clone())?;
+
+    match recipient_nft {
+        Some(_) => {}
+        None => {
+            // Crate a new NFT
+            let mut state = STATE.load(storage)?;
+            state.num_tokens += 1;
+            STATE.save(storage, &state)?;
+            TOKENS.save(
+                storage,
+                recipient.clone(),
+                &TokenInfo {
+                    owner: recipient.clone(),
+                    token_id: state.num_tokens,
+                },
+            )?;
+        }
+    };
+
+    Ok(())
+}
+
+/// Handles the transfer of a lock position between users. When acceptance is
+/// required, the position is only offered and moves once the recipient accepts it.
+pub fn handle_transfer(
+    deps: DepsMut<ComdexQuery>,
+    env: Env,
+    info: MessageInfo,
+    recipient: String,
+    position_id: u64,
+    require_acceptance: bool,
+) -> Result<Response<ComdexMessages>, ContractError> {
+    if !info.funds.is_empty() {
+        return Err(ContractError::FundsNotAllowed {});
+    }
+    let recipient = deps.api.addr_validate(&recipient)?;
+    if recipient == info.sender {
+        return Err(ContractError::CustomError {
+            val: "Cannot transfer a lock position to its owner".to_string(),
+        });
+    }
+
+    // Load the sender position that needs to be transferred
+    let vtoken = load_owned_position(deps.storage, &info.sender, position_id)?;
+
+    if require_acceptance {
+        POSITION_OFFERS.save(deps.storage, position_id, &recipient)?;
+        return Ok(Response::new()
+            .add_attribute("action", "offer_position")
+            .add_attribute("from", info.sender)
+            .add_attribute("to", recipient)
+            .add_attribute("position_id", position_id.to_string()));
+    }
+
+    transfer_position(deps.storage, env, &info.sender, &recipient, &vtoken)?;
+
+    Ok(Response::new()
+        .add_attribute("action", "transfer")
+        .add_attribute("from", info.sender)
+        .add_attribute("to", recipient)
+        .add_attribute("position_id", position_id.to_string()))
+}
+
+/// Accepts a lock position offered to the sender.
+pub fn handle_accept_position(
+    deps: DepsMut<ComdexQuery>,
+    env: Env,
+    info: MessageInfo,
+    position_id: u64,
+) -> Result<Response<ComdexMessages>, ContractError> {
+    if !info.funds.is_empty() {
+        return Err(ContractError::FundsNotAllowed {});
+    }
+
+    match POSITION_OFFERS.may_load(deps.storage, position_id)? {
+        Some(recipient) if recipient == info.sender => {}
+        _ => {
+            return Err(ContractError::NotFound {
+                msg: format!("No offer found for lock position {}", position_id),
+            })
+        }
+    }
+    let position = positions().load(deps.storage, position_id)?;
+
+    transfer_position(
+        deps.storage,
+        env,
+        &position.owner,
+        &info.sender,
+        &position.vtoken,
+    )?;
+
+    Ok(Response::new()
+        .add_attribute("action", "transfer")
+        .add_attribute("from", position.owner)
+        .add_attribute("to", info.sender)
+        .add_attribute("position_id", position_id.to_string()))
+}
+
+/// Cancels the pending transfer offer of a lock position.
+pub fn handle_cancel_position_offer(
+    deps: DepsMut<ComdexQuery>,
+    info: MessageInfo,
+    position_id: u64,
+) -> Result<Response<ComdexMessages>, ContractError> {
+    if !info.funds.is_empty() {
+        return Err(ContractError::FundsNotAllowed {});
+    }
+    load_owned_position(deps.storage, &info.sender, position_id)?;
+
+    if !POSITION_OFFERS.has(deps.storage, position_id) {
+        return Err(ContractError::NotFound {
+            msg: format!("No offer found for lock position {}", position_id),
+        });
+    }
+    POSITION_OFFERS.remove(deps.storage, position_id);
+
+    Ok(Response::new()
+        .add_attribute("action", "cancel_position_offer")
+        .add_attribute("from", info.sender)
+        .add_attribute("position_id", position_id.to_string()))
+}
+
+pub fn bribe_proposal(
+    deps: DepsMut<ComdexQuery>,
+    env: Env,
+    info: MessageInfo,
+    proposal_id: u64,
+    gauge: GaugeTarget,
+    bribe_coin: Coin,
+) -> Result<Response<ComdexMessages>, ContractError> {
+    // bribe denom should be a single coin
+    if info.funds.is_empty() {
+        return Err(ContractError::InsufficientFunds { funds: 0 });
+    } else if info.funds.len() > 1 {
+        return Err(ContractError::CustomError {
+            val: String::from("Multiple denominations are not supported"),
+        });
+    }
+
+    // bribe coin should not have zero amount
+    if info.funds[0].amount.is_zero() {
+        return Err(ContractError::InsufficientFunds { funds: 0 });
+    }
+
+    //check if active proposal
+    let proposal = PROPOSAL.load(deps.storage, proposal_id)?;
+    if proposal_status(&proposal, env.block.time) != ProposalStatus::Active {
+        return Err(ContractError::CustomError {
+            val: "Proposal Bribing Period Ended".to_string(),
+        });
+    }
+
+    // check if gauge param exist in gauge list to vote for
+    if !proposal.gauges.contains(&gauge) || proposal.killed_gauges.contains(&gauge) {
+        return Err(ContractError::CustomError {
+            val: "Invalid gauge".to_string(),
+        });
+    }
+
+    deposit_bribe(deps.storage, proposal_id, &info.sender, &gauge, bribe_coin)?;
+    Ok(Response::new().add_attribute("method", "bribe"))
+}
+
+/// Deposits a bribe released in equal parts to the gauge of the next `epochs` proposals
+/// raised for the app.
+pub fn schedule_bribe(
+    deps: DepsMut<ComdexQuery>,
+    info: MessageInfo,
+    app_id: u64,
+    gauge: GaugeTarget,
+    epochs: u64,
+    bribe_coin: Coin,
+) -> Result<Response<ComdexMessages>, ContractError> {
+    if epochs == 0 {
+        return Err(ContractError::CustomError {
+            val: "Bribe must be scheduled over at least one epoch".to_string(),
+        });
+    }
+    // ensure the app exists and lists the gauge
+    query_app_exists(deps.as_ref(), app_id)?;
+    if !query_app_gauges(deps.as_ref(), app_id)?.contains(&gauge) {
+        return Err(ContractError::CustomError {
+            val: "Invalid gauge".to_string(),
+        });
+    }
+    let app_config = load_app_config(deps.storage, app_id)?;
+    if bribe_coin.amount < app_config.min_scheduled_bribe * Uint128::from(epochs) {
+        return Err(ContractError::InsufficientFunds {
+            funds: bribe_coin.amount.u128(),
+        });
+    }
+    let pending_bribes = SCHEDULED_BRIBES
+        .prefix(app_id)
+        .keys(deps.storage, None, None, Order::Ascending)
+        .take(MAX_SCHEDULED_BRIBES)
+        .count();
+    if pending_bribes >= MAX_SCHEDULED_BRIBES {
+        return Err(ContractError::CustomError {
+            val: "Too many scheduled bribes pending for the app".to_string(),
+        });
+    }
+
+    let scheduled_bribe_id = SCHEDULED_BRIBE_COUNT
+        .may_load(deps.storage)?
+        .unwrap_or_default()
+        + 1;
+    SCHEDULED_BRIBE_COUNT.save(deps.storage, &scheduled_bribe_id)?;
+    let scheduled_bribe = ScheduledBribe {
+        id: scheduled_bribe_id,
+        depositor: info.sender,
+        gauge,
+        remaining: bribe_coin,
+        remaining_epochs: epochs,
+    };
+    SCHEDULED_BRIBES.save(deps.storage, (app_id, scheduled_bribe_id), &scheduled_bribe)?;
+
+    Ok(Response::new()
+        .add_attribute("method", "schedule_bribe")
+        .add_attribute("scheduled_bribe_id", scheduled_bribe_id.to_string()))
+}
+
+/// Refunds the unreleased part of a scheduled bribe whose gauge is not part of the
+/// latest proposal of the app.
+pub fn handle_reclaim_scheduled_bribe(
+    deps: DepsMut<ComdexQuery>,
+    info: MessageInfo,
+    app_id: u64,
+    scheduled_bribe_id: u64,
+) -> Result<Response<ComdexMessages>, ContractError> {
+    if !info.funds.is_empty() {
+        return Err(ContractError::FundsNotAllowed {});
+    }
+
+    let scheduled_bribe = SCHEDULED_BRIBES
+        .may_load(deps.storage, (app_id, scheduled_bribe_id))?
+        .ok_or_else(|| ContractError::NotFound {
+            msg: format!("Scheduled bribe {} not found", scheduled_bribe_id),
+        })?;
+    if scheduled_bribe.depositor != info.sender {
+        return Err(ContractError::Unauthorized {});
+    }
+
+    let current_proposal = APPCURRENTPROPOSAL.may_load(deps.storage, app_id)?;
+    if let Some(current_proposal) = current_proposal {
+        let proposal = PROPOSAL.load(deps.storage, current_proposal)?;
+        if proposal.gauges.contains(&scheduled_bribe.gauge) {
+            return Err(ContractError::CustomError {
+                val: "Gauge of the scheduled bribe is still listed".to_string(),
+            });
+        }
+    }
+    SCHEDULED_BRIBES.remove(deps.storage, (app_id, scheduled_bribe_id));
+
+    Ok(Response::new()
+        .add_attribute("method", "reclaim_scheduled_bribe")
+        .add_attribute("scheduled_bribe_id", scheduled_bribe_id.to_string())
+        .add_message(BankMsg::Send {
+            to_address: info.sender.to_string(),
+            amount: vec![scheduled_bribe.remaining],
+        }))
+}
+
+/// Releases the share of the scheduled bribes of the app to the gauges of the raised
+/// proposal. Bribes on gauges missing from the proposal are held back for later ones.
+fn release_scheduled_bribes(
+    storage: &mut dyn Storage,
+    app_id: u64,
+    proposal_id: u64,
+    gauges: &[GaugeTarget],
+) -> Result<(), ContractError> {
+    let scheduled_bribes = SCHEDULED_BRIBES
+        .prefix(app_id)
+        .range(storage, None, None, Order::Ascending)
+        .take(MAX_SCHEDULED_BRIBES)
+        .collect::<StdResult<Vec<(u64, ScheduledBribe)>>>()?;
+
+    for (scheduled_bribe_id, mut scheduled_bribe) in scheduled_bribes {
+        if !gauges.contains(&scheduled_bribe.gauge) {
+            continue;
+        }
+        // the last epoch releases the rounding remainder
+        let amount =
+            scheduled_bribe.remaining.amount / Uint128::from(scheduled_bribe.remaining_epochs);
+        scheduled_bribe.remaining.amount -= amount;
+        scheduled_bribe.remaining_epochs -= 1;
+        if !amount.is_zero() {
+            deposit_bribe(
+                storage,
+                proposal_id,
+                &scheduled_bribe.depositor,
+                &scheduled_bribe.gauge,
+                Coin {
+                    denom: scheduled_bribe.remaining.denom.clone(),
+                    amount,
+                },
+            )?;
+        }
+        if scheduled_bribe.remaining_epochs == 0 {
+            SCHEDULED_BRIBES.remove(storage, (app_id, scheduled_bribe_id));
+        } else {
+            SCHEDULED_BRIBES.save(storage, (app_id, scheduled_bribe_id), &scheduled_bribe)?;
+        }
+    }
+    Ok(())
+}
+
+/// Adds the bribe to the gauge of the proposal, recording the deposit of the briber
+/// to refund it if the proposal is cancelled or the gauge receives no votes.
+fn deposit_bribe(
+    storage: &mut dyn Storage,
+    proposal_id: u64,
+    depositor: &Addr,
+    gauge: &GaugeTarget,
+    bribe_coin: Coin,
+) -> Result<(), ContractError> {
+    // UPDATE BRIBE FOR PROPOSAL (IF EXISTS THEN UPDATE ELSE APPEND)
+    let mut existing_bribes = BRIBES_BY_PROPOSAL
+        .may_load(storage, (proposal_id, gauge.clone()))?
+        .unwrap_or_default();
+    add_coin(&mut existing_bribes, bribe_coin.clone());
+    BRIBES_BY_PROPOSAL.save(storage, (proposal_id, gauge.clone()), &existing_bribes)?;
+
+    let mut deposits = BRIBE_DEPOSITS
+        .may_load(storage, (proposal_id, depositor.clone()))?
+        .unwrap_or_default();
+    match deposits.iter_mut().find(|deposit| deposit.gauge == *gauge) {
+        Some(deposit) => add_coin(&mut deposit.coins, bribe_coin),
+        None => deposits.push(BribeDeposit {
+            gauge: gauge.clone(),
+            coins: vec![bribe_coin],
+        }),
+    }
+    BRIBE_DEPOSITS.save(storage, (proposal_id, depositor.clone()), &deposits)?;
+    Ok(())
+}
+
+pub fn claim_rewards(
+    deps: DepsMut<ComdexQuery>,
+    env: Env,
+    info: MessageInfo,
+    app_id: u64,
+    proposal_id: Option<u64>,
+) -> Result<Response<ComdexMessages>, ContractError> {
+    if !info.funds.is_empty() {
+        return Err(ContractError::FundsNotAllowed {});
+    }
+
+    let all_proposals = match COMPLETEDPROPOSALS.may_load(deps.storage, app_id)? {
+        Some(val) => val,
+        None => vec![],
+    };
+    if let Some(proposal_id) = proposal_id {
+        if !all_proposals.contains(&proposal_id) {
+            return Err(ContractError::CustomError {
+                val: String::from("Proposal not completed"),
+            });
+        }
+
+        let voters_claimed = VOTERS_CLAIM
+            .load(deps.storage, (info.sender.clone(), proposal_id))
+            .unwrap_or_default();
+
+        if voters_claimed {
+            return Err(ContractError::CustomError {
+                val: String::from("Already claimed"),
+            });
+        }
+
+        let mut bribe_coin =
+            calculate_bribe_reward_proposal(deps.as_ref(), env, info.clone(), proposal_id)?;
+        VOTERS_CLAIM.save(deps.storage, (info.sender.clone(), proposal_id), &true)?;
+        let mut claimed_proposal =
+            match VOTERS_CLAIMED_PROPOSALS.may_load(deps.storage, info.sender.clone())? {
+                Some(val) => val,
+                None => vec![],
+            };
+        claimed_proposal.push(proposal_id);
+        claimed_proposal.sort();
+        VOTERS_CLAIMED_PROPOSALS.save(deps.storage, info.sender.clone(), &claimed_proposal)?;
+        bribe_coin.sort_by_key(|element| element.denom.clone());
+        bribe_coin.retain(|coin| !coin.amount.is_zero());
+
+        return Ok(Response::new()
+            .add_attribute("method", "External Incentive Claimed")
+            .add_message(BankMsg::Send {
+                to_address: info.sender.to_string(),
+                amount: bribe_coin,
+            }));
+    }
+
+    let (mut bribe_coins, claimed_proposals) =
+        calculate_bribe_reward(deps.as_ref(), env, info.clone(), all_proposals, app_id)?;
+
+    VOTERS_CLAIMED_PROPOSALS.save(deps.storage, info.sender.clone(), &claimed_proposals)?;
+    for proposal in claimed_proposals {
+        VOTERS_CLAIM.save(deps.storage, (info.sender.clone(), proposal), &true)?;
+    }
+
+    if !bribe_coins.is_empty() {
+        bribe_coins.sort_by_key(|element| element.denom.clone());
+
+        // remove all coin having amount as 0
+        bribe_coins.retain(|coin| !coin.amount.is_zero());
+
+        Ok(Response::new()
+            .add_attribute("method", "External Incentive Claimed")
+            .add_message(BankMsg::Send {
+                to_address: info.sender.to_string(),
+                amount: bribe_coins,
+            }))
+    } else {
+        Err(ContractError::CustomError {
+            val: String::from("No rewards to claim."),
+        })
+    }
+}
+
+pub fn calculate_bribe_reward(
+    deps: Deps<ComdexQuery>,
+    _env: Env,
+    info: MessageInfo,
+    all_proposals: Vec<u64>,
+    _app_id: u64,
+) -> Result<(Vec<Coin>, Vec<u64>), ContractError> {
+    let mut bribe_coins: Vec<Coin> = vec![];
+    let mut claimed_proposal =
+        match VOTERS_CLAIMED_PROPOSALS.may_load(deps.storage, info.sender.clone())? {
+            Some(val) => val,
+            None => vec![],
+        };
+    for proposalid in all_proposals {
+        if claimed_proposal.contains(&proposalid) {
+            continue;
+        }
+        let claimable_bribe = match proposal_bribe_share(deps.storage, &info.sender, proposalid)? {
+            Some(val) => val,
+            None => continue,
+        };
+
+        for bribe_deposited in claimable_bribe {
+            add_coin(&mut bribe_coins, bribe_deposited);
+        }
+        claimed_proposal.push(proposalid);
+        claimed_proposal.sort();
+    }
+
+    //// send bank message to band
+
+    Ok((bribe_coins, claimed_proposal))
+}
+
+pub fn calculate_bribe_reward_proposal(
+    deps: Deps<ComdexQuery>,
+    _env: Env,
+    info: MessageInfo,
+    proposal_id: u64,
+) -> Result<Vec<Coin>, ContractError> {
+    let bribe_coins = proposal_bribe_share(deps.storage, &info.sender, proposal_id)?.ok_or(
+        ContractError::NotFound {
+            msg: "No vote found for the proposal".to_string(),
+        },
+    )?;
+    Ok(bribe_coins)
+}
+
+pub fn calculate_rebase_reward(
+    mut deps: DepsMut<ComdexQuery>,
+    env: Env,
+    info: MessageInfo,
+    proposal_id: u64,
+) -> Result<Response<ComdexMessages>, ContractError> {
+    if !info.funds.is_empty() {
+        return Err(ContractError::FundsNotAllowed {});
+    }
+    let proposal = PROPOSAL.load(deps.storage, proposal_id)?;
+    if !is_emission_executed(&proposal) {
+        return Err(ContractError::CustomError {
+            val: "Emission for proposal not completed".to_string(),
+        });
+    }
+    let has_rebased = REBASE_CLAIMED
+        .may_load(deps.storage, (info.sender.clone(), proposal_id))?
+        .unwrap_or_default();
+
+    if has_rebased {
+        return Err(ContractError::CustomError {
+            val: "Already claimed rebase".to_string(),
+        });
+    }
+    let total_rebase_amount: u128 = proposal.rebase_distributed;
+
+    let app_response = query_app_exists(deps.as_ref(), proposal.app_id)?;
+
+    // the rebase is minted in the native gov token, CW20 locks are paid out instead of relocked
+    let native_denom = query_get_asset_data(deps.as_ref(), app_response.gov_token_id)?;
+    let gov_token_denom = query_gov_lock_denom(deps.as_ref(), proposal.app_id)?;
+    let relock = gov_token_denom == native_denom;
+    let mut payout = Uint128::zero();
+
     //// get v-tokens at proposal height
     let vtokens = match VTOKENS.may_load_at_height(
         deps.storage,
-        (info.sender.clone(), &gov_token_denom),
-        proposal.height,
-    )? {
-        Some(val) => val,
-        None => vec![],
+        (info.sender.clone(), &gov_token_denom),
+        proposal.height,
+    )? {
+        Some(val) => val,
+        None => vec![],
+    };
+
+    if vtokens.is_empty() {
+        return Err(ContractError::CustomError {
+            val: "No locked tokens for users to claim rebase".to_string(),
+        });
+    }
+    let supply = SUPPLY
+        .may_load_at_height(deps.storage, &gov_token_denom, proposal.height)?
+        .unwrap();
+    let total_locked: u128 = supply.token;
+    //// get locked amount per period and weight
+    let mut locked_by_period: Vec<(Vtoken, u128)> = vec![];
+    for vtoken in vtokens {
+        match locked_by_period.iter_mut().find(|(position, _)| {
+            position.period == vtoken.period
+                && position.auto_relock == vtoken.auto_relock
+                && position_weight(position) == position_weight(&vtoken)
+        }) {
+            Some((_, locked)) => *locked += vtoken.token.amount.u128(),
+            None => {
+                let locked = vtoken.token.amount.u128();
+                locked_by_period.push((vtoken, locked))
+            }
+        }
+    }
+
+    //// lock the rebase in the same period as the existing locks
+    let mut rebased = false;
+    for (position, locked) in locked_by_period {
+        let lock_amount = Uint128::from(locked).mul(Decimal::from_ratio(
+            Uint128::from(total_rebase_amount),
+            Uint128::from(total_locked),
+        ));
+
+        if lock_amount.is_zero() {
+            continue;
+        }
+        rebased = true;
+        if !relock {
+            payout += lock_amount;
+            continue;
+        }
+        let fund = Coin {
+            amount: lock_amount,
+            denom: gov_token_denom.clone(),
+        };
+        relock_funds(
+            deps.branch(),
+            env.clone(),
+            info.sender.clone(),
+            fund,
+            &position,
+        )?;
+    }
+
+    if !rebased {
+        return Err(ContractError::CustomError {
+            val: "Claimable rebase ratio not met for the existing locks".to_string(),
+        });
+    }
+    REBASE_CLAIMED.save(deps.storage, (info.sender.clone(), proposal_id), &true)?;
+
+    let mut response = Response::new().add_attribute("method", "rebase all holders");
+    if !payout.is_zero() {
+        response = response.add_message(BankMsg::Send {
+            to_address: info.sender.to_string(),
+            amount: vec![Coin {
+                denom: native_denom,
+                amount: payout,
+            }],
+        });
+    }
+    Ok(response)
+}
+
+pub fn calculate_surplus_reward(
+    deps: Deps<ComdexQuery>,
+    _env: Env,
+    info: MessageInfo,
+    max_proposal_claimed: u64,
+    all_proposals: Vec<u64>,
+    app_id: u64,
+) -> Result<Coin, ContractError> {
+    let mut asset_denom = String::new();
+    let mut total_claimable: u128 = 0_u128;
+    for proposalid in all_proposals {
+        if proposalid <= max_proposal_claimed {
+            continue;
+        }
+        let proposal = PROPOSAL.load(deps.storage, proposalid)?;
+        if proposal.total_surplus.denom.eq("nodenom") {
+            continue;
+        }
+        let proposal_surplus = proposal.total_surplus.amount;
+        asset_denom = proposal.total_surplus.denom;
+
+        let gov_token_denom = query_gov_lock_denom(deps, app_id)?;
+
+        let vtokens = VTOKENS.may_load_at_height(
+            deps.storage,
+            (info.sender.clone(), &gov_token_denom),
+            proposal.height,
+        )?;
+        if vtokens.is_none() {
+            continue;
+        }
+
+        let supply = SUPPLY
+            .may_load_at_height(deps.storage, &gov_token_denom, proposal.height)?
+            .unwrap();
+        let total_locked: u128 = supply.vtoken;
+        //// get rebase amount per period
+        let mut locked: u128 = 0;
+        let vtokens = vtokens.unwrap();
+        for vtoken in vtokens {
+            locked += vtoken.vtoken.amount.u128();
+        }
+        let mut share = Decimal::new(Uint128::from(locked))
+            .div(Decimal::new(Uint128::from(total_locked)))
+            .mul(Uint128::from(1_u8))
+            .u128();
+        share *= proposal_surplus.u128();
+        total_claimable += share;
+    }
+    let claim_coin = Coin {
+        amount: Uint128::from(total_claimable),
+        denom: asset_denom,
+    };
+
+    Ok(claim_coin)
+}
+
+pub fn emission(
+    deps: DepsMut<ComdexQuery>,
+    env: Env,
+    info: MessageInfo,
+    proposal_id: u64,
+) -> Result<Response<ComdexMessages>, ContractError> {
+    //// anyone can execute once the voting period has ended
+    // do not accept funds
+    if !info.funds.is_empty() {
+        return Err(ContractError::FundsNotAllowed {});
+    }
+
+    // check if already emission executed
+
+    let mut proposal = PROPOSAL.load(deps.storage, proposal_id)?;
+    //check if voting ended and emission not executed yet
+    transition_proposal(
+        &mut proposal,
+        ProposalStatus::EmissionExecuted,
+        env.block.time,
+    )?;
+
+    //check governance token via app_id
+    let app_id = proposal.app_id;
+    // the proposal is final if the next proposal of the app was already raised
+    if APPCURRENTPROPOSAL.may_load(deps.storage, app_id)? != Some(proposal_id) {
+        transition_proposal(&mut proposal, ProposalStatus::Finalized, env.block.time)?;
+    }
+    let app_response = query_app_exists(deps.as_ref(), app_id)?;
+    let gov_token_id = app_response.gov_token_id;
+    let gov_token_denom = query_get_asset_data(deps.as_ref(), gov_token_id)?;
+    if gov_token_denom.is_empty() || gov_token_id == 0 {
+        return Err(ContractError::CustomError {
+            val: "Gov token not found".to_string(),
+        });
+    }
+    // the gov token is locked as its CW20 token if set, the emission is minted natively
+    let lock_denom = query_gov_lock_denom(deps.as_ref(), app_id)?;
+
+    //// GET TOTAL V-TOKEN SUPPLY
+
+    let vtokens = SUPPLY
+        .may_load_at_height(deps.storage, &lock_denom, proposal.height)?
+        .unwrap();
+    let total_v_token = total_voting_power(
+        deps.storage,
+        &lock_denom,
+        proposal.height,
+        proposal.voting_start_time,
+    )?;
+    /////query token TOTAL SUPPLY
+    let total_weight = query_gov_token_supply(deps.as_ref(), app_id, gov_token_id)?;
+    if total_weight.is_zero() {
+        return Err(ContractError::CustomError {
+            val: "Current Circulating Supply is 0".to_string(),
+        });
+    }
+
+    //// GET TOTAL VESTED TOKEN
+    let state = STATE.load(deps.storage)?;
+    let query_msg = QueryMsg::VestedTokens {
+        denom: lock_denom.clone(),
+    };
+    let query_response: Uint128 = deps.querier.query(&QueryRequest::Wasm(WasmQuery::Smart {
+        contract_addr: state.vesting_contract.to_string(),
+        msg: to_binary(&query_msg).unwrap(),
+    }))?;
+
+    //// CALCULATE CIRCULATING SUPPLY (TOTAL SUPPLY-VESTED TOKENS-vtokens(locked))
+    let circulating_supply = total_weight - query_response - Uint128::from(vtokens.token);
+
+    //// QUERY PERCENTAGE LOCKED (VTOKENS_LOCKED/V-TOKENS+CIRCULATING SUPPLY)
+    let percentage_locked =
+        Decimal::raw(total_v_token).div(Decimal::raw(circulating_supply.u128() + total_v_token));
+
+    //// LOAD EMISSION FOR THE APP
+    let mut emission = EMISSION.load(deps.storage, proposal.app_id)?;
+
+    //// CALCULATE EFFECTIVE EMISSION = (EPOCH EMISSION AS PER SCHEDULE-KEEPER REWARD)*(1-PERCENTAGE_LOCKED)
+    let epoch_amount = epoch_emission(&emission);
+    let keeper_amount = keeper_reward(deps.storage, epoch_amount)?;
+    let reward_emission = epoch_amount - keeper_amount;
+    let effective_emission = reward_emission.mul(Decimal::one() - percentage_locked);
+    // mint and distribute to vault owner  based vote portion
+    let gauges = proposal.gauges.clone();
+    // votes on killed gauges are left out, and replaced by the fallback if missing the quorum
+    let (gauge_votes, quorum_met) =
+        allocation_gauge_votes(deps.storage, proposal_id, &proposal, total_v_token)?;
+    let total_vote: Uint128 = gauge_votes.iter().sum();
+
+    // votes above the cap of the proposal are redistributed to the other gauges
+    let capped_votes = cap_gauge_votes(&gauge_votes, proposal.gauge_vote_cap);
+    let mut votes: Vec<Uint128> = vec![];
+    let mut extd_pair: Vec<u64> = vec![];
+    let mut votes_pool: Vec<Uint128> = vec![];
+    let mut pools: Vec<(u64, u64)> = vec![];
+    for (gauge, vote) in gauges.iter().zip(capped_votes.iter()) {
+        match gauge {
+            GaugeTarget::Vault { ext_pair_id } => {
+                votes.push(*vote);
+                extd_pair.push(*ext_pair_id);
+            }
+            GaugeTarget::Pool { app_id, pool_id } => {
+                votes_pool.push(*vote);
+                pools.push((*app_id, *pool_id));
+            }
+            GaugeTarget::Contract { .. } => (),
+        }
+    }
+
+    //// UPDATE Foundation Nodes Share
+    proposal.foundation_distributed = (state.foundation_percentage.mul(effective_emission)).u128();
+    //// effective emission
+    proposal.emission_distributed =
+        effective_emission.u128() - (state.foundation_percentage.mul(effective_emission)).u128();
+    // update effective emission
+
+    //// UPDATE REBASE AMOUNT
+    //// early unlock penalties are already held by the contract and only added to the rebase
+    let rebase_minted = (reward_emission.mul(percentage_locked)).u128();
+    let penalties = PENDING_PENALTIES
+        .may_load(deps.storage, app_id)?
+        .unwrap_or_default();
+    PENDING_PENALTIES.remove(deps.storage, app_id);
+    proposal.rebase_distributed = rebase_minted + penalties;
+    //// EMISSION Data Update
+    emission.rewards_pending -= epoch_amount.u128();
+    emission.distributed_rewards += epoch_amount.u128();
+    emission.epoch += 1;
+
+    let pool_votes: Uint128 = votes_pool.iter().sum();
+    let vault_votes: Uint128 = votes.iter().sum();
+    let emission_distributed = Uint128::from(proposal.emission_distributed);
+    let uncapped = allocate_emission(emission_distributed, &gauges, &gauge_votes, total_vote);
+    let allocation = allocate_emission(emission_distributed, &gauges, &capped_votes, total_vote);
+    let pools_share = allocation.pools_share;
+    let vault_share = allocation.vault_share;
+    let rewards = allocation.rewards;
+
+    // rewards left out by the vote cap or the quorum are carried forward to the next epoch
+    proposal.emission_distributed -= allocation.unallocated.u128();
+    emission.rewards_pending += allocation.unallocated.u128();
+    emission.distributed_rewards -= allocation.unallocated.u128();
+    emission.carried_forward = allocation.unallocated.u128();
+
+    let emission_reward = EmissionVaultPool {
+        app_id,
+        gauges: gauges.clone(),
+        total_emission_rewards: Uint128::from(proposal.emission_distributed),
+        rewards: rewards.clone(),
+        uncapped_rewards: uncapped.rewards,
+    };
+
+    EMISSION_REWARD.save(deps.storage, proposal_id, &emission_reward)?;
+    let app_config = load_app_config(deps.storage, app_id)?;
+    let surplus = query_surplus_reward(deps.as_ref(), app_id, app_config.surplus_asset_id)?;
+    proposal.total_surplus = surplus.clone();
+    EMISSION.save(deps.storage, proposal.app_id, &emission)?;
+    PROPOSAL.save(deps.storage, proposal_id, &proposal)?;
+
+    let mut msg: Vec<ComdexMessages> = vec![];
+    let app_id_param = app_id;
+
+    let emission_msg = ComdexMessages::MsgEmissionRewards {
+        app_id: app_id_param,
+        amount: vault_share,
+        extended_pair: extd_pair,
+        voting_ratio: votes,
+    };
+    // pools are rewarded with a message for each cswap app, sharing the pools share by votes
+    let mut cswap_ids: Vec<u64> = pools.iter().map(|(cswap_id, _)| *cswap_id).collect();
+    cswap_ids.sort();
+    cswap_ids.dedup();
+    let mut emission_msg_pools: Vec<ComdexMessages> = vec![];
+    for cswap_id in cswap_ids {
+        let (pool_ids, cswap_votes): (Vec<u64>, Vec<Uint128>) = pools
+            .iter()
+            .zip(votes_pool.iter())
+            .filter(|((pool_app_id, _), _)| *pool_app_id == cswap_id)
+            .map(|((_, pool_id), vote)| (*pool_id, *vote))
+            .unzip();
+        let cswap_pool_votes: Uint128 = cswap_votes.iter().sum();
+        if cswap_pool_votes.is_zero() {
+            continue;
+        }
+        emission_msg_pools.push(ComdexMessages::MsgEmissionPoolRewards {
+            app_id,
+            cswap_app_id: cswap_id,
+            amount: cswap_pool_votes.multiply_ratio(pools_share, pool_votes),
+            pools: pool_ids,
+            voting_ratio: cswap_votes,
+        });
+    }
+    // contract gauges are sent their rewards, minted to this contract, with the emission hook
+    let mut contract_rewards = Uint128::zero();
+    let mut contract_msgs: Vec<SubMsg<ComdexMessages>> = vec![];
+    for (index, (gauge, reward)) in gauges.iter().zip(rewards.iter()).enumerate() {
+        if let GaugeTarget::Contract { address } = gauge {
+            if reward.is_zero() {
+                continue;
+            }
+            let amount = Coin {
+                denom: gov_token_denom.clone(),
+                amount: *reward,
+            };
+            // a failing contract must not block the emission, its share is returned on reply
+            contract_msgs.push(SubMsg::reply_on_error(
+                WasmMsg::Execute {
+                    contract_addr: address.to_string(),
+                    msg: to_json_binary(&GaugeHookMsg::ReceiveEmission {
+                        app_id,
+                        proposal_id,
+                        amount: amount.clone(),
+                    })?,
+                    funds: vec![amount],
+                },
+                (proposal_id << GAUGE_INDEX_BITS) | index as u64,
+            ));
+            contract_rewards += reward;
+        }
+    }
+    let contract_mint_msg = ComdexMessages::MsgRebaseMint {
+        app_id: app_id_param,
+        amount: contract_rewards,
+        contract_addr: env.contract.address.to_string(),
+    };
+
+    let rebase_msg = ComdexMessages::MsgRebaseMint {
+        app_id: app_id_param,
+        amount: Uint128::from(rebase_minted),
+        contract_addr: env.contract.address.to_string(),
+    };
+
+    if total_vote != Uint128::zero() {
+        if vault_votes.ne(&Uint128::zero()) {
+            msg.push(emission_msg);
+        }
+        if pool_votes.ne(&Uint128::zero()) {
+            msg.extend(emission_msg_pools);
+        }
+        if !contract_rewards.is_zero() {
+            msg.push(contract_mint_msg);
+        }
+    }
+    msg.push(rebase_msg);
+
+    if surplus.amount != Uint128::new(0) {
+        let surplus_msg = ComdexMessages::MsgGetSurplusFund {
+            app_id: app_id_param,
+            asset_id: app_config.surplus_asset_id,
+            contract_addr: env.contract.address.clone().into_string(),
+            amount: surplus,
+        };
+        msg.push(surplus_msg);
+    }
+    if !keeper_amount.is_zero() {
+        msg.push(ComdexMessages::MsgFoundationEmission {
+            app_id: app_id_param,
+            amount: keeper_amount,
+            foundation_address: vec![info.sender.to_string()],
+        });
+    }
+
+    let mut all_proposals = match COMPLETEDPROPOSALS.may_load(deps.storage, app_id)? {
+        Some(val) => val,
+        None => vec![],
+    };
+    all_proposals.push(proposal_id);
+    COMPLETEDPROPOSALS.save(deps.storage, app_id, &all_proposals)?;
+    let vec_foundation = emission_foundation(deps, env, info, proposal_id)?;
+    msg.extend(vec_foundation);
+    Ok(Response::new()
+        .add_attribute("method", "emission")
+        .add_attribute("quorum_met", quorum_met.to_string())
+        .add_attribute("keeper_reward", keeper_amount.to_string())
+        .add_messages(msg)
+        .add_submessages(contract_msgs))
+}
+
+/// Handles the failure of the emission hook of a contract gauge: its share of the emission
+/// is burnt and carried forward to the next emission of the app.
+#[cfg_attr(not(feature = "library"), entry_point)]
+pub fn reply(
+    deps: DepsMut<ComdexQuery>,
+    env: Env,
+    msg: Reply,
+) -> Result<Response<ComdexMessages>, ContractError> {
+    let error = match msg.result {
+        SubMsgResult::Err(error) => error,
+        SubMsgResult::Ok(_) => {
+            return Err(ContractError::ParseFailure(format!(
+                "Unexpected reply {}",
+                msg.id
+            )))
+        }
+    };
+    let proposal_id = msg.id >> GAUGE_INDEX_BITS;
+    let index = (msg.id & ((1 << GAUGE_INDEX_BITS) - 1)) as usize;
+
+    let mut emission_reward = EMISSION_REWARD.load(deps.storage, proposal_id)?;
+    let reward = match emission_reward.rewards.get_mut(index) {
+        Some(reward) => std::mem::take(reward),
+        None => {
+            return Err(ContractError::NotFound {
+                msg: format!("Gauge {} of proposal {} not found", index, proposal_id),
+            })
+        }
+    };
+    let app_id = emission_reward.app_id;
+    emission_reward.total_emission_rewards -= reward;
+    EMISSION_REWARD.save(deps.storage, proposal_id, &emission_reward)?;
+
+    let mut proposal = PROPOSAL.load(deps.storage, proposal_id)?;
+    proposal.emission_distributed -= reward.u128();
+    PROPOSAL.save(deps.storage, proposal_id, &proposal)?;
+    if let Some(mut emission) = EMISSION.may_load(deps.storage, app_id)? {
+        emission.rewards_pending += reward.u128();
+        emission.distributed_rewards -= reward.u128();
+        emission.carried_forward += reward.u128();
+        EMISSION.save(deps.storage, app_id, &emission)?;
+    }
+
+    let app_response = query_app_exists(deps.as_ref(), app_id)?;
+    let gov_token_denom = query_get_asset_data(deps.as_ref(), app_response.gov_token_id)?;
+    Ok(Response::new()
+        .add_attribute("method", "gauge_emission_failed")
+        .add_attribute("proposal_id", proposal_id.to_string())
+        .add_attribute("returned", reward.to_string())
+        .add_attribute("error", error)
+        .add_message(ComdexMessages::MsgBurnGovTokensForApp {
+            app_id,
+            amount: Coin {
+                denom: gov_token_denom,
+                amount: reward,
+            },
+            from: env.contract.address.to_string(),
+        }))
+}
+
+fn has_duplicate_elements<T: Eq + std::hash::Hash>(vec: &[T]) -> bool {
+    let mut seen_elements = std::collections::HashSet::new();
+    for element in vec.iter() {
+        if seen_elements.contains(element) {
+            return true;
+        }
+        seen_elements.insert(element);
+    }
+    false
+}
+pub fn vote_proposal(
+    deps: DepsMut<ComdexQuery>,
+    env: Env,
+    info: MessageInfo,
+    _app_id: u64,
+    proposal_id: u64,
+    gauges: Vec<GaugeTarget>,
+    gov_token_denom: String,
+    ratio: Vec<Decimal>,
+    persistent: Option<bool>,
+) -> Result<Response<ComdexMessages>, ContractError> {
+    // check if admin (admin cannot vote)
+    if ADMIN.is_admin(deps.as_ref(), &info.sender)? {
+        return Err(ContractError::CustomError {
+            val: "Admin cannot vote".to_string(),
+        });
+    }
+
+    // do not accept  funds
+    if !info.funds.is_empty() {
+        return Err(ContractError::FundsNotAllowed {});
+    }
+
+    //check if active proposal
+    let mut proposal = PROPOSAL.load(deps.storage, proposal_id)?;
+
+    // Check if proposal in voting period
+    if proposal_status(&proposal, env.block.time) != ProposalStatus::Active {
+        return Err(ContractError::CustomError {
+            val: "Proposal Voting Period Ended".to_string(),
+        });
+    }
+
+    // check if vote sequence is correct
+    if gauges.len() != ratio.len() {
+        return Err(ContractError::CustomError {
+            val: "Invalid ratio".to_string(),
+        });
+    }
+
+    let mut total_ration = Decimal::zero();
+    for ratio in ratio.iter() {
+        total_ration += ratio;
+    }
+
+    //// check if total ratio is 100%
+    if total_ration > Decimal::one() {
+        return Err(ContractError::CustomError {
+            val: "Ratio cannot be more than 100 %".to_string(),
+        });
+    }
+
+    if has_duplicate_elements(&gauges) {
+        return Err(ContractError::CustomError {
+            val: "Gauges have duplicate elements".to_string(),
+        });
+    }
+
+    // check if total ratio is not 0%
+    if total_ration == Decimal::zero() {
+        return Err(ContractError::CustomError {
+            val: "voted ratio cannot be zero".to_string(),
+        });
+    }
+
+    //// check if gauges exist in proposal's gauges
+    if !gauges.iter().all(|item| proposal.gauges.contains(item)) {
+        return Err(ContractError::CustomError {
+            val: "Gauge does not exist in proposal".to_string(),
+        });
+    }
+    if gauges
+        .iter()
+        .any(|item| proposal.killed_gauges.contains(item))
+    {
+        return Err(ContractError::CustomError {
+            val: "Gauge is killed".to_string(),
+        });
+    }
+
+    let vote_power = cast_vote(
+        deps.storage,
+        proposal_id,
+        &mut proposal,
+        &info.sender,
+        &gov_token_denom,
+        &gauges,
+        &ratio,
+    )?;
+    if vote_power == 0 {
+        return Err(ContractError::CustomError {
+            val: "No tokens locked to perform voting on proposals".to_string(),
+        });
+    }
+    PROPOSAL.save(deps.storage, proposal_id, &proposal)?;
+
+    // keep the allocation to re-apply it on the next proposals of the app
+    match persistent {
+        Some(true) => {
+            if !STICKY_VOTES.has(deps.storage, (proposal.app_id, info.sender.clone())) {
+                evict_weakest_sticky_vote(deps.storage, proposal.app_id, vote_power)?;
+            }
+            STICKY_VOTES.save(
+                deps.storage,
+                (proposal.app_id, info.sender),
+                &StickyVote {
+                    gauges,
+                    ratio,
+                    voting_power: vote_power,
+                },
+            )?
+        }
+        Some(false) => STICKY_VOTES.remove(deps.storage, (proposal.app_id, info.sender)),
+        None => (),
+    }
+
+    Ok(Response::new().add_attribute("method", "voted for proposal"))
+}
+
+/// Makes room for a new sticky vote cast with `voting_power` once the app holds the maximum,
+/// by evicting the sticky vote last cast with the least voting power, if less than it.
+fn evict_weakest_sticky_vote(
+    storage: &mut dyn Storage,
+    app_id: u64,
+    voting_power: u128,
+) -> Result<(), ContractError> {
+    let sticky_votes = STICKY_VOTES
+        .prefix(app_id)
+        .range(storage, None, None, Order::Ascending)
+        .take(MAX_STICKY_VOTES)
+        .collect::<StdResult<Vec<(Addr, StickyVote)>>>()?;
+    if sticky_votes.len() < MAX_STICKY_VOTES {
+        return Ok(());
+    }
+    let weakest = sticky_votes
+        .into_iter()
+        .min_by_key(|(_, sticky_vote)| sticky_vote.voting_power);
+    match weakest {
+        Some((voter, sticky_vote)) if sticky_vote.voting_power < voting_power => {
+            STICKY_VOTES.remove(storage, (app_id, voter));
+            Ok(())
+        }
+        _ => Err(ContractError::CustomError {
+            val: "Too many sticky votes for the app with more voting power".to_string(),
+        }),
+    }
+}
+
+/// Clears the sticky vote of the sender on the proposals of the app.
+pub fn handle_clear_sticky_vote(
+    deps: DepsMut<ComdexQuery>,
+    info: MessageInfo,
+    app_id: u64,
+) -> Result<Response<ComdexMessages>, ContractError> {
+    if !info.funds.is_empty() {
+        return Err(ContractError::FundsNotAllowed {});
+    }
+    if !STICKY_VOTES.has(deps.storage, (app_id, info.sender.clone())) {
+        return Err(ContractError::NotFound {
+            msg: "No sticky vote for the app".to_string(),
+        });
+    }
+    STICKY_VOTES.remove(deps.storage, (app_id, info.sender));
+
+    Ok(Response::new()
+        .add_attribute("method", "clear_sticky_vote")
+        .add_attribute("app_id", app_id.to_string()))
+}
+
+/// Re-applies the sticky votes of the app to the raised proposal, at the voting power of
+/// each voter at its snapshot. Gauges missing from the proposal are left out, and the
+/// sticky votes of voters without voting power left are dropped, the others recording the
+/// voting power cast.
+fn apply_sticky_votes(
+    storage: &mut dyn Storage,
+    proposal_id: u64,
+    proposal: &mut Proposal,
+    gov_token_denom: &str,
+) -> Result<(), ContractError> {
+    let sticky_votes = STICKY_VOTES
+        .prefix(proposal.app_id)
+        .range(storage, None, None, Order::Ascending)
+        .take(MAX_STICKY_VOTES)
+        .collect::<StdResult<Vec<(Addr, StickyVote)>>>()?;
+
+    for (voter, mut sticky_vote) in sticky_votes {
+        let (gauges, ratio): (Vec<GaugeTarget>, Vec<Decimal>) = sticky_vote
+            .gauges
+            .iter()
+            .cloned()
+            .zip(sticky_vote.ratio.iter().cloned())
+            .filter(|(gauge, _)| {
+                proposal.gauges.contains(gauge) && !proposal.killed_gauges.contains(gauge)
+            })
+            .unzip();
+        if gauges.is_empty() {
+            continue;
+        }
+        let vote_power = cast_vote(
+            storage,
+            proposal_id,
+            proposal,
+            &voter,
+            gov_token_denom,
+            &gauges,
+            &ratio,
+        )?;
+        if vote_power == 0 {
+            STICKY_VOTES.remove(storage, (proposal.app_id, voter));
+        } else {
+            sticky_vote.voting_power = vote_power;
+            STICKY_VOTES.save(storage, (proposal.app_id, voter), &sticky_vote)?;
+        }
+    }
+    PROPOSAL.save(storage, proposal_id, proposal)?;
+    Ok(())
+}
+
+/// Casts the vote of the voter on the proposal at its snapshot voting power, replacing
+/// its previous vote. Returns the voting power cast, nothing is cast without any.
+fn cast_vote(
+    storage: &mut dyn Storage,
+    proposal_id: u64,
+    proposal: &mut Proposal,
+    voter: &Addr,
+    gov_token_denom: &str,
+    gauges: &[GaugeTarget],
+    ratio: &[Decimal],
+) -> Result<u128, ContractError> {
+    let mut total_ration = Decimal::zero();
+    for ratio in ratio.iter() {
+        total_ration += ratio;
+    }
+
+    //balance of  denom for voting
+    // the voting power delegated away is cast by the delegates
+    let own_power = proposal_voting_power(storage, voter, gov_token_denom, proposal)?;
+    let delegated_away: u128 =
+        proposal_delegations(storage, voter, gov_token_denom, proposal, own_power)?
+            .iter()
+            .map(|(_, delegated)| delegated)
+            .sum();
+
+    // voting power delegated to the voter for the proposal
+    let mut delegated_votes: Vec<(Addr, u128)> = vec![];
+    let delegators = DELEGATORS
+        .may_load_at_height(storage, (voter.clone(), gov_token_denom), proposal.height)?
+        .unwrap_or_default();
+    for delegator in delegators {
+        let delegator_power =
+            proposal_voting_power(storage, &delegator, gov_token_denom, proposal)?;
+        let delegations = proposal_delegations(
+            storage,
+            &delegator,
+            gov_token_denom,
+            proposal,
+            delegator_power,
+        )?;
+        for (delegate, delegated) in delegations {
+            if delegate == *voter && delegated != 0 {
+                delegated_votes.push((delegator.clone(), delegated));
+            }
+        }
+    }
+    let delegated_power: u128 = delegated_votes.iter().map(|(_, delegated)| delegated).sum();
+    let vote_power = own_power - delegated_away + delegated_power;
+
+    if vote_power == 0 {
+        return Ok(0);
+    }
+
+    //if already voted , decrease previous vote weight
+    let has_voted = VOTERS_VOTE
+        .may_load(storage, (voter.clone(), proposal_id))?
+        .unwrap_or_default();
+    if has_voted {
+        let prev_vote = VOTERSPROPOSAL.load(storage, (voter.clone(), proposal_id))?;
+        let last_vote_weight = prev_vote.votes;
+        for pair_vote in last_vote_weight {
+            let mut proposal_vote = PROPOSALVOTE
+                .load(storage, (proposal_id, pair_vote.gauge.clone()))
+                .unwrap_or_default();
+            proposal_vote -= Uint128::from(pair_vote.vote_weight);
+            PROPOSALVOTE.save(storage, (proposal_id, pair_vote.gauge), &proposal_vote)?;
+            proposal.total_voted_weight -= pair_vote.vote_weight;
+        }
+    }
+
+    let mut vote_pair: Vec<VotePair> = vec![];
+    for (i, gauge) in gauges.iter().enumerate() {
+        let vote_pair_param = VotePair {
+            gauge: gauge.clone(),
+            vote_ratio: ratio[i],
+            vote_weight: Uint128::from(vote_power).mul(ratio[i]).u128(),
+        };
+        vote_pair.push(vote_pair_param);
+    }
+
+    for pair_vote in vote_pair.iter_mut() {
+        let mut proposal_vote = PROPOSALVOTE
+            .load(storage, (proposal_id, pair_vote.gauge.clone()))
+            .unwrap_or_default();
+        proposal_vote += Uint128::from(pair_vote.vote_weight);
+        PROPOSALVOTE.save(
+            storage,
+            (proposal_id, pair_vote.gauge.clone()),
+            &proposal_vote,
+        )?;
+        proposal.total_voted_weight += pair_vote.vote_weight;
+    }
+    let commission = if delegated_power == 0 {
+        Decimal::zero()
+    } else {
+        DELEGATION_INFO
+            .may_load_at_height(storage, voter.clone(), proposal.height)?
+            .map(|delegation_info| delegation_info.commission)
+            .unwrap_or_default()
+    };
+    let vote = Vote {
+        voting_power_total: vote_power,
+        total_voted_ratio: total_ration,
+        votes: vote_pair,
+        delegated_power,
+        commission,
+    };
+    VOTERSPROPOSAL.save(storage, (voter.clone(), proposal_id), &vote)?;
+
+    // record the voting power of the delegators, for them to claim their share of the bribes
+    for (delegator, vote_power) in delegated_votes {
+        let mut votes = DELEGATED_VOTES
+            .may_load(storage, (delegator.clone(), proposal_id))?
+            .unwrap_or_default();
+        votes.retain(|vote| vote.delegated_to != *voter);
+        votes.push(DelegatedVote {
+            delegated_to: voter.clone(),
+            vote_power,
+        });
+        DELEGATED_VOTES.save(storage, (delegator, proposal_id), &votes)?;
+    }
+    VOTERS_VOTE.save(storage, (voter.clone(), proposal_id), &true)?;
+    Ok(vote_power)
+}
+
+pub fn raise_proposal(
+    deps: DepsMut<ComdexQuery>,
+    env: Env,
+    info: MessageInfo,
+    app_id: u64,
+    mut gauges: Vec<GaugeTarget>,
+    gov_token_denom: String,
+) -> Result<Response<ComdexMessages>, ContractError> {
+    //// anyone can execute once the previous proposal has ended
+    // do not accept  funds
+    if !info.funds.is_empty() {
+        return Err(ContractError::FundsNotAllowed {});
+    }
+
+    // proposals are only raised for apps with an emission to distribute
+    if !EMISSION.has(deps.storage, app_id) {
+        return Err(ContractError::NotFound {
+            msg: format!("No emission found for app {}", app_id),
+        });
+    }
+
+    // killed gauges are left out until revived
+    gauges.retain(|gauge| !KILLED_GAUGES.has(deps.storage, (app_id, gauge.clone())));
+
+    //// No proposal
+    if gauges.is_empty() {
+        return Err(ContractError::CustomError {
+            val: "No gauge to vote".to_string(),
+        });
+    }
+    // the index of each gauge must fit in the reply id of the emission hook
+    if gauges.len() > 1 << GAUGE_INDEX_BITS {
+        return Err(ContractError::CustomError {
+            val: "Too many gauges for a proposal".to_string(),
+        });
+    }
+    //check no proposal active for app
+    let current_app_proposal = APPCURRENTPROPOSAL
+        .may_load(deps.storage, app_id)?
+        .unwrap_or(0);
+
+    // if proposal already exist , check if whether it is in voting period
+    // proposal cannot be raised until current proposal voting time is ended
+    if current_app_proposal != 0 {
+        let mut proposal = PROPOSAL.load(deps.storage, current_app_proposal)?;
+        match proposal_status(&proposal, env.block.time) {
+            ProposalStatus::Active => {
+                return Err(ContractError::CustomError {
+                    val: "Previous proposal in voting state for the app".to_string(),
+                })
+            }
+            ProposalStatus::EmissionExecuted => {
+                transition_proposal(&mut proposal, ProposalStatus::Finalized, env.block.time)?;
+                PROPOSAL.save(deps.storage, current_app_proposal, &proposal)?;
+            }
+            _ => (),
+        }
+    }
+
+    // set proposal data
+    let app_config = load_app_config(deps.storage, app_id)?;
+    let app_id_param = app_id;
+    //update proposal maps
+    let mut proposal = Proposal {
+        app_id: app_id_param,              //app_id for proposal
+        voting_start_time: env.block.time, // Current block timestamp
+        voting_end_time: voting_end_time(deps.storage, env.block.time, app_config.voting_period)?, // end voting timestamp
+        gauges,                         // gauges for which voting is taking place
+        status: ProposalStatus::Active, // open for voting
+        emission_distributed: 0,        //emission distributed token as 0
+        rebase_distributed: 0,          //rebase distributed token as 0
+        total_voted_weight: 0,          // total_weight of voted vtoken
+        foundation_distributed: 0,      // total distributed tokens as 0
+        total_surplus: Coin {
+            amount: Uint128::from(0_u32),
+            denom: "nodenom".to_string(),
+        }, // initialized dummy token
+        height: env.block.height,       // current block height of token,
+        gauge_vote_cap: app_config.gauge_vote_cap, // vote cap of each gauge
+        killed_gauges: vec![],          // no gauge killed
+        quorum: app_config.quorum,      // quorum of the votes
+        emission_fallback: app_config.emission_fallback, // allocation missing the quorum
+    };
+    let mut current_proposal = PROPOSALCOUNT.load(deps.storage).unwrap_or(0);
+    current_proposal += 1;
+    PROPOSALCOUNT.save(deps.storage, &current_proposal)?;
+    APPCURRENTPROPOSAL.save(deps.storage, app_id, &current_proposal)?;
+    PROPOSAL.save(deps.storage, current_proposal, &proposal)?;
+    release_scheduled_bribes(deps.storage, app_id, current_proposal, &proposal.gauges)?;
+    apply_sticky_votes(
+        deps.storage,
+        current_proposal,
+        &mut proposal,
+        &gov_token_denom,
+    )?;
+
+    // reward the keeper out of the emission budget of the app, the first
+    // proposal of an app does not roll a previous one over
+    let mut msg: Vec<ComdexMessages> = vec![];
+    let mut keeper_amount = Uint128::zero();
+    let emission = match current_app_proposal {
+        0 => None,
+        _ => EMISSION.may_load(deps.storage, app_id)?,
+    };
+    if let Some(mut emission) = emission {
+        keeper_amount = keeper_reward(deps.storage, Uint128::from(emission.rewards_pending))?;
+        if !keeper_amount.is_zero() {
+            emission.rewards_pending -= keeper_amount.u128();
+            emission.distributed_rewards += keeper_amount.u128();
+            EMISSION.save(deps.storage, app_id, &emission)?;
+            msg.push(ComdexMessages::MsgFoundationEmission {
+                app_id,
+                amount: keeper_amount,
+                foundation_address: vec![info.sender.to_string()],
+            });
+        }
+    }
+    Ok(Response::new()
+        .add_attribute("method", "proposal_raised")
+        .add_attribute("proposal_id", current_proposal.to_string())
+        .add_attribute("keeper_reward", keeper_amount.to_string())
+        .add_messages(msg))
+}
+
+/// Assigns position ids to the locks created before lock positions were introduced.
+fn migrate_positions(
+    storage: &mut dyn Storage,
+    env: Env,
+    denom_apps: &[DenomApp],
+) -> Result<(), ContractError> {
+    let all_vtokens = VTOKENS
+        .range(storage, None, None, Order::Ascending)
+        .collect::<StdResult<Vec<((Addr, String), Vec<Vtoken>)>>>()?;
+
+    let mut position_count = 0u64;
+    for ((owner, denom), vtokens) in all_vtokens {
+        for mut vtoken in vtokens {
+            position_count += 1;
+            vtoken.id = position_count;
+            // vtokens locked before recording their app are given the app of their denom
+            if vtoken.app_id == 0 {
+                vtoken.app_id = denom_apps
+                    .iter()
+                    .find(|denom_app| denom_app.denom == denom && denom_app.app_id != 0)
+                    .map(|denom_app| denom_app.app_id)
+                    .ok_or_else(|| ContractError::CustomError {
+                        val: format!("No app given for the locked denom {}", denom),
+                    })?;
+            }
+            let position = Position {
+                owner: owner.clone(),
+                vtoken,
+            };
+            positions().save(storage, position_count, &position)?;
+        }
+        sync_vtokens(storage, env.clone(), &owner, &denom)?;
+    }
+    POSITIONCOUNT.save(storage, &position_count)?;
+
+    Ok(())
+}
+
+/// Moves the proposal to the given status, if it is a legal transition from its status
+/// at the given time.
+fn transition_proposal(
+    proposal: &mut Proposal,
+    status: ProposalStatus,
+    time: Timestamp,
+) -> Result<(), ContractError> {
+    let current = proposal_status(proposal, time);
+    let legal = matches!(
+        (current, status),
+        (ProposalStatus::Active, ProposalStatus::Cancelled)
+            | (
+                ProposalStatus::VotingEnded,
+                ProposalStatus::EmissionExecuted
+            )
+            | (ProposalStatus::VotingEnded, ProposalStatus::Cancelled)
+            | (ProposalStatus::EmissionExecuted, ProposalStatus::Finalized)
+    );
+    if !legal {
+        return Err(ContractError::CustomError {
+            val: format!("Proposal is {:?}, cannot move to {:?}", current, status),
+        });
+    }
+    proposal.status = status;
+    Ok(())
+}
+
+/// Gauge target of an extended pair id of the legacy layout, where cswap pools were
+/// offset by `LEGACY_POOL_OFFSET`.
+fn legacy_gauge(extended_pair: u64, cswap_id: u64) -> GaugeTarget {
+    if extended_pair < LEGACY_POOL_OFFSET {
+        GaugeTarget::Vault {
+            ext_pair_id: extended_pair,
+        }
+    } else {
+        GaugeTarget::Pool {
+            app_id: cswap_id,
+            pool_id: extended_pair % LEGACY_POOL_OFFSET,
+        }
+    }
+}
+
+/// Converts the proposals, votes, bribes and emission rewards stored before gauge targets.
+fn migrate_gauges(storage: &mut dyn Storage, cswap_id: u64) -> Result<(), ContractError> {
+    let legacy_proposals = LEGACY_PROPOSAL
+        .range(storage, None, None, Order::Ascending)
+        .collect::<StdResult<Vec<(u64, LegacyProposal)>>>()?;
+    for (proposal_id, legacy) in legacy_proposals {
+        let current_proposal = APPCURRENTPROPOSAL.may_load(storage, legacy.app_id)?;
+        let legacy_status = if !legacy.emission_completed {
+            ProposalStatus::Active
+        } else if current_proposal == Some(proposal_id) {
+            ProposalStatus::EmissionExecuted
+        } else {
+            ProposalStatus::Finalized
+        };
+        let proposal = Proposal {
+            app_id: legacy.app_id,
+            voting_start_time: legacy.voting_start_time,
+            voting_end_time: legacy.voting_end_time,
+            gauges: legacy
+                .extended_pair
+                .iter()
+                .map(|extended_pair| legacy_gauge(*extended_pair, cswap_id))
+                .collect(),
+            status: legacy_status,
+            emission_distributed: legacy.emission_distributed,
+            rebase_distributed: legacy.rebase_distributed,
+            foundation_distributed: legacy.foundation_distributed,
+            total_voted_weight: legacy.total_voted_weight,
+            total_surplus: legacy.total_surplus,
+            height: legacy.height,
+            gauge_vote_cap: None,
+            killed_gauges: vec![],
+            quorum: None,
+            emission_fallback: EmissionFallback::default(),
+        };
+        PROPOSAL.save(storage, proposal_id, &proposal)?;
+    }
+
+    let legacy_votes = LEGACY_PROPOSALVOTE
+        .range(storage, None, None, Order::Ascending)
+        .collect::<StdResult<Vec<((u64, u64), Uint128)>>>()?;
+    for ((proposal_id, extended_pair), vote) in legacy_votes {
+        let gauge = legacy_gauge(extended_pair, cswap_id);
+        PROPOSALVOTE.save(storage, (proposal_id, gauge), &vote)?;
+        LEGACY_PROPOSALVOTE.remove(storage, (proposal_id, extended_pair));
+    }
+
+    let legacy_bribes = LEGACY_BRIBES_BY_PROPOSAL
+        .range(storage, None, None, Order::Ascending)
+        .collect::<StdResult<Vec<((u64, u64), Vec<Coin>)>>>()?;
+    for ((proposal_id, extended_pair), bribes) in legacy_bribes {
+        let gauge = legacy_gauge(extended_pair, cswap_id);
+        BRIBES_BY_PROPOSAL.save(storage, (proposal_id, gauge), &bribes)?;
+        LEGACY_BRIBES_BY_PROPOSAL.remove(storage, (proposal_id, extended_pair));
+    }
+
+    let legacy_voters = LEGACY_VOTERSPROPOSAL
+        .range(storage, None, None, Order::Ascending)
+        .collect::<StdResult<Vec<((Addr, u64), LegacyVote)>>>()?;
+    for (key, legacy) in legacy_voters {
+        let vote = Vote {
+            voting_power_total: legacy.voting_power_total,
+            total_voted_ratio: legacy.total_voted_ratio,
+            votes: legacy
+                .votes
+                .into_iter()
+                .map(|pair| VotePair {
+                    gauge: legacy_gauge(pair.extended_pair, cswap_id),
+                    vote_ratio: pair.vote_ratio,
+                    vote_weight: pair.vote_weight,
+                })
+                .collect(),
+            delegated_power: 0,
+            commission: Decimal::zero(),
+        };
+        VOTERSPROPOSAL.save(storage, key, &vote)?;
+    }
+
+    let legacy_rewards = LEGACY_EMISSION_REWARD
+        .range(storage, None, None, Order::Ascending)
+        .collect::<StdResult<Vec<(u64, LegacyEmissionVaultPool)>>>()?;
+    for (proposal_id, legacy) in legacy_rewards {
+        let mut gauges: Vec<GaugeTarget> = legacy
+            .vault_ids
+            .into_iter()
+            .map(|ext_pair_id| GaugeTarget::Vault { ext_pair_id })
+            .collect();
+        gauges.extend(
+            legacy
+                .pool_ids
+                .into_iter()
+                .map(|pool_id| GaugeTarget::Pool {
+                    app_id: cswap_id,
+                    pool_id,
+                }),
+        );
+        let mut rewards = legacy.vault_rewards;
+        rewards.extend(legacy.pool_rewards);
+        let emission_reward = EmissionVaultPool {
+            app_id: legacy.app_id,
+            gauges,
+            total_emission_rewards: legacy.total_emission_rewards,
+            uncapped_rewards: rewards.clone(),
+            rewards,
+        };
+        EMISSION_REWARD.save(storage, proposal_id, &emission_reward)?;
+    }
+
+    Ok(())
+}
+
+#[entry_point]
+pub fn migrate(deps: DepsMut, env: Env, msg: MigrateMsg) -> Result<Response, ContractError> {
+    let ver = cw2::get_contract_version(deps.storage)?;
+    // ensure we are migrating from an allowed contract
+    if ver.contract != CONTRACT_NAME {
+        return Err(StdError::generic_err("Can only upgrade from same type").into());
+    }
+    // note: better to do proper semver compare, but string compare *usually* works
+    if ver.version.as_str() > CONTRACT_VERSION {
+        return Err(StdError::generic_err("Cannot upgrade from a newer version").into());
+    }
+    CSWAP_ID.save(deps.storage, &msg.cswap_id)?;
+    if POSITIONCOUNT.may_load(deps.storage)?.is_none() {
+        migrate_positions(deps.storage, env, &msg.denom_apps)?;
+    }
+    // proposals raised before gauge targets still have the legacy layout
+    let has_legacy_proposals = matches!(
+        LEGACY_PROPOSAL
+            .range(deps.storage, None, None, Order::Ascending)
+            .next(),
+        Some(Ok(_))
+    );
+    if has_legacy_proposals {
+        migrate_gauges(deps.storage, msg.cswap_id)?;
+    }
+    // set the new version
+    cw2::set_contract_version(deps.storage, CONTRACT_NAME, CONTRACT_VERSION)?;
+    //do any desired state migrations...
+
+    Ok(Response::default())
+}
+
+#[entry_point]
+pub fn sudo(deps: DepsMut, env: Env, msg: SudoMsg) -> Result<Response, ContractError> {
+    match msg {
+        SudoMsg::UpdateVestingContract { address } => {
+            let mut state = STATE.load(deps.storage)?;
+            state.vesting_contract = deps.api.addr_validate(&address.into_string())?;
+            STATE.save(deps.storage, &state)?;
+            Ok(Response::new())
+        }
+        SudoMsg::UpdateEmissionRate {
+            emission_rate,
+            app_id,
+        } => {
+            let mut emission = EMISSION.load(deps.storage, app_id)?;
+            if emission_rate > Decimal::one() {
+                return Err(ContractError::CustomError {
+                    val: "Emission rate cannot be greater one".to_string(),
+                });
+            }
+            emission.emission_rate = emission_rate;
+            EMISSION.save(deps.storage, emission.app_id, &emission)?;
+            Ok(Response::new())
+        }
+        SudoMsg::RegisterEmission { emission } => {
+            if EMISSION.has(deps.storage, emission.app_id) {
+                return Err(ContractError::CustomError {
+                    val: "Emission already registered for the app".to_string(),
+                });
+            }
+            validate_emission(&emission)?;
+            EMISSION.save(deps.storage, emission.app_id, &emission)?;
+            Ok(Response::new())
+        }
+        SudoMsg::UpdateEmissionSchedule { app_id, schedule } => {
+            let mut emission = EMISSION.load(deps.storage, app_id)?;
+            validate_emission_schedule(&schedule)?;
+            emission.schedule = schedule;
+            EMISSION.save(deps.storage, app_id, &emission)?;
+            Ok(Response::new())
+        }
+        SudoMsg::TopUpEmission { app_id, amount } => {
+            if amount == 0 {
+                return Err(ContractError::CustomError {
+                    val: "Top up amount should not be zero".to_string(),
+                });
+            }
+            let mut emission = EMISSION.load(deps.storage, app_id)?;
+            emission.total_rewards += amount;
+            emission.rewards_pending += amount;
+            EMISSION.save(deps.storage, app_id, &emission)?;
+            Ok(Response::new())
+        }
+        SudoMsg::RetireEmission { app_id } => {
+            if !EMISSION.has(deps.storage, app_id) {
+                return Err(ContractError::NotFound {
+                    msg: format!("No emission found for app {}", app_id),
+                });
+            }
+            // the proposal of the app still waiting for its emission must be emitted first
+            if let Some(proposal_id) = APPCURRENTPROPOSAL.may_load(deps.storage, app_id)? {
+                let proposal = PROPOSAL.load(deps.storage, proposal_id)?;
+                if matches!(
+                    proposal_status(&proposal, env.block.time),
+                    ProposalStatus::Active | ProposalStatus::VotingEnded
+                ) {
+                    return Err(ContractError::CustomError {
+                        val: "Proposal of the app is waiting for its emission".to_string(),
+                    });
+                }
+            }
+            EMISSION.remove(deps.storage, app_id);
+            Ok(Response::new())
+        }
+        SudoMsg::UpdateFoundationInfo {
+            addresses,
+            foundation_percentage,
+        } => {
+            if foundation_percentage > Decimal::one() {
+                return Err(ContractError::CustomError {
+                    val: "Foundation Emission percentage cannot be greater than 100 %".to_string(),
+                });
+            }
+            let mut state = STATE.load(deps.storage)?;
+            map_validate(deps.api, &addresses)?;
+            state.foundation_addr = addresses;
+            state.foundation_addr.sort_unstable();
+            state.foundation_addr.dedup();
+            state.foundation_percentage = foundation_percentage;
+            STATE.save(deps.storage, &state)?;
+            Ok(Response::new())
+        }
+        SudoMsg::UpdateLockingPeriod { t1, t2 } => {
+            let mut state = STATE.load(deps.storage)?;
+            state.t1 = t1;
+            state.t2 = t2;
+            STATE.save(deps.storage, &state)?;
+            Ok(Response::new())
+        }
+        SudoMsg::UpdateAdmin { admin } => {
+            deps.api.addr_validate(&admin.clone().into_string())?;
+            ADMIN.set(deps, Some(admin))?;
+            Ok(Response::new())
+        }
+        SudoMsg::UpdateVotingPeriod { voting_period } => {
+            let mut state = STATE.load(deps.storage)?;
+            state.voting_period = voting_period;
+            STATE.save(deps.storage, &state)?;
+            Ok(Response::new())
+        }
+        SudoMsg::UpdateWeightCurve { weight_curve } => {
+            match weight_curve {
+                Some(weight_curve) => {
+                    validate_weight_curve(&weight_curve)?;
+                    WEIGHT_CURVE.save(deps.storage, &weight_curve)?;
+                }
+                None => WEIGHT_CURVE.remove(deps.storage),
+            }
+            Ok(Response::new())
+        }
+        SudoMsg::UpdateVotingDecay { voting_decay } => {
+            VOTING_DECAY.save(deps.storage, &voting_decay)?;
+            Ok(Response::new())
+        }
+        SudoMsg::UpdateAppConfig { app_id, app_config } => {
+            match app_config {
+                Some(app_config) => {
+                    if matches!(
+                        app_config.gauge_vote_cap,
+                        Some(cap) if cap.is_zero() || cap > Decimal::one()
+                    ) {
+                        return Err(ContractError::CustomError {
+                            val: "Gauge vote cap should be between 0 and 100 %".to_string(),
+                        });
+                    }
+                    if matches!(app_config.quorum, Some(quorum) if quorum > Decimal::one()) {
+                        return Err(ContractError::CustomError {
+                            val: "Quorum cannot be more than 100 %".to_string(),
+                        });
+                    }
+                    APP_CONFIG.save(deps.storage, app_id, &app_config)?
+                }
+                None => APP_CONFIG.remove(deps.storage, app_id),
+            }
+            Ok(Response::new())
+        }
+        SudoMsg::UpdateCw20GovToken { app_id, address } => {
+            match address {
+                Some(address) => {
+                    let address = deps.api.addr_validate(address.as_str())?;
+                    CW20_GOV_TOKENS.save(deps.storage, app_id, &address)?;
+                }
+                None => CW20_GOV_TOKENS.remove(deps.storage, app_id),
+            }
+            Ok(Response::new())
+        }
+        SudoMsg::UpdateKeeperConfig { keeper_config } => {
+            match keeper_config {
+                Some(keeper_config) => KEEPER_CONFIG.save(deps.storage, &keeper_config)?,
+                None => KEEPER_CONFIG.remove(deps.storage),
+            }
+            Ok(Response::new())
+        }
+        SudoMsg::UpdateEpochAlignment { epoch_alignment } => {
+            match epoch_alignment {
+                Some(0) => {
+                    return Err(ContractError::CustomError {
+                        val: "Epoch alignment cannot be zero".to_string(),
+                    })
+                }
+                Some(epoch_alignment) => EPOCH_ALIGNMENT.save(deps.storage, &epoch_alignment)?,
+                None => EPOCH_ALIGNMENT.remove(deps.storage),
+            }
+            Ok(Response::new())
+        }
+        SudoMsg::UpdateEarlyUnlock { early_unlock } => {
+            match early_unlock {
+                Some(early_unlock) => {
+                    validate_early_unlock(&early_unlock)?;
+                    EARLY_UNLOCK.save(deps.storage, &early_unlock)?;
+                }
+                None => EARLY_UNLOCK.remove(deps.storage),
+            }
+            Ok(Response::new())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::query::{query_positions, query_proposals};
+    use crate::state::{
+        EarlyUnlockConfig, Emission, EmissionFallback, EmissionSchedule, KeeperConfig,
+        LegacyProposal, PenaltyDestination, DEFAULT_MIN_SCHEDULED_BRIBE, LEGACY_POOL_OFFSET,
+    };
+    use comdex_bindings::{
+        GetAppResponse, GetAssetDataResponse, GetExtendedPairByAppResponse, GetPoolByAppResponse,
+        GetSurplusRewardAmount, GetWhitelistedAssetResponse, TotalSupplyResponse,
+    };
+    use cosmwasm_std::testing::{mock_env, mock_info, MockApi, MockQuerier, MockStorage};
+    use cosmwasm_std::{
+        to_json_binary, Attribute, ContractResult, CosmosMsg, OwnedDeps, QuerierWrapper, ReplyOn,
+        SystemResult, Timestamp, WasmQuery,
     };
+    use cw20::TokenInfoResponse;
+    use std::marker::PhantomData;
+
+    const DENOM: &str = "ucmdx";
+    const APP_ID: u64 = 1;
+    const CW20_TOKEN: &str = "cw20token";
+
+    type TestDeps = OwnedDeps<MockStorage, MockApi, MockQuerier<ComdexQuery>, ComdexQuery>;
+
+    fn mock_dependencies() -> TestDeps {
+        let mut querier = MockQuerier::<ComdexQuery>::new(&[]).with_custom_handler(|query| {
+            let response = match query {
+                // the gov token of each app is the asset with the id of the app
+                ComdexQuery::GetApp { app_id } => to_json_binary(&GetAppResponse {
+                    min_gov_deposit: "0".to_string(),
+                    gov_time_in_seconds: 0,
+                    gov_token_id: *app_id,
+                }),
+                ComdexQuery::GetAssetData { asset_id } => to_json_binary(&GetAssetDataResponse {
+                    denom: match asset_id {
+                        1 => DENOM.to_string(),
+                        _ => format!("uasset{}", asset_id),
+                    },
+                }),
+                ComdexQuery::ExtendedPairByApp { .. } => {
+                    to_json_binary(&GetExtendedPairByAppResponse {
+                        ext_pair: vec![1, 2],
+                    })
+                }
+                ComdexQuery::GetPoolByApp { .. } => {
+                    to_json_binary(&GetPoolByAppResponse { pools: vec![1] })
+                }
+                ComdexQuery::TotalSupply { .. } => to_json_binary(&TotalSupplyResponse {
+                    current_supply: 1_000_000,
+                }),
+                ComdexQuery::CheckSurplusReward { .. } => to_json_binary(&GetSurplusRewardAmount {
+                    amount: Coin::new(0, "usurplus"),
+                }),
+                ComdexQuery::CheckWhitelistedAsset { .. } => {
+                    to_json_binary(&GetWhitelistedAssetResponse { found: true })
+                }
+                _ => panic!("unexpected query {:?}", query),
+            };
+            SystemResult::Ok(ContractResult::Ok(response.unwrap()))
+        });
+        // the CW20 gov token has the supply of the native one, and nothing is vested
+        querier.update_wasm(|query| {
+            let response = match query {
+                WasmQuery::Smart { contract_addr, .. } if contract_addr == CW20_TOKEN => {
+                    to_json_binary(&TokenInfoResponse {
+                        name: "token".to_string(),
+                        symbol: "TKN".to_string(),
+                        decimals: 6,
+                        total_supply: Uint128::new(1_000_000),
+                    })
+                }
+                _ => to_json_binary(&Uint128::zero()),
+            };
+            SystemResult::Ok(ContractResult::Ok(response.unwrap()))
+        });
+        OwnedDeps {
+            storage: MockStorage::default(),
+            api: MockApi::default(),
+            querier,
+            custom_query_type: PhantomData,
+        }
+    }
+
+    fn instantiate_msg() -> InstantiateMsg {
+        InstantiateMsg {
+            t1: PeriodWeight {
+                period: 100,
+                weight: Decimal::percent(25),
+            },
+            t2: PeriodWeight {
+                period: 200,
+                weight: Decimal::percent(50),
+            },
+            voting_period: 1000,
+            vesting_contract: Addr::unchecked("vesting"),
+            foundation_addr: vec!["foundation".to_string()],
+            foundation_percentage: Decimal::percent(2),
+            surplus_asset_id: 3,
+            emission: Emission {
+                app_id: APP_ID,
+                total_rewards: 200_000,
+                rewards_pending: 200_000,
+                emission_rate: Decimal::percent(2),
+                distributed_rewards: 0,
+                schedule: EmissionSchedule::Constant,
+                epoch: 0,
+                carried_forward: 0,
+            },
+            admin: Addr::unchecked("admin"),
+            min_lock_amount: Uint128::new(1),
+            cswap_id: 1,
+            weight_curve: None,
+            voting_decay: false,
+            early_unlock: None,
+        }
+    }
+
+    fn instantiate_contract(deps: DepsMut<ComdexQuery>, msg: InstantiateMsg) {
+        instantiate(deps, mock_env(), mock_info("sender", &[]), msg).unwrap();
+    }
+
+    /// Locks the amount for the voter, moving to the next block for the lock to be part of
+    /// the snapshot of the proposals raised from then on.
+    fn lock(
+        deps: DepsMut<ComdexQuery>,
+        env: &mut Env,
+        voter: &str,
+        amount: u128,
+        locking_period: LockingPeriod,
+    ) {
+        execute(
+            deps,
+            env.clone(),
+            mock_info(voter, &[Coin::new(amount, DENOM)]),
+            ExecuteMsg::Lock {
+                app_id: APP_ID,
+                locking_period,
+                recipient: None,
+                auto_relock: None,
+            },
+        )
+        .unwrap();
+        env.block.height += 1;
+    }
+
+    fn sudo_deps(deps: &mut TestDeps) -> DepsMut<'_> {
+        DepsMut {
+            storage: &mut deps.storage,
+            api: &deps.api,
+            querier: QuerierWrapper::new(&deps.querier),
+        }
+    }
+
+    fn slope_change(deps: &TestDeps, time: Timestamp) -> Option<Decimal> {
+        SLOPE_CHANGES
+            .may_load(&deps.storage, (DENOM, time.seconds()))
+            .unwrap()
+    }
+
+    fn power_at(deps: &TestDeps, height: u64, time: Timestamp) -> u128 {
+        total_voting_power(&deps.storage, DENOM, height, time).unwrap()
+    }
+
+    #[test]
+    fn decaying_voting_power_follows_slope_changes() {
+        let mut deps = mock_dependencies();
+        let mut msg = instantiate_msg();
+        msg.voting_decay = true;
+        instantiate_contract(deps.as_mut(), msg);
+        let mut env = mock_env();
+        let start = env.block.time;
+
+        // 250 vtokens over 100 seconds and 1000 vtokens over 200 seconds
+        lock(deps.as_mut(), &mut env, "voter", 1000, LockingPeriod::T1);
+        lock(deps.as_mut(), &mut env, "voter", 2000, LockingPeriod::T2);
+        assert_eq!(
+            slope_change(&deps, start.plus_seconds(100)),
+            Some(Decimal::permille(2500))
+        );
+        assert_eq!(
+            slope_change(&deps, start.plus_seconds(200)),
+            Some(Decimal::percent(500))
+        );
+
+        let height = env.block.height;
+        assert_eq!(power_at(&deps, height, start), 1250);
+        assert_eq!(power_at(&deps, height, start.plus_seconds(50)), 875);
+        assert_eq!(power_at(&deps, height, start.plus_seconds(100)), 500);
+        assert_eq!(power_at(&deps, height, start.plus_seconds(150)), 250);
+        assert_eq!(power_at(&deps, height, start.plus_seconds(250)), 0);
+        // the first lock is not part of the snapshot before it was created
+        assert_eq!(power_at(&deps, height - 1, start), 250);
+    }
+
+    #[test]
+    fn locks_do_not_decay_unless_enabled() {
+        let mut deps = mock_dependencies();
+        instantiate_contract(deps.as_mut(), instantiate_msg());
+        let mut env = mock_env();
+        let start = env.block.time;
+
+        lock(deps.as_mut(), &mut env, "voter", 1000, LockingPeriod::T1);
+        assert_eq!(slope_change(&deps, start.plus_seconds(100)), None);
+        assert_eq!(
+            power_at(&deps, env.block.height, start.plus_seconds(150)),
+            250
+        );
+    }
+
+    #[test]
+    fn extend_lock_reweights_supply_and_decay() {
+        let mut deps = mock_dependencies();
+        let mut msg = instantiate_msg();
+        msg.voting_decay = true;
+        instantiate_contract(deps.as_mut(), msg);
+        let mut env = mock_env();
+        let start = env.block.time;
+        lock(deps.as_mut(), &mut env, "voter", 1000, LockingPeriod::T1);
+        lock(deps.as_mut(), &mut env, "voter", 2000, LockingPeriod::T2);
+        let extend = |deps: DepsMut<ComdexQuery>, env: &Env, position_id: u64, locking_period| {
+            execute(
+                deps,
+                env.clone(),
+                mock_info("voter", &[]),
+                ExecuteMsg::ExtendLock {
+                    position_id,
+                    locking_period,
+                },
+            )
+        };
 
-    if vtokens.is_empty() {
-        return Err(ContractError::CustomError {
-            val: "No locked tokens for users to claim rebase".to_string(),
-        });
+        // a lock cannot be shortened, nor extended to a lower weight
+        let err = extend(deps.as_mut(), &env, 2, LockingPeriod::T1).unwrap_err();
+        assert_eq!(
+            err,
+            ContractError::CustomError {
+                val: "Lock cannot end earlier than the existing lock".to_string()
+            }
+        );
+        let err = extend(deps.as_mut(), &env, 3, LockingPeriod::T2).unwrap_err();
+        assert_eq!(
+            err,
+            ContractError::NotFound {
+                msg: "No lock position found for id 3".to_string()
+            }
+        );
+
+        env.block.time = start.plus_seconds(50);
+        extend(deps.as_mut(), &env, 1, LockingPeriod::T2).unwrap();
+        env.block.height += 1;
+
+        let supply = SUPPLY.load(&deps.storage, DENOM).unwrap();
+        assert_eq!((supply.token, supply.vtoken), (3000, 1500));
+        let vtokens = VTOKENS
+            .load(&deps.storage, (Addr::unchecked("voter"), DENOM))
+            .unwrap();
+        assert_eq!(vtokens[0].vtoken.amount, Uint128::new(500));
+        assert_eq!(vtokens[0].end_time, start.plus_seconds(250));
+
+        // the slope change of the lock moves to its new end time
+        assert_eq!(slope_change(&deps, start.plus_seconds(100)), None);
+        assert_eq!(
+            slope_change(&deps, start.plus_seconds(250)),
+            Some(Decimal::permille(2500))
+        );
+        let height = env.block.height;
+        assert_eq!(power_at(&deps, height, start.plus_seconds(50)), 1250);
+        assert_eq!(power_at(&deps, height, start.plus_seconds(200)), 125);
+        assert_eq!(power_at(&deps, height, start.plus_seconds(250)), 0);
+        // the earlier snapshot still reads the decay before the extension
+        assert_eq!(power_at(&deps, height - 1, start.plus_seconds(50)), 875);
     }
-    let supply = SUPPLY
-        .may_load_at_height(deps.storage, &gov_token_denom, proposal.height)?
-        .unwrap();
-    let total_locked: u128 = supply.token;
-    //// get rebase amount per period
-    let (locked_t1, locked_t2): (u128, u128) =
-        vtokens
-            .iter()
-            .fold((0, 0), |(acc_t1, acc_t2), vtoken| match vtoken.period {
-                LockingPeriod::T1 => (acc_t1 + vtoken.token.amount.u128(), acc_t2),
-                LockingPeriod::T2 => (acc_t1, acc_t2 + vtoken.token.amount.u128()),
-            });
 
-    //// lock in t1
-    let lock_amount_t1 = Uint128::from(locked_t1).mul(Decimal::from_ratio(
-        Uint128::from(total_rebase_amount),
-        Uint128::from(total_locked),
-    ));
+    #[test]
+    fn locks_are_addressed_by_position_id() {
+        let mut deps = mock_dependencies();
+        instantiate_contract(deps.as_mut(), instantiate_msg());
+        let mut env = mock_env();
+        lock(deps.as_mut(), &mut env, "voter", 1000, LockingPeriod::T1);
+        lock(deps.as_mut(), &mut env, "other", 500, LockingPeriod::T2);
+        lock(deps.as_mut(), &mut env, "voter", 2000, LockingPeriod::T2);
+
+        let owned_ids = |deps: &TestDeps, owner: &str| -> Vec<u64> {
+            query_positions(
+                deps.as_ref(),
+                mock_env(),
+                Some(Addr::unchecked(owner)),
+                Some(DENOM.to_string()),
+                None,
+                None,
+            )
+            .unwrap()
+            .iter()
+            .map(|position| position.vtoken.id)
+            .collect()
+        };
+        assert_eq!(owned_ids(&deps, "voter"), vec![1, 3]);
+        assert_eq!(owned_ids(&deps, "other"), vec![2]);
 
-    if lock_amount_t1 > Uint128::zero() {
-        let fund_t1 = Coin {
-            amount: lock_amount_t1,
-            denom: gov_token_denom.clone(),
+        // positions of others cannot be withdrawn
+        env.block.time = env.block.time.plus_seconds(101);
+        let withdraw = |deps: DepsMut<ComdexQuery>, sender: &str, position_id: u64| {
+            execute(
+                deps,
+                env.clone(),
+                mock_info(sender, &[]),
+                ExecuteMsg::Withdraw {
+                    denom: DENOM.to_string(),
+                    amount: None,
+                    position_ids: Some(vec![position_id]),
+                    recipient: None,
+                },
+            )
         };
+        let err = withdraw(deps.as_mut(), "other", 1).unwrap_err();
+        assert_eq!(err, ContractError::Unauthorized {});
+
+        // withdrawing a position keeps the ids of the remaining ones
+        withdraw(deps.as_mut(), "voter", 1).unwrap();
+        assert!(positions().may_load(&deps.storage, 1).unwrap().is_none());
+        assert_eq!(owned_ids(&deps, "voter"), vec![3]);
+        let vtokens = VTOKENS
+            .load(&deps.storage, (Addr::unchecked("voter"), DENOM))
+            .unwrap();
+        assert_eq!(vtokens.len(), 1);
+        assert_eq!(vtokens[0].id, 3);
 
-        lock_funds(
-            deps.branch(),
+        lock(deps.as_mut(), &mut env, "voter", 1000, LockingPeriod::T1);
+        assert_eq!(owned_ids(&deps, "voter"), vec![3, 4]);
+    }
+
+    #[test]
+    fn custom_locks_rebase_after_weight_curve_removed() {
+        let mut deps = mock_dependencies();
+        instantiate_contract(deps.as_mut(), instantiate_msg());
+        let mut env = mock_env();
+        let update_weight_curve = |deps: &mut TestDeps, weight_curve| {
+            sudo(
+                sudo_deps(deps),
+                mock_env(),
+                SudoMsg::UpdateWeightCurve { weight_curve },
+            )
+            .unwrap();
+        };
+        update_weight_curve(
+            &mut deps,
+            Some(WeightCurve {
+                min_period: 100,
+                max_period: 200,
+                curve_type: CurveType::Linear,
+                points: vec![
+                    PeriodWeight {
+                        period: 100,
+                        weight: Decimal::percent(25),
+                    },
+                    PeriodWeight {
+                        period: 200,
+                        weight: Decimal::percent(50),
+                    },
+                ],
+            }),
+        );
+        let period = LockingPeriod::Custom { duration: 150 };
+        lock(deps.as_mut(), &mut env, "voter", 100_000, period.clone());
+        raise(deps.as_mut(), &env);
+        vote(deps.as_mut(), &env, "voter", 1, vec![vault(1)]);
+        env.block.time = env.block.time.plus_seconds(1001);
+        run_emission(deps.as_mut(), &env, 1);
+
+        // the rebase is relocked with the weight of the position, not the removed curve
+        update_weight_curve(&mut deps, None);
+        execute(
+            deps.as_mut(),
             env.clone(),
-            proposal.app_id,
-            info.sender.clone(),
-            fund_t1,
-            LockingPeriod::T1,
-        )?;
+            mock_info("voter", &[]),
+            ExecuteMsg::Rebase { proposal_id: 1 },
+        )
+        .unwrap();
+        let rebase = PROPOSAL.load(&deps.storage, 1).unwrap().rebase_distributed;
+        let vtokens = VTOKENS
+            .load(&deps.storage, (Addr::unchecked("voter"), DENOM))
+            .unwrap();
+        let relocked = vtokens.last().unwrap();
+        assert_eq!(relocked.period, period);
+        assert_eq!(relocked.weight, Some(Decimal::permille(375)));
+        assert_eq!(relocked.token.amount, Uint128::new(rebase));
+        assert_eq!(relocked.end_time, env.block.time.plus_seconds(150));
+    }
+
+    fn legacy_vtoken(amount: u128, env: &Env) -> Vtoken {
+        Vtoken {
+            id: 0,
+            app_id: 0,
+            token: Coin::new(amount, DENOM),
+            vtoken: Coin::new(amount / 4, format!("v{}", DENOM)),
+            period: LockingPeriod::T1,
+            start_time: env.block.time,
+            end_time: env.block.time.plus_seconds(100),
+            status: Status::Locked,
+            decay: false,
+            auto_relock: false,
+            weight: None,
+        }
+    }
+
+    #[test]
+    fn migration_assigns_position_ids() {
+        let mut deps = mock_dependencies();
+        let env = mock_env();
+        for (owner, amounts) in [("alice", vec![1000, 2000]), ("carol", vec![500])] {
+            let vtokens: Vec<Vtoken> = amounts
+                .into_iter()
+                .map(|amount| legacy_vtoken(amount, &env))
+                .collect();
+            VTOKENS
+                .save(
+                    &mut deps.storage,
+                    (Addr::unchecked(owner), DENOM),
+                    &vtokens,
+                    env.block.height,
+                )
+                .unwrap();
+        }
+
+        let err = migrate_positions(&mut deps.storage, env.clone(), &[]).unwrap_err();
+        assert_eq!(
+            err,
+            ContractError::CustomError {
+                val: format!("No app given for the locked denom {}", DENOM)
+            }
+        );
+        let denom_apps = [DenomApp {
+            denom: DENOM.to_string(),
+            app_id: APP_ID,
+        }];
+        migrate_positions(&mut deps.storage, env.clone(), &denom_apps).unwrap();
+
+        assert_eq!(POSITIONCOUNT.load(&deps.storage).unwrap(), 3);
+        let alice = VTOKENS
+            .load(&deps.storage, (Addr::unchecked("alice"), DENOM))
+            .unwrap();
+        assert_eq!(
+            alice.iter().map(|vtoken| vtoken.id).collect::<Vec<u64>>(),
+            vec![1, 2]
+        );
+        let position = positions().load(&deps.storage, 3).unwrap();
+        assert_eq!(position.owner, Addr::unchecked("carol"));
+        assert_eq!(position.vtoken.token.amount, Uint128::new(500));
+        assert_eq!(position.vtoken.app_id, APP_ID);
+        assert!(alice.iter().all(|vtoken| vtoken.app_id == APP_ID));
     }
-    let lock_amount_t2 = Uint128::from(locked_t2).mul(Decimal::from_ratio(
-        Uint128::from(total_rebase_amount),
-        Uint128::from(total_locked),
-    ));
 
-    if lock_amount_t2 > Uint128::zero() {
-        let fund_t2 = Coin {
-            amount: lock_amount_t2,
-            denom: gov_token_denom,
+    #[test]
+    fn positions_without_app_are_rejected() {
+        let mut deps = mock_dependencies();
+        instantiate_contract(deps.as_mut(), instantiate_msg());
+        let env = mock_env();
+        let position = Position {
+            owner: Addr::unchecked("voter"),
+            vtoken: legacy_vtoken(1000, &env),
         };
-        lock_funds(
-            deps.branch(),
+        positions().save(&mut deps.storage, 1, &position).unwrap();
+
+        let err = execute(
+            deps.as_mut(),
             env,
-            proposal.app_id,
-            info.sender.clone(),
-            fund_t2,
-            LockingPeriod::T2,
-        )?;
+            mock_info("voter", &[]),
+            ExecuteMsg::ExtendLock {
+                position_id: 1,
+                locking_period: LockingPeriod::T2,
+            },
+        )
+        .unwrap_err();
+        assert_eq!(
+            err,
+            ContractError::CustomError {
+                val: "Lock position 1 has no app".to_string()
+            }
+        );
     }
 
-    if lock_amount_t1 == Uint128::zero() && lock_amount_t2 == Uint128::zero() {
-        return Err(ContractError::CustomError {
-            val: "Claimable rebase ratio not met for the existing locks".to_string(),
+    fn position_sums(deps: &TestDeps, owner: &str) -> (u128, u128) {
+        VTOKENS
+            .load(&deps.storage, (Addr::unchecked(owner), DENOM))
+            .unwrap()
+            .iter()
+            .fold((0, 0), |(token, vtoken), position| {
+                (
+                    token + position.token.amount.u128(),
+                    vtoken + position.vtoken.amount.u128(),
+                )
+            })
+    }
+
+    #[test]
+    fn split_and_merge_keep_supply_consistent() {
+        let mut deps = mock_dependencies();
+        let mut msg = instantiate_msg();
+        msg.voting_decay = true;
+        instantiate_contract(deps.as_mut(), msg);
+        let mut env = mock_env();
+        lock(deps.as_mut(), &mut env, "voter", 1000, LockingPeriod::T1);
+        let supply = SUPPLY.load(&deps.storage, DENOM).unwrap();
+        let decay = SUPPLY_DECAY.load(&deps.storage, DENOM).unwrap();
+        assert_eq!(position_sums(&deps, "voter"), (supply.token, supply.vtoken));
+
+        execute(
+            deps.as_mut(),
+            env.clone(),
+            mock_info("voter", &[]),
+            ExecuteMsg::SplitPosition {
+                position_id: 1,
+                amount: Uint128::new(400),
+            },
+        )
+        .unwrap();
+        env.block.height += 1;
+        let split = positions().load(&deps.storage, 2).unwrap().vtoken;
+        assert_eq!(split.vtoken.amount, Uint128::new(100));
+        assert_eq!(split.end_time, env.block.time.plus_seconds(100));
+        assert_eq!(SUPPLY.load(&deps.storage, DENOM).unwrap(), supply);
+        assert_eq!(position_sums(&deps, "voter"), (supply.token, supply.vtoken));
+        let split_decay = SUPPLY_DECAY.load(&deps.storage, DENOM).unwrap();
+        assert_eq!(split_decay.vtoken, decay.vtoken);
+        assert_eq!(split_decay.slope, decay.slope);
+
+        execute(
+            deps.as_mut(),
+            env.clone(),
+            mock_info("voter", &[]),
+            ExecuteMsg::MergePositions {
+                position_ids: vec![1, 2],
+            },
+        )
+        .unwrap();
+        assert!(!positions().has(&deps.storage, 2));
+        assert_eq!(
+            positions()
+                .load(&deps.storage, 1)
+                .unwrap()
+                .vtoken
+                .token
+                .amount,
+            Uint128::new(1000)
+        );
+        assert_eq!(SUPPLY.load(&deps.storage, DENOM).unwrap(), supply);
+        assert_eq!(position_sums(&deps, "voter"), (supply.token, supply.vtoken));
+        let merged_decay = SUPPLY_DECAY.load(&deps.storage, DENOM).unwrap();
+        assert_eq!(merged_decay.vtoken, decay.vtoken);
+        assert_eq!(merged_decay.slope, decay.slope);
+    }
+
+    #[test]
+    fn positions_of_other_periods_are_not_merged() {
+        let mut deps = mock_dependencies();
+        instantiate_contract(deps.as_mut(), instantiate_msg());
+        let mut env = mock_env();
+        lock(deps.as_mut(), &mut env, "voter", 1000, LockingPeriod::T1);
+        lock(deps.as_mut(), &mut env, "voter", 1000, LockingPeriod::T2);
+
+        let err = execute(
+            deps.as_mut(),
+            env.clone(),
+            mock_info("voter", &[]),
+            ExecuteMsg::MergePositions {
+                position_ids: vec![1, 2],
+            },
+        )
+        .unwrap_err();
+        assert_eq!(
+            err,
+            ContractError::CustomError {
+                val: "Only positions of the same denom and locking period can be merged"
+                    .to_string()
+            }
+        );
+        let err = execute(
+            deps.as_mut(),
+            env,
+            mock_info("voter", &[]),
+            ExecuteMsg::SplitPosition {
+                position_id: 1,
+                amount: Uint128::new(1000),
+            },
+        )
+        .unwrap_err();
+        assert_eq!(
+            err,
+            ContractError::CustomError {
+                val: "Split amount should be between zero and the locked amount".to_string()
+            }
+        );
+    }
+
+    fn early_withdraw(
+        deps: DepsMut<ComdexQuery>,
+        env: &Env,
+        position_id: u64,
+    ) -> Response<ComdexMessages> {
+        execute(
+            deps,
+            env.clone(),
+            mock_info("voter", &[]),
+            ExecuteMsg::EarlyWithdraw { position_id },
+        )
+        .unwrap()
+    }
+
+    fn bank_send(to_address: &str, amount: u128) -> CosmosMsg<ComdexMessages> {
+        CosmosMsg::Bank(BankMsg::Send {
+            to_address: to_address.to_string(),
+            amount: vec![Coin::new(amount, DENOM)],
+        })
+    }
+
+    #[test]
+    fn early_unlock_penalty_decreases_to_zero() {
+        let mut deps = mock_dependencies();
+        let mut msg = instantiate_msg();
+        msg.early_unlock = Some(EarlyUnlockConfig {
+            max_penalty: Decimal::percent(50),
+            destination: PenaltyDestination::Redistribute,
         });
+        instantiate_contract(deps.as_mut(), msg);
+        let mut env = mock_env();
+        let start = env.block.time;
+        lock(deps.as_mut(), &mut env, "voter", 1000, LockingPeriod::T1);
+
+        let vtoken = positions().load(&deps.storage, 1).unwrap().vtoken;
+        let penalty_at = |seconds: u64| {
+            early_unlock_penalty(&vtoken, Decimal::percent(50), start.plus_seconds(seconds))
+        };
+        assert_eq!(penalty_at(0), Uint128::new(500));
+        assert_eq!(penalty_at(25), Uint128::new(375));
+        assert_eq!(penalty_at(99), Uint128::new(5));
+        assert_eq!(penalty_at(100), Uint128::zero());
+        assert_eq!(penalty_at(150), Uint128::zero());
+
+        env.block.time = start.plus_seconds(40);
+        let res = early_withdraw(deps.as_mut(), &env, 1);
+        assert_eq!(res.messages[0].msg, bank_send("voter", 700));
+        assert_eq!(PENDING_PENALTIES.load(&deps.storage, APP_ID).unwrap(), 300);
+        assert!(!positions().has(&deps.storage, 1));
+        let supply = SUPPLY.load(&deps.storage, DENOM).unwrap();
+        assert_eq!((supply.token, supply.vtoken), (0, 0));
     }
-    REBASE_CLAIMED.save(deps.storage, (info.sender, proposal_id), &true)?;
 
-    Ok(Response::new().add_attribute("method", "rebase all holders"))
-}
+    #[test]
+    fn early_unlock_penalty_is_sent_to_the_foundation() {
+        let mut deps = mock_dependencies();
+        let mut msg = instantiate_msg();
+        msg.foundation_addr = vec!["foundation1".to_string(), "foundation2".to_string()];
+        msg.early_unlock = Some(EarlyUnlockConfig {
+            max_penalty: Decimal::percent(50),
+            destination: PenaltyDestination::Foundation,
+        });
+        instantiate_contract(deps.as_mut(), msg);
+        let mut env = mock_env();
+        lock(deps.as_mut(), &mut env, "voter", 1001, LockingPeriod::T1);
+
+        // the remainder of the split goes to the first address
+        let res = early_withdraw(deps.as_mut(), &env, 1);
+        let messages: Vec<CosmosMsg<ComdexMessages>> =
+            res.messages.into_iter().map(|msg| msg.msg).collect();
+        assert_eq!(
+            messages,
+            vec![
+                bank_send("voter", 501),
+                bank_send("foundation1", 250),
+                bank_send("foundation2", 250),
+            ]
+        );
+    }
 
-pub fn calculate_surplus_reward(
-    deps: Deps<ComdexQuery>,
-    _env: Env,
-    info: MessageInfo,
-    max_proposal_claimed: u64,
-    all_proposals: Vec<u64>,
-    app_id: u64,
-) -> Result<Coin, ContractError> {
-    let mut asset_denom = String::new();
-    let mut total_claimable: u128 = 0_u128;
-    for proposalid in all_proposals {
-        if proposalid <= max_proposal_claimed {
-            continue;
-        }
-        let proposal = PROPOSAL.load(deps.storage, proposalid)?;
-        if proposal.total_surplus.denom.eq("nodenom") {
-            continue;
-        }
-        let proposal_surplus = proposal.total_surplus.amount;
-        asset_denom = proposal.total_surplus.denom;
+    #[test]
+    fn early_unlock_penalty_is_redistributed_to_the_app_of_the_position() {
+        let mut deps = mock_dependencies();
+        let mut msg = instantiate_msg();
+        msg.early_unlock = Some(EarlyUnlockConfig {
+            max_penalty: Decimal::percent(50),
+            destination: PenaltyDestination::Redistribute,
+        });
+        instantiate_contract(deps.as_mut(), msg);
+        let env = mock_env();
+        execute(
+            deps.as_mut(),
+            env.clone(),
+            mock_info("voter", &[Coin::new(1000, "uasset2")]),
+            ExecuteMsg::Lock {
+                app_id: 2,
+                locking_period: LockingPeriod::T1,
+                recipient: None,
+                auto_relock: None,
+            },
+        )
+        .unwrap();
 
-        let app_response = query_app_exists(deps, app_id)?;
-        let gov_token_denom = query_get_asset_data(deps, app_response.gov_token_id)?;
+        let res = early_withdraw(deps.as_mut(), &env, 1);
+        assert_eq!(
+            res.messages[0].msg,
+            CosmosMsg::Bank(BankMsg::Send {
+                to_address: "voter".to_string(),
+                amount: vec![Coin::new(500, "uasset2")],
+            })
+        );
+        assert_eq!(PENDING_PENALTIES.load(&deps.storage, 2).unwrap(), 500);
+        assert!(!PENDING_PENALTIES.has(&deps.storage, APP_ID));
+    }
 
-        let vtokens = VTOKENS.may_load_at_height(
-            deps.storage,
-            (info.sender.clone(), &gov_token_denom),
-            proposal.height,
-        )?;
-        if vtokens.is_none() {
-            continue;
-        }
+    #[test]
+    fn auto_relock_unlocks_after_a_cooldown() {
+        let mut deps = mock_dependencies();
+        let mut msg = instantiate_msg();
+        msg.voting_decay = true;
+        instantiate_contract(deps.as_mut(), msg);
+        let mut env = mock_env();
+        let start = env.block.time;
+        execute(
+            deps.as_mut(),
+            env.clone(),
+            mock_info("voter", &[Coin::new(1000, DENOM)]),
+            ExecuteMsg::Lock {
+                app_id: APP_ID,
+                locking_period: LockingPeriod::T1,
+                recipient: None,
+                auto_relock: Some(true),
+            },
+        )
+        .unwrap();
+        env.block.height += 1;
+        let withdraw = |deps: DepsMut<ComdexQuery>, env: &Env| {
+            execute(
+                deps,
+                env.clone(),
+                mock_info("voter", &[]),
+                ExecuteMsg::Withdraw {
+                    denom: DENOM.to_string(),
+                    amount: None,
+                    position_ids: Some(vec![1]),
+                    recipient: None,
+                },
+            )
+        };
 
-        let supply = SUPPLY
-            .may_load_at_height(deps.storage, &gov_token_denom, proposal.height)?
+        // auto-relock positions keep their full weight and never unlock on their own
+        assert!(!positions().load(&deps.storage, 1).unwrap().vtoken.decay);
+        env.block.time = start.plus_seconds(500);
+        assert_eq!(power_at(&deps, env.block.height, env.block.time), 250);
+        assert!(withdraw(deps.as_mut(), &env).is_err());
+
+        execute(
+            deps.as_mut(),
+            env.clone(),
+            mock_info("voter", &[]),
+            ExecuteMsg::StartUnlock { position_id: 1 },
+        )
+        .unwrap();
+        env.block.height += 1;
+        let vtoken = positions().load(&deps.storage, 1).unwrap().vtoken;
+        assert!(!vtoken.auto_relock);
+        assert_eq!(vtoken.end_time, start.plus_seconds(600));
+        // the cooldown decays as any other lock
+        assert_eq!(
+            power_at(&deps, env.block.height, start.plus_seconds(550)),
+            125
+        );
+        let err = execute(
+            deps.as_mut(),
+            env.clone(),
+            mock_info("voter", &[]),
+            ExecuteMsg::StartUnlock { position_id: 1 },
+        )
+        .unwrap_err();
+        assert_eq!(
+            err,
+            ContractError::CustomError {
+                val: "Lock position is not auto-relocking".to_string()
+            }
+        );
+
+        env.block.time = start.plus_seconds(550);
+        assert!(withdraw(deps.as_mut(), &env).is_err());
+        env.block.time = start.plus_seconds(601);
+        let res = withdraw(deps.as_mut(), &env).unwrap();
+        assert_eq!(res.messages[0].msg, bank_send("voter", 1000));
+    }
+
+    fn withdraw(
+        deps: DepsMut<ComdexQuery>,
+        env: &Env,
+        amount: Option<u128>,
+        position_ids: Option<Vec<u64>>,
+        recipient: Option<&str>,
+    ) -> Result<Response<ComdexMessages>, ContractError> {
+        execute(
+            deps,
+            env.clone(),
+            mock_info("voter", &[]),
+            ExecuteMsg::Withdraw {
+                denom: DENOM.to_string(),
+                amount: amount.map(Uint128::new),
+                position_ids,
+                recipient: recipient.map(|recipient| recipient.to_string()),
+            },
+        )
+    }
+
+    fn supply(deps: &TestDeps) -> (u128, u128) {
+        let supply = SUPPLY.load(&deps.storage, DENOM).unwrap();
+        (supply.token, supply.vtoken)
+    }
+
+    #[test]
+    fn withdraw_by_position_ids() {
+        let mut deps = mock_dependencies();
+        instantiate_contract(deps.as_mut(), instantiate_msg());
+        let mut env = mock_env();
+        lock(deps.as_mut(), &mut env, "voter", 1000, LockingPeriod::T1);
+        lock(deps.as_mut(), &mut env, "voter", 2000, LockingPeriod::T1);
+        lock(deps.as_mut(), &mut env, "voter", 4000, LockingPeriod::T2);
+
+        env.block.time = env.block.time.plus_seconds(101);
+        let err = withdraw(deps.as_mut(), &env, None, Some(vec![1, 3]), None).unwrap_err();
+        assert_eq!(
+            err,
+            ContractError::CustomError {
+                val: "Lock position 3 is still locked".to_string()
+            }
+        );
+        let err = withdraw(deps.as_mut(), &env, None, Some(vec![2, 2]), None).unwrap_err();
+        assert_eq!(
+            err,
+            ContractError::CustomError {
+                val: "Position ids has duplicate elements".to_string()
+            }
+        );
+
+        let res = withdraw(deps.as_mut(), &env, None, Some(vec![2]), None).unwrap();
+        assert_eq!(res.messages[0].msg, bank_send("voter", 2000));
+        assert!(positions().may_load(&deps.storage, 1).unwrap().is_some());
+        assert!(positions().may_load(&deps.storage, 2).unwrap().is_none());
+        assert_eq!(supply(&deps), (5000, 2250));
+    }
+
+    #[test]
+    fn withdraw_a_partial_amount() {
+        let mut deps = mock_dependencies();
+        instantiate_contract(deps.as_mut(), instantiate_msg());
+        let mut env = mock_env();
+        lock(deps.as_mut(), &mut env, "voter", 1000, LockingPeriod::T1);
+        lock(deps.as_mut(), &mut env, "voter", 2000, LockingPeriod::T1);
+
+        env.block.time = env.block.time.plus_seconds(101);
+        // the first position is withdrawn fully, the second keeps the remaining tokens
+        let res = withdraw(deps.as_mut(), &env, Some(1500), None, None).unwrap();
+        assert_eq!(res.messages[0].msg, bank_send("voter", 1500));
+        assert!(positions().may_load(&deps.storage, 1).unwrap().is_none());
+        let vtoken = positions().load(&deps.storage, 2).unwrap().vtoken;
+        assert_eq!(vtoken.token.amount.u128(), 1500);
+        assert_eq!(vtoken.vtoken.amount.u128(), 375);
+        assert_eq!(supply(&deps), (1500, 375));
+
+        let err = withdraw(deps.as_mut(), &env, Some(1501), None, None).unwrap_err();
+        assert_eq!(
+            err,
+            ContractError::CustomError {
+                val: "Only 1500 unlocked tokens available for \"ucmdx\"".to_string()
+            }
+        );
+    }
+
+    #[test]
+    fn withdraw_to_a_recipient() {
+        let mut deps = mock_dependencies();
+        instantiate_contract(deps.as_mut(), instantiate_msg());
+        let mut env = mock_env();
+        lock(deps.as_mut(), &mut env, "voter", 1000, LockingPeriod::T1);
+
+        env.block.time = env.block.time.plus_seconds(101);
+        let res = withdraw(deps.as_mut(), &env, None, None, Some("recipient")).unwrap();
+        assert_eq!(res.messages[0].msg, bank_send("recipient", 1000));
+        assert_eq!(supply(&deps), (0, 0));
+    }
+
+    #[test]
+    fn withdraw_all_sends_every_unlocked_denom() {
+        let mut deps = mock_dependencies();
+        instantiate_contract(deps.as_mut(), instantiate_msg());
+        let mut env = mock_env();
+        lock(deps.as_mut(), &mut env, "voter", 1000, LockingPeriod::T1);
+        lock(deps.as_mut(), &mut env, "voter", 2000, LockingPeriod::T2);
+        let mut lock_other = |env: &Env| {
+            execute(
+                deps.as_mut(),
+                env.clone(),
+                mock_info("voter", &[Coin::new(500, "uasset2")]),
+                ExecuteMsg::Lock {
+                    app_id: 2,
+                    locking_period: LockingPeriod::T1,
+                    recipient: None,
+                    auto_relock: None,
+                },
+            )
             .unwrap();
-        let total_locked: u128 = supply.vtoken;
-        //// get rebase amount per period
-        let mut locked: u128 = 0;
-        let vtokens = vtokens.unwrap();
-        for vtoken in vtokens {
-            locked += vtoken.vtoken.amount.u128();
+        };
+        lock_other(&env);
+
+        env.block.time = env.block.time.plus_seconds(101);
+        let res = execute(
+            deps.as_mut(),
+            env.clone(),
+            mock_info("voter", &[]),
+            ExecuteMsg::WithdrawAll {
+                recipient: Some("recipient".to_string()),
+            },
+        )
+        .unwrap();
+        let mut amount = vec![Coin::new(500, "uasset2"), Coin::new(1000, DENOM)];
+        if let CosmosMsg::Bank(BankMsg::Send {
+            to_address,
+            amount: sent,
+        }) = &res.messages[0].msg
+        {
+            assert_eq!(to_address, "recipient");
+            let mut sent = sent.clone();
+            sent.sort_by(|a, b| a.denom.cmp(&b.denom));
+            amount.sort_by(|a, b| a.denom.cmp(&b.denom));
+            assert_eq!(sent, amount);
+        } else {
+            panic!("unexpected message {:?}", res.messages[0].msg);
         }
-        let mut share = Decimal::new(Uint128::from(locked))
-            .div(Decimal::new(Uint128::from(total_locked)))
-            .mul(Uint128::from(1_u8))
-            .u128();
-        share *= proposal_surplus.u128();
-        total_claimable += share;
+        // the locked position is kept
+        assert_eq!(supply(&deps), (2000, 1000));
+        assert!(positions().may_load(&deps.storage, 2).unwrap().is_some());
     }
-    let claim_coin = Coin {
-        amount: Uint128::from(total_claimable),
-        denom: asset_denom,
-    };
 
-    Ok(claim_coin)
-}
+    fn owned_positions(deps: &TestDeps, owner: &str) -> Vec<u64> {
+        VTOKENS
+            .may_load(&deps.storage, (Addr::unchecked(owner), DENOM))
+            .unwrap()
+            .unwrap_or_default()
+            .iter()
+            .map(|vtoken| vtoken.id)
+            .collect()
+    }
 
-pub fn emission(
-    deps: DepsMut<ComdexQuery>,
-    env: Env,
-    info: MessageInfo,
-    proposal_id: u64,
-) -> Result<Response<ComdexMessages>, ContractError> {
-    //// Only admin can execute
-    ADMIN.assert_admin(deps.as_ref(), &info.sender)?;
+    #[test]
+    fn offered_positions_move_once_accepted() {
+        let mut deps = mock_dependencies();
+        instantiate_contract(deps.as_mut(), instantiate_msg());
+        let mut env = mock_env();
+        lock(deps.as_mut(), &mut env, "owner", 1000, LockingPeriod::T1);
+        let transfer = |deps: DepsMut<ComdexQuery>, recipient: &str, require_acceptance| {
+            execute(
+                deps,
+                env.clone(),
+                mock_info("owner", &[]),
+                ExecuteMsg::TransferPosition {
+                    position_id: 1,
+                    recipient: recipient.to_string(),
+                    require_acceptance,
+                },
+            )
+        };
+        let accept = |deps: DepsMut<ComdexQuery>, sender: &str| {
+            execute(
+                deps,
+                env.clone(),
+                mock_info(sender, &[]),
+                ExecuteMsg::AcceptPosition { position_id: 1 },
+            )
+        };
+
+        let err = transfer(deps.as_mut(), "owner", None).unwrap_err();
+        assert_eq!(
+            err,
+            ContractError::CustomError {
+                val: "Cannot transfer a lock position to its owner".to_string()
+            }
+        );
+
+        // an offer keeps the position with its owner until accepted by the recipient
+        transfer(deps.as_mut(), "buyer", Some(true)).unwrap();
+        assert_eq!(owned_positions(&deps, "owner"), vec![1]);
+        let err = accept(deps.as_mut(), "other").unwrap_err();
+        assert_eq!(
+            err,
+            ContractError::NotFound {
+                msg: "No offer found for lock position 1".to_string()
+            }
+        );
+        accept(deps.as_mut(), "buyer").unwrap();
+        assert_eq!(owned_positions(&deps, "owner"), Vec::<u64>::new());
+        assert_eq!(owned_positions(&deps, "buyer"), vec![1]);
+        assert_eq!(
+            positions().load(&deps.storage, 1).unwrap().owner,
+            Addr::unchecked("buyer")
+        );
+        assert!(!POSITION_OFFERS.has(&deps.storage, 1));
+        assert!(accept(deps.as_mut(), "buyer").is_err());
+    }
+
+    #[test]
+    fn positions_transfer_directly_without_acceptance() {
+        let mut deps = mock_dependencies();
+        instantiate_contract(deps.as_mut(), instantiate_msg());
+        let mut env = mock_env();
+        lock(deps.as_mut(), &mut env, "owner", 1000, LockingPeriod::T1);
+
+        execute(
+            deps.as_mut(),
+            env.clone(),
+            mock_info("owner", &[]),
+            ExecuteMsg::TransferPosition {
+                position_id: 1,
+                recipient: "buyer".to_string(),
+                require_acceptance: None,
+            },
+        )
+        .unwrap();
+        assert_eq!(owned_positions(&deps, "buyer"), vec![1]);
+        assert!(TOKENS.has(&deps.storage, Addr::unchecked("buyer")));
+        // the supply is unchanged
+        assert_eq!(SUPPLY.load(&deps.storage, DENOM).unwrap().token, 1000);
+
+        // the previous owner no longer controls the position
+        let err = execute(
+            deps.as_mut(),
+            env.clone(),
+            mock_info("owner", &[]),
+            ExecuteMsg::CancelPositionOffer { position_id: 1 },
+        )
+        .unwrap_err();
+        assert_eq!(err, ContractError::Unauthorized {});
+    }
+
+    #[test]
+    fn changed_positions_cancel_their_offer() {
+        let mut deps = mock_dependencies();
+        instantiate_contract(deps.as_mut(), instantiate_msg());
+        let env = mock_env();
+        execute(
+            deps.as_mut(),
+            env.clone(),
+            mock_info("owner", &[Coin::new(1000, DENOM)]),
+            ExecuteMsg::Lock {
+                app_id: APP_ID,
+                locking_period: LockingPeriod::T1,
+                recipient: None,
+                auto_relock: Some(true),
+            },
+        )
+        .unwrap();
+        let owner_execute = |deps: DepsMut<ComdexQuery>, msg: ExecuteMsg| {
+            execute(deps, env.clone(), mock_info("owner", &[]), msg).unwrap();
+        };
+        let offer = || ExecuteMsg::TransferPosition {
+            position_id: 1,
+            recipient: "buyer".to_string(),
+            require_acceptance: Some(true),
+        };
+
+        owner_execute(deps.as_mut(), offer());
+        assert!(POSITION_OFFERS.has(&deps.storage, 1));
+        owner_execute(
+            deps.as_mut(),
+            ExecuteMsg::ExtendLock {
+                position_id: 1,
+                locking_period: LockingPeriod::T2,
+            },
+        );
+        assert!(!POSITION_OFFERS.has(&deps.storage, 1));
+
+        owner_execute(deps.as_mut(), offer());
+        owner_execute(deps.as_mut(), ExecuteMsg::StartUnlock { position_id: 1 });
+        assert!(!POSITION_OFFERS.has(&deps.storage, 1));
+    }
+
+    fn receive_lock(
+        deps: DepsMut<ComdexQuery>,
+        env: &Env,
+        token: &str,
+        amount: u128,
+    ) -> Result<Response<ComdexMessages>, ContractError> {
+        execute(
+            deps,
+            env.clone(),
+            mock_info(token, &[]),
+            ExecuteMsg::Receive(Cw20ReceiveMsg {
+                sender: "voter".to_string(),
+                amount: Uint128::new(amount),
+                msg: to_json_binary(&Cw20HookMsg::Lock {
+                    app_id: APP_ID,
+                    locking_period: LockingPeriod::T1,
+                    recipient: None,
+                    auto_relock: None,
+                })
+                .unwrap(),
+            }),
+        )
+    }
+
+    #[test]
+    fn cw20_gov_tokens_are_locked_through_receive() {
+        let mut deps = mock_dependencies();
+        instantiate_contract(deps.as_mut(), instantiate_msg());
+        let mut env = mock_env();
+
+        // only the registered CW20 gov token of the app is accepted
+        let err = receive_lock(deps.as_mut(), &env, CW20_TOKEN, 1000).unwrap_err();
+        assert_eq!(
+            err,
+            ContractError::CustomError {
+                val: "Wrong Deposit token".to_string()
+            }
+        );
+        sudo(
+            sudo_deps(&mut deps),
+            env.clone(),
+            SudoMsg::UpdateCw20GovToken {
+                app_id: APP_ID,
+                address: Some(Addr::unchecked(CW20_TOKEN)),
+            },
+        )
+        .unwrap();
+        assert!(receive_lock(deps.as_mut(), &env, "other", 1000).is_err());
+        receive_lock(deps.as_mut(), &env, CW20_TOKEN, 1000).unwrap();
+
+        let denom = cw20_denom(&Addr::unchecked(CW20_TOKEN));
+        let vtoken = positions().load(&deps.storage, 1).unwrap().vtoken;
+        assert_eq!(vtoken.token, Coin::new(1000, denom.clone()));
+        assert_eq!(SUPPLY.load(&deps.storage, &denom).unwrap().vtoken, 250);
+
+        // withdrawals are sent back as a CW20 transfer
+        env.block.time = env.block.time.plus_seconds(101);
+        let res = execute(
+            deps.as_mut(),
+            env,
+            mock_info("voter", &[]),
+            ExecuteMsg::Withdraw {
+                denom,
+                amount: None,
+                position_ids: None,
+                recipient: None,
+            },
+        )
+        .unwrap();
+        assert_eq!(
+            res.messages[0].msg,
+            CosmosMsg::Wasm(WasmMsg::Execute {
+                contract_addr: CW20_TOKEN.to_string(),
+                msg: to_json_binary(&Cw20ExecuteMsg::Transfer {
+                    recipient: "voter".to_string(),
+                    amount: Uint128::new(1000),
+                })
+                .unwrap(),
+                funds: vec![],
+            })
+        );
+    }
+
+    #[test]
+    fn cw20_locks_vote_and_receive_rebase() {
+        let mut deps = mock_dependencies();
+        instantiate_contract(deps.as_mut(), instantiate_msg());
+        let mut env = mock_env();
+        sudo(
+            sudo_deps(&mut deps),
+            env.clone(),
+            SudoMsg::UpdateCw20GovToken {
+                app_id: APP_ID,
+                address: Some(Addr::unchecked(CW20_TOKEN)),
+            },
+        )
+        .unwrap();
+
+        // native locks are rejected once the app has a CW20 gov token
+        let err = execute(
+            deps.as_mut(),
+            env.clone(),
+            mock_info("voter", &[Coin::new(1000, DENOM)]),
+            ExecuteMsg::Lock {
+                app_id: APP_ID,
+                locking_period: LockingPeriod::T1,
+                recipient: None,
+                auto_relock: None,
+            },
+        )
+        .unwrap_err();
+        assert_eq!(
+            err,
+            ContractError::CustomError {
+                val: "Wrong Deposit token".to_string()
+            }
+        );
+
+        receive_lock(deps.as_mut(), &env, CW20_TOKEN, 100_000).unwrap();
+        env.block.height += 1;
+        raise(deps.as_mut(), &env);
+        vote(deps.as_mut(), &env, "voter", 1, vec![vault(1)]);
+        assert_eq!(
+            PROPOSALVOTE.load(&deps.storage, (1, vault(1))).unwrap(),
+            Uint128::new(25_000)
+        );
+
+        env.block.time = env.block.time.plus_seconds(1001);
+        run_emission(deps.as_mut(), &env, 1);
+        let rebase = PROPOSAL.load(&deps.storage, 1).unwrap().rebase_distributed;
+        assert!(rebase > 0);
+
+        let res = execute(
+            deps.as_mut(),
+            env,
+            mock_info("voter", &[]),
+            ExecuteMsg::Rebase { proposal_id: 1 },
+        )
+        .unwrap();
+        assert_eq!(res.messages[0].msg, bank_send("voter", rebase));
+    }
+
+    fn app_config(weight: u64, min_lock_amount: u128) -> AppConfig {
+        AppConfig {
+            t1: PeriodWeight {
+                period: 100,
+                weight: Decimal::percent(weight),
+            },
+            t2: PeriodWeight {
+                period: 200,
+                weight: Decimal::percent(2 * weight),
+            },
+            min_lock_amount: Uint128::new(min_lock_amount),
+            voting_period: 2000,
+            surplus_asset_id: 3,
+            gauge_vote_cap: None,
+            quorum: None,
+            emission_fallback: EmissionFallback::CarryForward,
+            min_scheduled_bribe: DEFAULT_MIN_SCHEDULED_BRIBE,
+        }
+    }
+
+    #[test]
+    fn apps_without_config_fall_back_to_state() {
+        let mut deps = mock_dependencies();
+        instantiate_contract(deps.as_mut(), instantiate_msg());
+        let env = mock_env();
+        sudo(
+            sudo_deps(&mut deps),
+            env.clone(),
+            SudoMsg::UpdateAppConfig {
+                app_id: 2,
+                app_config: Some(app_config(40, 500)),
+            },
+        )
+        .unwrap();
+        let lock_app = |deps: DepsMut<ComdexQuery>, app_id: u64, amount: u128| {
+            execute(
+                deps,
+                env.clone(),
+                mock_info("voter", &[Coin::new(amount, format!("uasset{}", app_id))]),
+                ExecuteMsg::Lock {
+                    app_id,
+                    locking_period: LockingPeriod::T1,
+                    recipient: None,
+                    auto_relock: None,
+                },
+            )
+        };
+
+        // the config of the app applies to its locks
+        let err = lock_app(deps.as_mut(), 2, 400).unwrap_err();
+        assert_eq!(
+            err,
+            ContractError::CustomError {
+                val: "Lock amount less than minimum lock amount".to_string()
+            }
+        );
+        lock_app(deps.as_mut(), 2, 1000).unwrap();
+        let vtoken = positions().load(&deps.storage, 1).unwrap().vtoken;
+        assert_eq!(vtoken.app_id, 2);
+        assert_eq!(vtoken.vtoken.amount.u128(), 400);
+
+        // other apps use the defaults held in State
+        lock_app(deps.as_mut(), 3, 400).unwrap();
+        let vtoken = positions().load(&deps.storage, 2).unwrap().vtoken;
+        assert_eq!(vtoken.app_id, 3);
+        assert_eq!(vtoken.vtoken.amount.u128(), 100);
+        let state = STATE.load(&deps.storage).unwrap();
+        let config = load_app_config(&deps.storage, 3).unwrap();
+        assert_eq!(config.t1, state.t1);
+        assert_eq!(config.voting_period, state.voting_period);
+
+        // removing the config of an app restores the defaults
+        sudo(
+            sudo_deps(&mut deps),
+            env,
+            SudoMsg::UpdateAppConfig {
+                app_id: 2,
+                app_config: None,
+            },
+        )
+        .unwrap();
+        assert_eq!(load_app_config(&deps.storage, 2).unwrap(), config);
+    }
+
+    #[test]
+    fn app_emissions_are_registered_topped_up_and_retired() {
+        let mut deps = mock_dependencies();
+        instantiate_contract(deps.as_mut(), instantiate_msg());
+        let env = mock_env();
+        let emission = Emission {
+            app_id: 2,
+            total_rewards: 1000,
+            rewards_pending: 1000,
+            emission_rate: Decimal::percent(5),
+            distributed_rewards: 0,
+            schedule: EmissionSchedule::Constant,
+            epoch: 0,
+            carried_forward: 0,
+        };
+        let run = |deps: &mut TestDeps, msg: SudoMsg| sudo(sudo_deps(deps), env.clone(), msg);
+
+        run(
+            &mut deps,
+            SudoMsg::RegisterEmission {
+                emission: emission.clone(),
+            },
+        )
+        .unwrap();
+        let err = run(
+            &mut deps,
+            SudoMsg::RegisterEmission {
+                emission: emission.clone(),
+            },
+        )
+        .unwrap_err();
+        assert_eq!(
+            err,
+            ContractError::CustomError {
+                val: "Emission already registered for the app".to_string()
+            }
+        );
+        let err = run(
+            &mut deps,
+            SudoMsg::RegisterEmission {
+                emission: Emission {
+                    app_id: 3,
+                    distributed_rewards: 10,
+                    ..emission.clone()
+                },
+            },
+        )
+        .unwrap_err();
+        assert_eq!(
+            err,
+            ContractError::CustomError {
+                val: "Distributed rewards should be zero".to_string()
+            }
+        );
+
+        run(
+            &mut deps,
+            SudoMsg::TopUpEmission {
+                app_id: 2,
+                amount: 500,
+            },
+        )
+        .unwrap();
+        let topped_up = EMISSION.load(&deps.storage, 2).unwrap();
+        assert_eq!(topped_up.total_rewards, 1500);
+        assert_eq!(topped_up.rewards_pending, 1500);
+        let err = run(
+            &mut deps,
+            SudoMsg::TopUpEmission {
+                app_id: 2,
+                amount: 0,
+            },
+        )
+        .unwrap_err();
+        assert_eq!(
+            err,
+            ContractError::CustomError {
+                val: "Top up amount should not be zero".to_string()
+            }
+        );
+
+        run(&mut deps, SudoMsg::RetireEmission { app_id: 2 }).unwrap();
+        let err = run(&mut deps, SudoMsg::RetireEmission { app_id: 2 }).unwrap_err();
+        assert_eq!(
+            err,
+            ContractError::NotFound {
+                msg: "No emission found for app 2".to_string()
+            }
+        );
+        assert!(!EMISSION.has(&deps.storage, 2));
+    }
+
+    #[test]
+    fn retire_emission_waits_for_pending_proposal() {
+        let mut deps = mock_dependencies();
+        instantiate_contract(deps.as_mut(), instantiate_msg());
+        let mut env = mock_env();
+        lock(deps.as_mut(), &mut env, "voter", 1000, LockingPeriod::T1);
+        raise(deps.as_mut(), &env);
+        vote(deps.as_mut(), &env, "voter", 1, vec![vault(1)]);
+
+        env.block.time = env.block.time.plus_seconds(1001);
+        let retire = || SudoMsg::RetireEmission { app_id: APP_ID };
+        let err = sudo(sudo_deps(&mut deps), env.clone(), retire()).unwrap_err();
+        assert_eq!(
+            err,
+            ContractError::CustomError {
+                val: "Proposal of the app is waiting for its emission".to_string()
+            }
+        );
+
+        run_emission(deps.as_mut(), &env, 1);
+        sudo(sudo_deps(&mut deps), env, retire()).unwrap();
+        assert!(!EMISSION.has(&deps.storage, APP_ID));
+    }
 
-    // do not accept funds
-    if !info.funds.is_empty() {
-        return Err(ContractError::FundsNotAllowed {});
+    fn scheduled_emission(schedule: EmissionSchedule, epoch: u64) -> u128 {
+        epoch_emission(&Emission {
+            app_id: APP_ID,
+            total_rewards: 10_000,
+            rewards_pending: 5000,
+            emission_rate: Decimal::percent(10),
+            distributed_rewards: 5000,
+            schedule,
+            epoch,
+            carried_forward: 0,
+        })
+        .u128()
     }
 
-    // check if already emission executed
+    #[test]
+    fn emission_schedules() {
+        assert_eq!(scheduled_emission(EmissionSchedule::Constant, 7), 500);
 
-    let mut proposal = PROPOSAL.load(deps.storage, proposal_id)?;
-    if proposal.emission_completed {
-        return Err(ContractError::CustomError {
-            val: "Emission already completed".to_string(),
-        });
+        let halving = EmissionSchedule::Halving {
+            initial_amount: Uint128::new(4000),
+            epochs: 2,
+        };
+        assert_eq!(scheduled_emission(halving.clone(), 0), 4000);
+        assert_eq!(scheduled_emission(halving.clone(), 1), 4000);
+        assert_eq!(scheduled_emission(halving.clone(), 2), 2000);
+        assert_eq!(scheduled_emission(halving.clone(), 5), 1000);
+        assert_eq!(scheduled_emission(halving, 400), 0);
+
+        let table = EmissionSchedule::Table {
+            amounts: vec![Uint128::new(100), Uint128::new(9000)],
+        };
+        assert_eq!(scheduled_emission(table.clone(), 0), 100);
+        // the release is capped by the pending rewards
+        assert_eq!(scheduled_emission(table.clone(), 1), 5000);
+        assert_eq!(scheduled_emission(table, 2), 0);
+
+        let decay = EmissionSchedule::ExponentialDecay {
+            initial_amount: Uint128::new(1000),
+            decay_rate: Decimal::percent(10),
+        };
+        assert_eq!(scheduled_emission(decay.clone(), 0), 1000);
+        assert_eq!(scheduled_emission(decay.clone(), 2), 810);
+        assert_eq!(scheduled_emission(decay, u64::MAX), 0);
     }
-    //check if active proposal
-    if proposal.voting_end_time > env.block.time {
-        return Err(ContractError::CustomError {
-            val: "Proposal Voting Period not ended to execute emission for the proposal"
-                .to_string(),
-        });
+
+    fn raise(deps: DepsMut<ComdexQuery>, env: &Env) -> Response<ComdexMessages> {
+        execute(
+            deps,
+            env.clone(),
+            mock_info("keeper", &[]),
+            ExecuteMsg::RaiseProposal { app_id: APP_ID },
+        )
+        .unwrap()
     }
 
-    //check governance token via app_id
-    let app_id = proposal.app_id;
-    let app_response = query_app_exists(deps.as_ref(), app_id)?;
-    let gov_token_id = app_response.gov_token_id;
-    let gov_token_denom = query_get_asset_data(deps.as_ref(), gov_token_id)?;
-    if gov_token_denom.is_empty() || gov_token_id == 0 {
-        return Err(ContractError::CustomError {
-            val: "Gov token not found".to_string(),
-        });
+    #[test]
+    fn proposals_are_raised_for_apps_with_an_emission() {
+        let mut deps = mock_dependencies();
+        instantiate_contract(deps.as_mut(), instantiate_msg());
+        let err = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("keeper", &[]),
+            ExecuteMsg::RaiseProposal { app_id: 2 },
+        )
+        .unwrap_err();
+        assert_eq!(
+            err,
+            ContractError::NotFound {
+                msg: "No emission found for app 2".to_string()
+            }
+        );
+        assert!(!APPCURRENTPROPOSAL.has(&deps.storage, 2));
     }
 
-    //// GET TOTAL V-TOKEN SUPPLY
+    #[test]
+    fn keepers_raise_proposals_for_a_reward() {
+        let mut deps = mock_dependencies();
+        instantiate_contract(deps.as_mut(), instantiate_msg());
+        let mut env = mock_env();
+        sudo(
+            sudo_deps(&mut deps),
+            env.clone(),
+            SudoMsg::UpdateKeeperConfig {
+                keeper_config: Some(KeeperConfig {
+                    reward: Uint128::new(100),
+                }),
+            },
+        )
+        .unwrap();
+        let align = |deps: &mut TestDeps, epoch_alignment| {
+            sudo(
+                sudo_deps(deps),
+                mock_env(),
+                SudoMsg::UpdateEpochAlignment {
+                    epoch_alignment: Some(epoch_alignment),
+                },
+            )
+        };
+        let err = align(&mut deps, 0).unwrap_err();
+        assert_eq!(
+            err,
+            ContractError::CustomError {
+                val: "Epoch alignment cannot be zero".to_string()
+            }
+        );
+        align(&mut deps, 500).unwrap();
+
+        // the first proposal of the app does not roll a previous one over
+        let res = raise(deps.as_mut(), &env);
+        assert!(res.messages.is_empty());
+
+        // the end of the voting period is rounded up to the epoch boundary
+        let proposal = PROPOSAL.load(&deps.storage, 1).unwrap();
+        let end_time = proposal.voting_end_time.seconds();
+        assert_eq!(end_time % 500, 0);
+        assert!(end_time >= env.block.time.seconds() + 1000);
+        assert!(end_time < env.block.time.seconds() + 1500);
+
+        let err = execute(
+            deps.as_mut(),
+            env.clone(),
+            mock_info("other", &[]),
+            ExecuteMsg::RaiseProposal { app_id: APP_ID },
+        )
+        .unwrap_err();
+        assert_eq!(
+            err,
+            ContractError::CustomError {
+                val: "Previous proposal in voting state for the app".to_string()
+            }
+        );
+
+        env.block.time = proposal.voting_end_time.plus_seconds(1);
+        let res = raise(deps.as_mut(), &env);
+        assert_eq!(
+            res.messages[0].msg,
+            CosmosMsg::Custom(ComdexMessages::MsgFoundationEmission {
+                app_id: APP_ID,
+                amount: Uint128::new(100),
+                foundation_address: vec!["keeper".to_string()],
+            })
+        );
+        let emission = EMISSION.load(&deps.storage, APP_ID).unwrap();
+        assert_eq!(emission.rewards_pending, 199_900);
+        assert_eq!(emission.distributed_rewards, 100);
+    }
 
-    let vtokens = SUPPLY
-        .may_load_at_height(deps.storage, &gov_token_denom, proposal.height)?
+    fn vault(ext_pair_id: u64) -> GaugeTarget {
+        GaugeTarget::Vault { ext_pair_id }
+    }
+
+    fn vote(
+        deps: DepsMut<ComdexQuery>,
+        env: &Env,
+        voter: &str,
+        proposal_id: u64,
+        gauges: Vec<GaugeTarget>,
+    ) {
+        let ratio = vec![Decimal::from_ratio(1u128, gauges.len() as u128); gauges.len()];
+        execute(
+            deps,
+            env.clone(),
+            mock_info(voter, &[]),
+            ExecuteMsg::VoteProposal {
+                app_id: APP_ID,
+                proposal_id,
+                gauges,
+                ratio,
+                persistent: None,
+            },
+        )
         .unwrap();
-    let total_v_token = vtokens.vtoken;
-    /////query token TOTAL SUPPLY
-    let total_weight = get_token_supply(deps.as_ref(), app_id, gov_token_id)?;
-    if total_weight == 0 {
-        return Err(ContractError::CustomError {
-            val: "Current Circulating Supply is 0".to_string(),
-        });
     }
 
-    //// GET TOTAL VESTED TOKEN
-    let state = STATE.load(deps.storage)?;
-    let query_msg = QueryMsg::VestedTokens {
-        denom: gov_token_denom,
-    };
-    let query_response: Uint128 = deps.querier.query(&QueryRequest::Wasm(WasmQuery::Smart {
-        contract_addr: state.vesting_contract.to_string(),
-        msg: to_binary(&query_msg).unwrap(),
-    }))?;
+    #[test]
+    fn proposals_vote_on_vaults_and_pools() {
+        let mut deps = mock_dependencies();
+        instantiate_contract(deps.as_mut(), instantiate_msg());
+        let mut env = mock_env();
+        lock(deps.as_mut(), &mut env, "voter", 1000, LockingPeriod::T1);
+        raise(deps.as_mut(), &env);
+        let pool = GaugeTarget::Pool {
+            app_id: 1,
+            pool_id: 1,
+        };
+        assert_eq!(
+            PROPOSAL.load(&deps.storage, 1).unwrap().gauges,
+            vec![vault(1), vault(2), pool.clone()]
+        );
+
+        vote(
+            deps.as_mut(),
+            &env,
+            "voter",
+            1,
+            vec![vault(2), pool.clone()],
+        );
+        let votes = PROPOSALVOTE
+            .prefix(1)
+            .range(&deps.storage, None, None, Order::Ascending)
+            .collect::<StdResult<Vec<_>>>()
+            .unwrap();
+        // gauge keys are read back from storage
+        assert_eq!(
+            votes,
+            vec![(vault(2), Uint128::new(125)), (pool, Uint128::new(125))]
+        );
+
+        let err = execute(
+            deps.as_mut(),
+            env,
+            mock_info("voter", &[]),
+            ExecuteMsg::VoteProposal {
+                app_id: APP_ID,
+                proposal_id: 1,
+                gauges: vec![vault(3)],
+                ratio: vec![Decimal::one()],
+                persistent: None,
+            },
+        );
+        assert!(err.is_err());
+    }
 
-    //// CALCULATE CIRCULATING SUPPLY (TOTAL SUPPLY-VESTED TOKENS-vtokens(locked))
-    let circulating_supply =
-        Uint128::from(total_weight) - query_response - Uint128::from(vtokens.token);
+    #[test]
+    fn migrate_gauges_converts_vaults_and_pools() {
+        let mut deps = mock_dependencies();
+        let env = mock_env();
+        let pool = GaugeTarget::Pool {
+            app_id: 2,
+            pool_id: 7,
+        };
+        LEGACY_PROPOSAL
+            .save(
+                &mut deps.storage,
+                1,
+                &LegacyProposal {
+                    app_id: APP_ID,
+                    voting_start_time: env.block.time,
+                    voting_end_time: env.block.time.plus_seconds(1000),
+                    extended_pair: vec![5, LEGACY_POOL_OFFSET + 7],
+                    emission_completed: true,
+                    rebase_completed: true,
+                    foundation_emission_completed: true,
+                    emission_distributed: 1000,
+                    rebase_distributed: 100,
+                    foundation_distributed: 10,
+                    total_voted_weight: 300,
+                    total_surplus: Coin::new(0, "nodenom"),
+                    height: env.block.height,
+                },
+            )
+            .unwrap();
+        LEGACY_PROPOSALVOTE
+            .save(&mut deps.storage, (1, 5), &Uint128::new(100))
+            .unwrap();
+        LEGACY_PROPOSALVOTE
+            .save(
+                &mut deps.storage,
+                (1, LEGACY_POOL_OFFSET + 7),
+                &Uint128::new(200),
+            )
+            .unwrap();
+        LEGACY_BRIBES_BY_PROPOSAL
+            .save(&mut deps.storage, (1, 5), &vec![Coin::new(10, "uatom")])
+            .unwrap();
+        LEGACY_BRIBES_BY_PROPOSAL
+            .save(
+                &mut deps.storage,
+                (1, LEGACY_POOL_OFFSET + 7),
+                &vec![Coin::new(20, "uosmo")],
+            )
+            .unwrap();
 
-    //// QUERY PERCENTAGE LOCKED (VTOKENS_LOCKED/V-TOKENS+CIRCULATING SUPPLY)
-    let percentage_locked =
-        Decimal::raw(total_v_token).div(Decimal::raw(circulating_supply.u128() + total_v_token));
+        migrate_gauges(&mut deps.storage, 2).unwrap();
+
+        let proposal = PROPOSAL.load(&deps.storage, 1).unwrap();
+        assert_eq!(proposal.gauges, vec![vault(5), pool.clone()]);
+        assert_eq!(proposal.status, ProposalStatus::Finalized);
+        assert_eq!(
+            PROPOSALVOTE.load(&deps.storage, (1, vault(5))).unwrap(),
+            Uint128::new(100)
+        );
+        assert_eq!(
+            PROPOSALVOTE.load(&deps.storage, (1, pool.clone())).unwrap(),
+            Uint128::new(200)
+        );
+        assert_eq!(
+            BRIBES_BY_PROPOSAL
+                .load(&deps.storage, (1, vault(5)))
+                .unwrap(),
+            vec![Coin::new(10, "uatom")]
+        );
+        assert_eq!(
+            BRIBES_BY_PROPOSAL.load(&deps.storage, (1, pool)).unwrap(),
+            vec![Coin::new(20, "uosmo")]
+        );
+        assert!(!LEGACY_PROPOSALVOTE.has(&deps.storage, (1, LEGACY_POOL_OFFSET + 7)));
+        assert!(!LEGACY_BRIBES_BY_PROPOSAL.has(&deps.storage, (1, LEGACY_POOL_OFFSET + 7)));
+    }
 
-    //// LOAD EMISSION FOR THE APP
-    let mut emission = EMISSION.load(deps.storage, proposal.app_id)?;
+    fn run_emission(
+        deps: DepsMut<ComdexQuery>,
+        env: &Env,
+        proposal_id: u64,
+    ) -> Response<ComdexMessages> {
+        execute(
+            deps,
+            env.clone(),
+            mock_info("keeper", &[]),
+            ExecuteMsg::Emission { proposal_id },
+        )
+        .unwrap()
+    }
 
-    //// CALCULATE EFFECTIVE EMISSION = (REWARD_PENDING*EMISSION RATE)*(1-PERCENTAGE_LOCKED)
-    let reward_emission = Uint128::from(emission.rewards_pending) * emission.emission_rate;
-    let effective_emission = reward_emission.mul(Decimal::one() - percentage_locked);
-    // mint and distribute to vault owner  based vote portion
-    let ext_pair = proposal.extended_pair.clone();
-    let mut votes: Vec<Uint128> = vec![];
-    let mut extd_pair: Vec<u64> = vec![];
-    let mut votes_pool: Vec<Uint128> = vec![];
-    let mut pool_ids: Vec<u64> = vec![];
-    let mut total_vote: Uint128 = Uint128::zero();
-    for i in ext_pair {
-        let vote = PROPOSALVOTE
-            .load(deps.storage, (proposal_id, i))
-            .unwrap_or_else(|_| Uint128::from(0_u32));
-        if i < 1000000 {
-            votes.push(vote);
-            extd_pair.push(i);
-        } else {
-            let pool_id = i % 1000000;
-            votes_pool.push(vote);
-            pool_ids.push(pool_id);
-        }
-        total_vote += vote;
+    #[test]
+    fn contract_gauges_receive_their_emission() {
+        let mut deps = mock_dependencies();
+        instantiate_contract(deps.as_mut(), instantiate_msg());
+        let mut env = mock_env();
+        let register = |deps: DepsMut<ComdexQuery>, sender: &str| {
+            execute(
+                deps,
+                env.clone(),
+                mock_info(sender, &[]),
+                ExecuteMsg::RegisterContractGauge {
+                    app_id: APP_ID,
+                    address: "gauge".to_string(),
+                },
+            )
+        };
+        assert!(register(deps.as_mut(), "other").is_err());
+        register(deps.as_mut(), "admin").unwrap();
+        let err = register(deps.as_mut(), "admin").unwrap_err();
+        assert_eq!(
+            err,
+            ContractError::CustomError {
+                val: "Contract gauge already registered".to_string()
+            }
+        );
+
+        lock(deps.as_mut(), &mut env, "voter", 1000, LockingPeriod::T1);
+        raise(deps.as_mut(), &env);
+        let gauge = GaugeTarget::Contract {
+            address: Addr::unchecked("gauge"),
+        };
+        assert!(PROPOSAL
+            .load(&deps.storage, 1)
+            .unwrap()
+            .gauges
+            .contains(&gauge));
+        vote(
+            deps.as_mut(),
+            &env,
+            "voter",
+            1,
+            vec![vault(1), gauge.clone()],
+        );
+
+        env.block.time = env.block.time.plus_seconds(1001);
+        let res = run_emission(deps.as_mut(), &env, 1);
+        let rewards = EMISSION_REWARD.load(&deps.storage, 1).unwrap();
+        let reward = rewards.rewards[rewards.gauges.iter().position(|g| *g == gauge).unwrap()];
+        assert!(!reward.is_zero());
+        let amount = Coin {
+            denom: DENOM.to_string(),
+            amount: reward,
+        };
+        assert!(res.messages.iter().any(|sub_msg| sub_msg.msg
+            == CosmosMsg::Wasm(WasmMsg::Execute {
+                contract_addr: "gauge".to_string(),
+                msg: to_json_binary(&GaugeHookMsg::ReceiveEmission {
+                    app_id: APP_ID,
+                    proposal_id: 1,
+                    amount: amount.clone(),
+                })
+                .unwrap(),
+                funds: vec![amount.clone()],
+            })));
     }
 
-    //// UPDATE Foundation Nodes Share
-    proposal.foundation_distributed = (state.foundation_percentage.mul(effective_emission)).u128();
-    //// Update proposal Emission State
-    proposal.emission_completed = true;
-    //// effective emission
-    proposal.emission_distributed =
-        effective_emission.u128() - (state.foundation_percentage.mul(effective_emission)).u128();
-    // update effective emission
+    #[test]
+    fn failing_contract_gauge_returns_its_emission() {
+        let mut deps = mock_dependencies();
+        instantiate_contract(deps.as_mut(), instantiate_msg());
+        let mut env = mock_env();
+        execute(
+            deps.as_mut(),
+            env.clone(),
+            mock_info("admin", &[]),
+            ExecuteMsg::RegisterContractGauge {
+                app_id: APP_ID,
+                address: "gauge".to_string(),
+            },
+        )
+        .unwrap();
+        lock(deps.as_mut(), &mut env, "voter", 1000, LockingPeriod::T1);
+        raise(deps.as_mut(), &env);
+        let gauge = GaugeTarget::Contract {
+            address: Addr::unchecked("gauge"),
+        };
+        vote(deps.as_mut(), &env, "voter", 1, vec![gauge.clone()]);
+
+        env.block.time = env.block.time.plus_seconds(1001);
+        let res = run_emission(deps.as_mut(), &env, 1);
+        let proposal = PROPOSAL.load(&deps.storage, 1).unwrap();
+        let index = proposal.gauges.iter().position(|g| *g == gauge).unwrap();
+        let hook = res.messages.last().unwrap();
+        assert_eq!(hook.reply_on, ReplyOn::Error);
+        assert_eq!(hook.id, 1 << GAUGE_INDEX_BITS | index as u64);
+
+        let reward = EMISSION_REWARD.load(&deps.storage, 1).unwrap().rewards[index];
+        assert!(!reward.is_zero());
+        let pending = EMISSION
+            .load(&deps.storage, APP_ID)
+            .unwrap()
+            .rewards_pending;
+        let res = reply(
+            deps.as_mut(),
+            env.clone(),
+            Reply {
+                id: hook.id,
+                result: SubMsgResult::Err("out of gas".to_string()),
+            },
+        )
+        .unwrap();
+        // the share of the gauge is burnt back and returned to the pending rewards
+        assert_eq!(
+            res.messages[0].msg,
+            CosmosMsg::Custom(ComdexMessages::MsgBurnGovTokensForApp {
+                app_id: APP_ID,
+                amount: Coin {
+                    denom: DENOM.to_string(),
+                    amount: reward,
+                },
+                from: env.contract.address.to_string(),
+            })
+        );
+        let emission = EMISSION.load(&deps.storage, APP_ID).unwrap();
+        assert_eq!(emission.rewards_pending, pending + reward.u128());
+        let emission_reward = EMISSION_REWARD.load(&deps.storage, 1).unwrap();
+        assert!(emission_reward.rewards[index].is_zero());
+        assert_eq!(
+            PROPOSAL
+                .load(&deps.storage, 1)
+                .unwrap()
+                .emission_distributed,
+            proposal.emission_distributed - reward.u128()
+        );
+    }
 
-    //// UPDATE REBASE AMOUNT
-    proposal.rebase_distributed = (reward_emission.mul(percentage_locked)).u128();
-    //// EMISSION Data Update
-    emission.rewards_pending -= reward_emission.u128();
-    emission.distributed_rewards += reward_emission.u128();
+    #[test]
+    fn proposals_are_capped_to_the_reply_id_gauge_index() {
+        let mut deps = mock_dependencies();
+        instantiate_contract(deps.as_mut(), instantiate_msg());
+        let env = mock_env();
+        // with the 2 vaults and the pool of the app, one contract gauge too many
+        let contract_gauges: Vec<Addr> = (0..(1 << GAUGE_INDEX_BITS) - 2)
+            .map(|i| Addr::unchecked(format!("gauge{}", i)))
+            .collect();
+        CONTRACT_GAUGES
+            .save(&mut deps.storage, APP_ID, &contract_gauges)
+            .unwrap();
 
-    let pool_votes: Uint128 = votes_pool.iter().sum();
-    let vault_votes: Uint128 = votes.iter().sum();
-    let pools_share = pool_votes
-        .mul(Uint128::from(proposal.emission_distributed))
-        .div(total_vote);
-    let vault_share = Uint128::from(proposal.emission_distributed) - pools_share;
-    let pool_rewards: Vec<Uint128> = votes_pool
-        .iter()
-        .map(|votes| {
-            if pool_votes.is_zero() {
-                Uint128::zero()
-            } else {
-                votes * pools_share / pool_votes
+        let err = execute(
+            deps.as_mut(),
+            env,
+            mock_info("keeper", &[]),
+            ExecuteMsg::RaiseProposal { app_id: APP_ID },
+        )
+        .unwrap_err();
+        assert_eq!(
+            err,
+            ContractError::CustomError {
+                val: "Too many gauges for a proposal".to_string()
             }
-        })
-        .collect();
+        );
+    }
 
-    let vault_rewards: Vec<Uint128> = votes
-        .iter()
-        .map(|vote| {
-            if vault_votes.is_zero() {
-                Uint128::zero()
-            } else {
-                vote * vault_share / vault_votes
-            }
-        })
-        .collect();
+    fn status_of(deps: &TestDeps, env: &Env, proposal_id: u64) -> ProposalStatus {
+        proposal_status(
+            &PROPOSAL.load(&deps.storage, proposal_id).unwrap(),
+            env.block.time,
+        )
+    }
 
-    let emission_reward = EmissionVaultPool {
-        app_id: app_id,
-        pool_ids: pool_ids.clone(),
-        vault_ids: extd_pair.clone(),
-        total_emission_rewards: Uint128::from(proposal.emission_distributed),
-        pool_rewards: pool_rewards,
-        vault_rewards: vault_rewards,
-    };
+    #[test]
+    fn proposal_lifecycle() {
+        let mut deps = mock_dependencies();
+        instantiate_contract(deps.as_mut(), instantiate_msg());
+        let mut env = mock_env();
+        lock(deps.as_mut(), &mut env, "voter", 1000, LockingPeriod::T1);
+        raise(deps.as_mut(), &env);
+        vote(deps.as_mut(), &env, "voter", 1, vec![vault(1)]);
+        assert_eq!(status_of(&deps, &env, 1), ProposalStatus::Active);
+        let emission = |deps: DepsMut<ComdexQuery>, env: &Env| {
+            execute(
+                deps,
+                env.clone(),
+                mock_info("keeper", &[]),
+                ExecuteMsg::Emission { proposal_id: 1 },
+            )
+        };
 
-    EMISSION_REWARD.save(deps.storage, proposal_id, &emission_reward)?;
-    let surplus = query_surplus_reward(deps.as_ref(), app_id, state.surplus_asset_id)?;
-    proposal.total_surplus = surplus.clone();
-    EMISSION.save(deps.storage, proposal.app_id, &emission)?;
-    PROPOSAL.save(deps.storage, proposal_id, &proposal)?;
+        let err = emission(deps.as_mut(), &env).unwrap_err();
+        assert_eq!(
+            err,
+            ContractError::CustomError {
+                val: "Proposal is Active, cannot move to EmissionExecuted".to_string()
+            }
+        );
+
+        env.block.time = env.block.time.plus_seconds(1001);
+        assert_eq!(status_of(&deps, &env, 1), ProposalStatus::VotingEnded);
+        emission(deps.as_mut(), &env).unwrap();
+        assert_eq!(status_of(&deps, &env, 1), ProposalStatus::EmissionExecuted);
+        let err = emission(deps.as_mut(), &env).unwrap_err();
+        assert_eq!(
+            err,
+            ContractError::CustomError {
+                val: "Proposal is EmissionExecuted, cannot move to EmissionExecuted".to_string()
+            }
+        );
+
+        // raising the next proposal finalizes the previous one
+        raise(deps.as_mut(), &env);
+        assert_eq!(status_of(&deps, &env, 1), ProposalStatus::Finalized);
+        assert_eq!(status_of(&deps, &env, 2), ProposalStatus::Active);
+        let ids = |status| -> Vec<u64> {
+            query_proposals(deps.as_ref(), env.clone(), Some(APP_ID), status, None, None)
+                .unwrap()
+                .iter()
+                .map(|response| response.proposal_id)
+                .collect()
+        };
+        assert_eq!(ids(None), vec![1, 2]);
+        assert_eq!(ids(Some(ProposalStatus::Finalized)), vec![1]);
+        assert_eq!(ids(Some(ProposalStatus::Active)), vec![2]);
+
+        let mut proposal = PROPOSAL.load(&deps.storage, 1).unwrap();
+        let err = transition_proposal(&mut proposal, ProposalStatus::Cancelled, env.block.time)
+            .unwrap_err();
+        assert_eq!(
+            err,
+            ContractError::CustomError {
+                val: "Proposal is Finalized, cannot move to Cancelled".to_string()
+            }
+        );
+    }
 
-    let mut msg: Vec<ComdexMessages> = vec![];
-    let app_id_param = app_id;
+    fn bribe(
+        deps: DepsMut<ComdexQuery>,
+        env: &Env,
+        briber: &str,
+        proposal_id: u64,
+        gauge: GaugeTarget,
+        coin: Coin,
+    ) {
+        execute(
+            deps,
+            env.clone(),
+            mock_info(briber, &[coin]),
+            ExecuteMsg::Bribe { proposal_id, gauge },
+        )
+        .unwrap();
+    }
 
-    let emission_msg = ComdexMessages::MsgEmissionRewards {
-        app_id: app_id_param,
-        amount: vault_share,
-        extended_pair: extd_pair,
-        voting_ratio: votes,
-    };
-    let cswap_id = CSWAP_ID.load(deps.storage)?;
-    let emission_msg_pools = ComdexMessages::MsgEmissionPoolRewards {
-        app_id: app_id,
-        cswap_app_id: cswap_id,
-        amount: pools_share,
-        pools: pool_ids,
-        voting_ratio: votes_pool,
-    };
-    let rebase_msg = ComdexMessages::MsgRebaseMint {
-        app_id: app_id_param,
-        amount: Uint128::from(proposal.rebase_distributed),
-        contract_addr: env.contract.address.to_string(),
-    };
+    #[test]
+    fn cancelled_proposals_refund_their_bribes() {
+        let mut deps = mock_dependencies();
+        instantiate_contract(deps.as_mut(), instantiate_msg());
+        let env = mock_env();
+        raise(deps.as_mut(), &env);
+        bribe(
+            deps.as_mut(),
+            &env,
+            "briber",
+            1,
+            vault(1),
+            Coin::new(100, "uatom"),
+        );
+        bribe(
+            deps.as_mut(),
+            &env,
+            "briber",
+            1,
+            vault(2),
+            Coin::new(50, "uatom"),
+        );
+        bribe(
+            deps.as_mut(),
+            &env,
+            "briber",
+            1,
+            vault(2),
+            Coin::new(20, "uosmo"),
+        );
+        bribe(
+            deps.as_mut(),
+            &env,
+            "other",
+            1,
+            vault(1),
+            Coin::new(30, "uatom"),
+        );
+        let reclaim = |deps: DepsMut<ComdexQuery>, sender: &str| {
+            execute(
+                deps,
+                env.clone(),
+                mock_info(sender, &[]),
+                ExecuteMsg::ReclaimBribes {
+                    proposal_id: 1,
+                    roll_over: None,
+                },
+            )
+        };
+        let cancel = |deps: DepsMut<ComdexQuery>, sender: &str| {
+            execute(
+                deps,
+                env.clone(),
+                mock_info(sender, &[]),
+                ExecuteMsg::CancelProposal { proposal_id: 1 },
+            )
+        };
 
-    if total_vote != Uint128::zero() {
-        if vault_votes.ne(&Uint128::zero()) {
-            msg.push(emission_msg);
-        }
-        if pool_votes.ne(&Uint128::zero()) {
-            msg.push(emission_msg_pools);
-        }
+        let err = reclaim(deps.as_mut(), "briber").unwrap_err();
+        assert_eq!(
+            err,
+            ContractError::CustomError {
+                val: "Bribes are only reclaimable once the proposal is cancelled or its emission executed"
+                    .to_string()
+            }
+        );
+        assert!(cancel(deps.as_mut(), "briber").is_err());
+        cancel(deps.as_mut(), "admin").unwrap();
+        assert_eq!(
+            PROPOSAL.load(&deps.storage, 1).unwrap().status,
+            ProposalStatus::Cancelled
+        );
+        let err = cancel(deps.as_mut(), "admin").unwrap_err();
+        assert_eq!(
+            err,
+            ContractError::CustomError {
+                val: "Proposal is Cancelled, cannot move to Cancelled".to_string()
+            }
+        );
+
+        // each briber is refunded their own deposits
+        let res = reclaim(deps.as_mut(), "briber").unwrap();
+        assert_eq!(
+            res.messages[0].msg,
+            CosmosMsg::Bank(BankMsg::Send {
+                to_address: "briber".to_string(),
+                amount: vec![Coin::new(150, "uatom"), Coin::new(20, "uosmo")],
+            })
+        );
+        assert_eq!(
+            BRIBES_BY_PROPOSAL
+                .load(&deps.storage, (1, vault(1)))
+                .unwrap(),
+            vec![Coin::new(30, "uatom")]
+        );
+        assert_eq!(
+            BRIBES_BY_PROPOSAL
+                .load(&deps.storage, (1, vault(2)))
+                .unwrap(),
+            vec![]
+        );
+        let err = reclaim(deps.as_mut(), "briber").unwrap_err();
+        assert_eq!(
+            err,
+            ContractError::NotFound {
+                msg: "No reclaimable bribes deposited on the proposal".to_string()
+            }
+        );
+        let res = reclaim(deps.as_mut(), "other").unwrap();
+        assert_eq!(
+            res.messages[0].msg,
+            CosmosMsg::Bank(BankMsg::Send {
+                to_address: "other".to_string(),
+                amount: vec![Coin::new(30, "uatom")],
+            })
+        );
+
+        // the next proposal of the app can be raised right away
+        raise(deps.as_mut(), &env);
+        assert_eq!(APPCURRENTPROPOSAL.load(&deps.storage, APP_ID).unwrap(), 2);
     }
-    msg.push(rebase_msg);
 
-    if surplus.amount != Uint128::new(0) {
-        let surplus_msg = ComdexMessages::MsgGetSurplusFund {
-            app_id: app_id_param,
-            asset_id: state.surplus_asset_id,
-            contract_addr: env.contract.address.clone().into_string(),
-            amount: surplus,
+    #[test]
+    fn bribes_on_gauges_without_votes_are_reclaimed_or_rolled_over() {
+        let mut deps = mock_dependencies();
+        instantiate_contract(deps.as_mut(), instantiate_msg());
+        let mut env = mock_env();
+        lock(deps.as_mut(), &mut env, "voter", 1000, LockingPeriod::T1);
+        raise(deps.as_mut(), &env);
+        bribe(
+            deps.as_mut(),
+            &env,
+            "briber",
+            1,
+            vault(1),
+            Coin::new(100, "uatom"),
+        );
+        bribe(
+            deps.as_mut(),
+            &env,
+            "briber",
+            1,
+            vault(2),
+            Coin::new(50, "uatom"),
+        );
+        bribe(
+            deps.as_mut(),
+            &env,
+            "other",
+            1,
+            vault(2),
+            Coin::new(10, "uosmo"),
+        );
+        bribe(
+            deps.as_mut(),
+            &env,
+            "third",
+            1,
+            vault(2),
+            Coin::new(5, "uatom"),
+        );
+        vote(deps.as_mut(), &env, "voter", 1, vec![vault(1)]);
+        let reclaim = |deps: DepsMut<ComdexQuery>, env: &Env, sender: &str, roll_over| {
+            execute(
+                deps,
+                env.clone(),
+                mock_info(sender, &[]),
+                ExecuteMsg::ReclaimBribes {
+                    proposal_id: 1,
+                    roll_over,
+                },
+            )
         };
-        msg.push(surplus_msg);
+
+        env.block.time = env.block.time.plus_seconds(1001);
+        assert!(reclaim(deps.as_mut(), &env, "briber", None).is_err());
+        run_emission(deps.as_mut(), &env, 1);
+        let err = reclaim(deps.as_mut(), &env, "third", Some(true)).unwrap_err();
+        assert_eq!(
+            err,
+            ContractError::CustomError {
+                val: "No active proposal to roll the bribes over to".to_string()
+            }
+        );
+
+        // bribes on the gauge without votes are rolled over to the next proposal
+        raise(deps.as_mut(), &env);
+        let res = reclaim(deps.as_mut(), &env, "briber", Some(true)).unwrap();
+        assert!(res.messages.is_empty());
+        assert_eq!(
+            BRIBES_BY_PROPOSAL
+                .load(&deps.storage, (2, vault(2)))
+                .unwrap(),
+            vec![Coin::new(50, "uatom")]
+        );
+        assert_eq!(
+            BRIBE_DEPOSITS
+                .load(&deps.storage, (2, Addr::unchecked("briber")))
+                .unwrap(),
+            vec![BribeDeposit {
+                gauge: vault(2),
+                coins: vec![Coin::new(50, "uatom")],
+            }]
+        );
+        // the bribe on the voted gauge is kept for its voters
+        assert_eq!(
+            BRIBE_DEPOSITS
+                .load(&deps.storage, (1, Addr::unchecked("briber")))
+                .unwrap(),
+            vec![BribeDeposit {
+                gauge: vault(1),
+                coins: vec![Coin::new(100, "uatom")],
+            }]
+        );
+        assert_eq!(
+            BRIBES_BY_PROPOSAL
+                .load(&deps.storage, (1, vault(1)))
+                .unwrap(),
+            vec![Coin::new(100, "uatom")]
+        );
+        let err = reclaim(deps.as_mut(), &env, "briber", None).unwrap_err();
+        assert_eq!(
+            err,
+            ContractError::NotFound {
+                msg: "No reclaimable bribes deposited on the proposal".to_string()
+            }
+        );
+
+        let res = reclaim(deps.as_mut(), &env, "other", None).unwrap();
+        assert_eq!(
+            res.messages[0].msg,
+            CosmosMsg::Bank(BankMsg::Send {
+                to_address: "other".to_string(),
+                amount: vec![Coin::new(10, "uosmo")],
+            })
+        );
     }
 
-    let mut all_proposals = match COMPLETEDPROPOSALS.may_load(deps.storage, app_id)? {
-        Some(val) => val,
-        None => vec![],
-    };
-    all_proposals.push(proposal_id);
-    COMPLETEDPROPOSALS.save(deps.storage, app_id, &all_proposals)?;
-    let vec_foundation = emission_foundation(deps, env, info, proposal_id)?;
-    msg.extend(vec_foundation);
-    Ok(Response::new()
-        .add_attribute("method", "emission")
-        .add_messages(msg))
-}
+    fn schedule(
+        deps: DepsMut<ComdexQuery>,
+        env: &Env,
+        gauge: GaugeTarget,
+        coin: Coin,
+        epochs: u64,
+    ) -> Result<Response<ComdexMessages>, ContractError> {
+        execute(
+            deps,
+            env.clone(),
+            mock_info("briber", &[coin]),
+            ExecuteMsg::ScheduleBribe {
+                app_id: APP_ID,
+                gauge,
+                epochs,
+            },
+        )
+    }
 
-fn has_duplicate_elements(vec: &Vec<u64>) -> bool {
-    let mut seen_elements = std::collections::HashSet::new();
-    for element in vec.iter() {
-        if seen_elements.contains(element) {
-            return true;
+    #[test]
+    fn scheduled_bribes_are_released_over_epochs() {
+        let mut deps = mock_dependencies();
+        instantiate_contract(deps.as_mut(), instantiate_msg());
+        let mut env = mock_env();
+        let err = schedule(deps.as_mut(), &env, vault(1), Coin::new(100, "uatom"), 0).unwrap_err();
+        assert_eq!(
+            err,
+            ContractError::CustomError {
+                val: "Bribe must be scheduled over at least one epoch".to_string()
+            }
+        );
+        schedule(
+            deps.as_mut(),
+            &env,
+            vault(1),
+            Coin::new(3_000_001, "uatom"),
+            3,
+        )
+        .unwrap();
+
+        let mut released = vec![];
+        for proposal_id in 1..=3 {
+            raise(deps.as_mut(), &env);
+            released.push(
+                BRIBES_BY_PROPOSAL
+                    .load(&deps.storage, (proposal_id, vault(1)))
+                    .unwrap(),
+            );
+            env.block.time = env.block.time.plus_seconds(1001);
         }
-        seen_elements.insert(element);
+        // the last epoch releases the rounding remainder
+        assert_eq!(
+            released,
+            vec![
+                vec![Coin::new(1_000_000, "uatom")],
+                vec![Coin::new(1_000_000, "uatom")],
+                vec![Coin::new(1_000_001, "uatom")],
+            ]
+        );
+        assert!(!SCHEDULED_BRIBES.has(&deps.storage, (APP_ID, 1)));
     }
-    false
-}
-pub fn vote_proposal(
-    deps: DepsMut<ComdexQuery>,
-    env: Env,
-    info: MessageInfo,
-    _app_id: u64,
-    proposal_id: u64,
-    extended_pair: Vec<u64>,
-    gov_token_denom: String,
-    ratio: Vec<Decimal>,
-) -> Result<Response<ComdexMessages>, ContractError> {
-    // check if admin (admin cannot vote)
-    if ADMIN.is_admin(deps.as_ref(), &info.sender)? {
-        return Err(ContractError::CustomError {
-            val: "Admin cannot vote".to_string(),
-        });
+
+    #[test]
+    fn scheduled_bribes_on_unlisted_gauges_are_reclaimed() {
+        let mut deps = mock_dependencies();
+        instantiate_contract(deps.as_mut(), instantiate_msg());
+        let mut env = mock_env();
+        lock(deps.as_mut(), &mut env, "voter", 1000, LockingPeriod::T1);
+        raise(deps.as_mut(), &env);
+        vote(deps.as_mut(), &env, "voter", 1, vec![vault(1)]);
+        schedule(
+            deps.as_mut(),
+            &env,
+            vault(1),
+            Coin::new(2_000_000, "uatom"),
+            2,
+        )
+        .unwrap();
+        schedule(
+            deps.as_mut(),
+            &env,
+            vault(2),
+            Coin::new(2_000_000, "uatom"),
+            2,
+        )
+        .unwrap();
+        // killing the gauge leaves it out of the next proposal
+        execute(
+            deps.as_mut(),
+            env.clone(),
+            mock_info("admin", &[]),
+            ExecuteMsg::KillGauge {
+                proposal_id: 1,
+                gauge: vault(2),
+            },
+        )
+        .unwrap();
+        env.block.time = env.block.time.plus_seconds(1001);
+        run_emission(deps.as_mut(), &env, 1);
+        raise(deps.as_mut(), &env);
+        let reclaim = |deps: DepsMut<ComdexQuery>, sender: &str, scheduled_bribe_id| {
+            execute(
+                deps,
+                env.clone(),
+                mock_info(sender, &[]),
+                ExecuteMsg::ReclaimScheduledBribe {
+                    app_id: APP_ID,
+                    scheduled_bribe_id,
+                },
+            )
+        };
+
+        let err = reclaim(deps.as_mut(), "briber", 1).unwrap_err();
+        assert_eq!(
+            err,
+            ContractError::CustomError {
+                val: "Gauge of the scheduled bribe is still listed".to_string()
+            }
+        );
+        let err = reclaim(deps.as_mut(), "other", 2).unwrap_err();
+        assert_eq!(err, ContractError::Unauthorized {});
+
+        // the bribe on the unlisted gauge was not released
+        let res = reclaim(deps.as_mut(), "briber", 2).unwrap();
+        assert_eq!(
+            res.messages[0].msg,
+            CosmosMsg::Bank(BankMsg::Send {
+                to_address: "briber".to_string(),
+                amount: vec![Coin::new(2_000_000, "uatom")],
+            })
+        );
+        let err = reclaim(deps.as_mut(), "briber", 2).unwrap_err();
+        assert_eq!(
+            err,
+            ContractError::NotFound {
+                msg: "Scheduled bribe 2 not found".to_string()
+            }
+        );
     }
 
-    // do not accept  funds
-    if !info.funds.is_empty() {
-        return Err(ContractError::FundsNotAllowed {});
+    #[test]
+    fn schedule_bribe_checks_gauge_and_deposit() {
+        let mut deps = mock_dependencies();
+        instantiate_contract(deps.as_mut(), instantiate_msg());
+        let env = mock_env();
+        let funds = Coin::new(2_000_000, "ubribe");
+
+        let err = schedule(deps.as_mut(), &env, vault(9), funds.clone(), 2).unwrap_err();
+        assert_eq!(
+            err,
+            ContractError::CustomError {
+                val: "Invalid gauge".to_string()
+            }
+        );
+        let err = schedule(deps.as_mut(), &env, vault(1), funds.clone(), 3).unwrap_err();
+        assert_eq!(err, ContractError::InsufficientFunds { funds: 2_000_000 });
+
+        schedule(deps.as_mut(), &env, vault(1), funds, 2).unwrap();
+        let scheduled_bribe = SCHEDULED_BRIBES.load(&deps.storage, (APP_ID, 1)).unwrap();
+        assert_eq!(scheduled_bribe.remaining_epochs, 2);
     }
 
-    //check if active proposal
-    let mut proposal = PROPOSAL.load(deps.storage, proposal_id)?;
+    #[test]
+    fn scheduled_bribes_are_capped() {
+        let mut deps = mock_dependencies();
+        instantiate_contract(deps.as_mut(), instantiate_msg());
+        let env = mock_env();
+        for id in 1..=MAX_SCHEDULED_BRIBES as u64 {
+            let scheduled_bribe = ScheduledBribe {
+                id,
+                depositor: Addr::unchecked("briber"),
+                gauge: vault(1),
+                remaining: Coin::new(2_000_000, "ubribe"),
+                remaining_epochs: 2,
+            };
+            SCHEDULED_BRIBES
+                .save(&mut deps.storage, (APP_ID, id), &scheduled_bribe)
+                .unwrap();
+        }
 
-    // Check if proposal in voting period
-    if proposal.voting_end_time < env.block.time {
-        return Err(ContractError::CustomError {
-            val: "Proposal Voting Period Ended".to_string(),
-        });
-    }
+        let err = schedule(
+            deps.as_mut(),
+            &env,
+            vault(1),
+            Coin::new(2_000_000, "ubribe"),
+            1,
+        )
+        .unwrap_err();
+        assert_eq!(
+            err,
+            ContractError::CustomError {
+                val: "Too many scheduled bribes pending for the app".to_string()
+            }
+        );
 
-    // check if vote sequence is correct
-    if extended_pair.len() != ratio.len() {
-        return Err(ContractError::CustomError {
-            val: "Invalid ratio".to_string(),
-        });
+        // raising a proposal releases the first epoch of each of them
+        raise(deps.as_mut(), &env);
+        let bribes = BRIBES_BY_PROPOSAL
+            .load(&deps.storage, (1, vault(1)))
+            .unwrap();
+        assert_eq!(
+            bribes,
+            vec![Coin::new(
+                1_000_000 * MAX_SCHEDULED_BRIBES as u128,
+                "ubribe"
+            )]
+        );
     }
 
-    let mut total_ration = Decimal::zero();
-    for ratio in ratio.iter() {
-        total_ration += ratio;
-    }
+    #[test]
+    fn delegated_bribes_are_split_by_commission() {
+        let mut deps = mock_dependencies();
+        instantiate_contract(deps.as_mut(), instantiate_msg());
+        let mut env = mock_env();
+        lock(
+            deps.as_mut(),
+            &mut env,
+            "delegator",
+            1000,
+            LockingPeriod::T1,
+        );
+        lock(deps.as_mut(), &mut env, "delegate", 1000, LockingPeriod::T1);
+        execute(
+            deps.as_mut(),
+            env.clone(),
+            mock_info("delegate", &[]),
+            ExecuteMsg::UpdateDelegationCommission {
+                commission: Decimal::percent(10),
+            },
+        )
+        .unwrap();
+        execute(
+            deps.as_mut(),
+            env.clone(),
+            mock_info("delegator", &[]),
+            ExecuteMsg::Delegate {
+                app_id: APP_ID,
+                delegate: "delegate".to_string(),
+                amount: Uint128::new(250),
+                delegation_period: 10_000,
+            },
+        )
+        .unwrap();
+        env.block.height += 1;
 
-    //// check if total ratio is 100%
-    if total_ration > Decimal::one() {
-        return Err(ContractError::CustomError {
-            val: "Ratio cannot be more than 100 %".to_string(),
-        });
-    }
+        raise(deps.as_mut(), &env);
+        vote(deps.as_mut(), &env, "delegate", 1, vec![vault(1)]);
+        execute(
+            deps.as_mut(),
+            env.clone(),
+            mock_info("briber", &[Coin::new(1000, "uatom")]),
+            ExecuteMsg::Bribe {
+                proposal_id: 1,
+                gauge: vault(1),
+            },
+        )
+        .unwrap();
 
-    if has_duplicate_elements(&extended_pair) {
-        return Err(ContractError::CustomError {
-            val: "Extended pair has duplicate elements".to_string(),
-        });
+        let vote = VOTERSPROPOSAL
+            .load(&deps.storage, (Addr::unchecked("delegate"), 1))
+            .unwrap();
+        assert_eq!(vote.voting_power_total, 500);
+        assert_eq!(vote.delegated_power, 250);
+        assert_eq!(vote.commission, Decimal::percent(10));
+        // the delegate keeps its own half and 10 % of the delegated half
+        assert_eq!(
+            proposal_bribe_share(&deps.storage, &Addr::unchecked("delegate"), 1).unwrap(),
+            Some(vec![Coin::new(550, "uatom")])
+        );
+        assert_eq!(
+            proposal_bribe_share(&deps.storage, &Addr::unchecked("delegator"), 1).unwrap(),
+            Some(vec![Coin::new(450, "uatom")])
+        );
+        assert_eq!(
+            proposal_bribe_share(&deps.storage, &Addr::unchecked("briber"), 1).unwrap(),
+            None
+        );
     }
 
-    // check if total ratio is not 0%
-    if total_ration == Decimal::zero() {
-        return Err(ContractError::CustomError {
-            val: "voted ratio cannot be zero".to_string(),
-        });
+    fn delegate(
+        deps: DepsMut<ComdexQuery>,
+        env: &Env,
+        delegator: &str,
+        amount: u128,
+    ) -> Result<Response<ComdexMessages>, ContractError> {
+        execute(
+            deps,
+            env.clone(),
+            mock_info(delegator, &[]),
+            ExecuteMsg::Delegate {
+                app_id: APP_ID,
+                delegate: "delegate".to_string(),
+                amount: Uint128::new(amount),
+                delegation_period: 10_000,
+            },
+        )
     }
 
-    //// check if already voted for proposal
-    let has_voted = VOTERS_VOTE
-        .may_load(deps.storage, (info.sender.clone(), proposal_id))?
-        .unwrap_or_default();
+    fn update_commission(deps: DepsMut<ComdexQuery>, env: &Env, commission: Decimal) {
+        execute(
+            deps,
+            env.clone(),
+            mock_info("delegate", &[]),
+            ExecuteMsg::UpdateDelegationCommission { commission },
+        )
+        .unwrap();
+    }
 
-    // check if ext_pair param exist in extended pair list to vote for
+    #[test]
+    fn delegations_are_accepted_once_the_commission_is_set() {
+        let mut deps = mock_dependencies();
+        instantiate_contract(deps.as_mut(), instantiate_msg());
+        let mut env = mock_env();
+        lock(
+            deps.as_mut(),
+            &mut env,
+            "delegator",
+            1000,
+            LockingPeriod::T1,
+        );
+        let err = delegate(deps.as_mut(), &env, "delegator", 250).unwrap_err();
+        assert_eq!(
+            err,
+            ContractError::CustomError {
+                val: "delegate does not accept delegations".to_string()
+            }
+        );
 
-    let extended_pairs_proposal = proposal.extended_pair.clone();
+        update_commission(deps.as_mut(), &env, Decimal::zero());
+        delegate(deps.as_mut(), &env, "delegator", 250).unwrap();
+    }
 
-    //// check if extended pair exists in proposal's extended pair
+    #[test]
+    fn delegators_are_capped_per_delegate() {
+        let mut deps = mock_dependencies();
+        instantiate_contract(deps.as_mut(), instantiate_msg());
+        let mut env = mock_env();
+        update_commission(deps.as_mut(), &env, Decimal::zero());
+        for i in 0..=MAX_DELEGATORS {
+            let delegator = format!("delegator{}", i);
+            lock(deps.as_mut(), &mut env, &delegator, 1000, LockingPeriod::T1);
+        }
+        for i in 0..MAX_DELEGATORS {
+            delegate(deps.as_mut(), &env, &format!("delegator{}", i), 100).unwrap();
+        }
 
-    if !extended_pair
-        .iter()
-        .all(|item| extended_pairs_proposal.contains(item))
-    {
-        return Err(ContractError::CustomError {
-            val: "Extended pair does not exist in proposal".to_string(),
-        });
+        let err = delegate(
+            deps.as_mut(),
+            &env,
+            &format!("delegator{}", MAX_DELEGATORS),
+            100,
+        )
+        .unwrap_err();
+        assert_eq!(
+            err,
+            ContractError::CustomError {
+                val: "Too many delegators for the delegate".to_string()
+            }
+        );
+        // current delegators may still update their delegation
+        delegate(deps.as_mut(), &env, "delegator0", 200).unwrap();
     }
 
-    //balance of  denom for voting
+    #[test]
+    fn commission_changes_apply_from_the_next_proposal() {
+        let mut deps = mock_dependencies();
+        instantiate_contract(deps.as_mut(), instantiate_msg());
+        let mut env = mock_env();
+        lock(
+            deps.as_mut(),
+            &mut env,
+            "delegator",
+            1000,
+            LockingPeriod::T1,
+        );
+        lock(deps.as_mut(), &mut env, "delegate", 1000, LockingPeriod::T1);
+        update_commission(deps.as_mut(), &env, Decimal::percent(10));
+        delegate(deps.as_mut(), &env, "delegator", 250).unwrap();
+        env.block.height += 1;
+
+        raise(deps.as_mut(), &env);
+        env.block.height += 1;
+        update_commission(deps.as_mut(), &env, Decimal::percent(90));
+        vote(deps.as_mut(), &env, "delegate", 1, vec![vault(1)]);
+        let vote_of = |deps: &TestDeps, proposal_id: u64| {
+            VOTERSPROPOSAL
+                .load(&deps.storage, (Addr::unchecked("delegate"), proposal_id))
+                .unwrap()
+        };
+        assert_eq!(vote_of(&deps, 1).commission, Decimal::percent(10));
 
-    let mut vote_power: u128 = 0;
+        env.block.time = env.block.time.plus_seconds(1001);
+        env.block.height += 1;
+        raise(deps.as_mut(), &env);
+        vote(deps.as_mut(), &env, "delegate", 2, vec![vault(1)]);
+        assert_eq!(vote_of(&deps, 2).commission, Decimal::percent(90));
+    }
 
-    let vtokens = VTOKENS.may_load_at_height(
-        deps.storage,
-        (info.sender.clone(), &gov_token_denom),
-        proposal.height,
-    )?;
+    #[test]
+    fn sticky_votes_are_applied_to_new_proposals() {
+        let mut deps = mock_dependencies();
+        instantiate_contract(deps.as_mut(), instantiate_msg());
+        let mut env = mock_env();
+        lock(deps.as_mut(), &mut env, "voter", 1000, LockingPeriod::T2);
+        raise(deps.as_mut(), &env);
+        execute(
+            deps.as_mut(),
+            env.clone(),
+            mock_info("voter", &[]),
+            ExecuteMsg::VoteProposal {
+                app_id: APP_ID,
+                proposal_id: 1,
+                gauges: vec![vault(1), vault(2)],
+                ratio: vec![Decimal::percent(60), Decimal::percent(40)],
+                persistent: Some(true),
+            },
+        )
+        .unwrap();
 
-    if vtokens.is_none() {
-        return Err(ContractError::CustomError {
-            val: "No tokens locked to perform voting on proposals".to_string(),
-        });
-    }
-    if let Some(_vtokens) = vtokens {
-        // calculate voting power for the proposal
-        for vtoken in _vtokens {
-            vote_power += vtoken.vtoken.amount.u128();
-        }
-    }
+        env.block.time = env.block.time.plus_seconds(1001);
+        raise(deps.as_mut(), &env);
+        let vote = |deps: &TestDeps, proposal_id: u64, gauge: GaugeTarget| {
+            PROPOSALVOTE
+                .may_load(&deps.storage, (proposal_id, gauge))
+                .unwrap()
+                .unwrap_or_default()
+                .u128()
+        };
+        assert_eq!(vote(&deps, 2, vault(1)), 300);
+        assert_eq!(vote(&deps, 2, vault(2)), 200);
+        assert!(VOTERS_VOTE.has(&deps.storage, (Addr::unchecked("voter"), 2)));
 
-    if vote_power == 0 {
-        return Err(ContractError::CustomError {
-            val: "No tokens locked to perform voting on proposals".to_string(),
-        });
+        execute(
+            deps.as_mut(),
+            env.clone(),
+            mock_info("voter", &[]),
+            ExecuteMsg::ClearStickyVote { app_id: APP_ID },
+        )
+        .unwrap();
+        env.block.time = env.block.time.plus_seconds(1001);
+        raise(deps.as_mut(), &env);
+        assert_eq!(vote(&deps, 3, vault(1)), 0);
     }
 
-    //if already voted , decrease previous vote weight
-    if has_voted {
-        let prev_vote = VOTERSPROPOSAL.load(deps.storage, (info.sender.clone(), proposal_id))?;
-        let last_vote_weight = prev_vote.votes;
-        for pair_vote in last_vote_weight {
-            let mut proposal_vote = PROPOSALVOTE
-                .load(deps.storage, (proposal_id, pair_vote.extended_pair))
-                .unwrap_or_default();
-            proposal_vote -= Uint128::from(pair_vote.vote_weight);
-            PROPOSALVOTE.save(
-                deps.storage,
-                (proposal_id, pair_vote.extended_pair),
-                &proposal_vote,
-            )?;
-            proposal.total_voted_weight -= pair_vote.vote_weight;
-        }
+    #[test]
+    fn sticky_votes_without_power_are_dropped() {
+        let mut deps = mock_dependencies();
+        instantiate_contract(deps.as_mut(), instantiate_msg());
+        let mut env = mock_env();
+        lock(deps.as_mut(), &mut env, "voter", 1000, LockingPeriod::T1);
+        raise(deps.as_mut(), &env);
+        execute(
+            deps.as_mut(),
+            env.clone(),
+            mock_info("voter", &[]),
+            ExecuteMsg::VoteProposal {
+                app_id: APP_ID,
+                proposal_id: 1,
+                gauges: vec![vault(1)],
+                ratio: vec![Decimal::one()],
+                persistent: Some(true),
+            },
+        )
+        .unwrap();
+        let sticky_vote = StickyVote {
+            gauges: vec![vault(2)],
+            ratio: vec![Decimal::one()],
+            voting_power: 100,
+        };
+        STICKY_VOTES
+            .save(
+                &mut deps.storage,
+                (APP_ID, Addr::unchecked("unlocked")),
+                &sticky_vote,
+            )
+            .unwrap();
+
+        env.block.time = env.block.time.plus_seconds(1001);
+        raise(deps.as_mut(), &env);
+        assert_eq!(
+            PROPOSALVOTE.load(&deps.storage, (2, vault(1))).unwrap(),
+            Uint128::new(250)
+        );
+        assert!(STICKY_VOTES.has(&deps.storage, (APP_ID, Addr::unchecked("voter"))));
+        assert!(!STICKY_VOTES.has(&deps.storage, (APP_ID, Addr::unchecked("unlocked"))));
     }
 
-    let mut vote_pair: Vec<VotePair> = vec![];
-    for (i, pair) in extended_pair.iter().enumerate() {
-        let vote_pair_param = VotePair {
-            extended_pair: *pair,
-            vote_ratio: ratio[i],
-            vote_weight: Uint128::from(vote_power).mul(ratio[i]).u128(),
+    #[test]
+    fn weakest_sticky_vote_is_evicted_once_full() {
+        let mut deps = mock_dependencies();
+        instantiate_contract(deps.as_mut(), instantiate_msg());
+        let mut env = mock_env();
+        lock(deps.as_mut(), &mut env, "small", 400, LockingPeriod::T1);
+        lock(deps.as_mut(), &mut env, "voter", 1000, LockingPeriod::T1);
+        raise(deps.as_mut(), &env);
+        for i in 0..MAX_STICKY_VOTES as u128 {
+            let sticky_vote = StickyVote {
+                gauges: vec![vault(1)],
+                ratio: vec![Decimal::one()],
+                voting_power: 200 - i,
+            };
+            STICKY_VOTES
+                .save(
+                    &mut deps.storage,
+                    (APP_ID, Addr::unchecked(format!("sticky{}", i))),
+                    &sticky_vote,
+                )
+                .unwrap();
+        }
+        let sticky_vote = |deps: DepsMut<ComdexQuery>, voter: &str| {
+            execute(
+                deps,
+                env.clone(),
+                mock_info(voter, &[]),
+                ExecuteMsg::VoteProposal {
+                    app_id: APP_ID,
+                    proposal_id: 1,
+                    gauges: vec![vault(1)],
+                    ratio: vec![Decimal::one()],
+                    persistent: Some(true),
+                },
+            )
         };
-        vote_pair.push(vote_pair_param);
-    }
 
-    for pair_vote in vote_pair.iter_mut() {
-        let mut proposal_vote = PROPOSALVOTE
-            .load(deps.storage, (proposal_id, pair_vote.extended_pair))
-            .unwrap_or_default();
-        proposal_vote += Uint128::from(pair_vote.vote_weight);
-        PROPOSALVOTE.save(
-            deps.storage,
-            (proposal_id, pair_vote.extended_pair),
-            &proposal_vote,
-        )?;
-        proposal.total_voted_weight += pair_vote.vote_weight;
+        // 100 of voting power does not beat the weakest sticky vote, of 101
+        let err = sticky_vote(deps.as_mut(), "small").unwrap_err();
+        assert_eq!(
+            err,
+            ContractError::CustomError {
+                val: "Too many sticky votes for the app with more voting power".to_string()
+            }
+        );
+        sticky_vote(deps.as_mut(), "voter").unwrap();
+        let voter = STICKY_VOTES
+            .load(&deps.storage, (APP_ID, Addr::unchecked("voter")))
+            .unwrap();
+        assert_eq!(voter.voting_power, 250);
+        assert!(!STICKY_VOTES.has(&deps.storage, (APP_ID, Addr::unchecked("sticky99"))));
+        assert!(STICKY_VOTES.has(&deps.storage, (APP_ID, Addr::unchecked("sticky98"))));
     }
-    let vote = Vote {
-        voting_power_total: vote_power,
-        total_voted_ratio: total_ration,
-        votes: vote_pair,
-    };
-    VOTERSPROPOSAL.save(deps.storage, (info.sender.clone(), proposal_id), &vote)?;
-    PROPOSAL.save(deps.storage, proposal_id, &proposal)?;
-    VOTERS_VOTE.save(deps.storage, (info.sender, proposal_id), &true)?;
-
-    Ok(Response::new().add_attribute("method", "voted for proposal"))
-}
 
-pub fn raise_proposal(
-    deps: DepsMut<ComdexQuery>,
-    env: Env,
-    info: MessageInfo,
-    app_id: u64,
-    ext_pairs: Vec<u64>,
-) -> Result<Response<ComdexMessages>, ContractError> {
-    //// only admin can execute
-    ADMIN.assert_admin(deps.as_ref(), &info.sender)?;
+    fn update_app_config(deps: DepsMut<ComdexQuery>, update: impl FnOnce(&mut AppConfig)) {
+        let mut app_config = load_app_config(deps.storage, APP_ID).unwrap();
+        update(&mut app_config);
+        APP_CONFIG.save(deps.storage, APP_ID, &app_config).unwrap();
+    }
 
-    // do not accept  funds
-    if !info.funds.is_empty() {
-        return Err(ContractError::FundsNotAllowed {});
+    #[test]
+    fn capped_votes_are_redistributed() {
+        let votes = vec![Uint128::new(600), Uint128::new(300), Uint128::new(100)];
+        assert_eq!(cap_gauge_votes(&votes, None), votes);
+        assert_eq!(
+            cap_gauge_votes(&votes, Some(Decimal::percent(40))),
+            vec![Uint128::new(400), Uint128::new(400), Uint128::new(200)]
+        );
+        // nothing to redistribute to once every voted gauge is capped
+        let votes = vec![Uint128::new(600), Uint128::new(400), Uint128::zero()];
+        assert_eq!(
+            cap_gauge_votes(&votes, Some(Decimal::percent(40))),
+            vec![Uint128::new(400), Uint128::new(400), Uint128::zero()]
+        );
+
+        // large votes and emissions do not overflow
+        let amount = Uint128::new(10u128.pow(30));
+        let gauges = vec![vault(1), vault(2)];
+        let votes = vec![amount, amount];
+        let allocation = allocate_emission(amount, &gauges, &votes, amount + amount);
+        assert_eq!(allocation.vault_share, amount);
+        assert_eq!(
+            allocation.rewards,
+            vec![Uint128::new(5 * 10u128.pow(29)); 2]
+        );
     }
 
-    //// No proposal
-    if ext_pairs.is_empty() {
-        return Err(ContractError::CustomError {
-            val: "No extended pair to vote".to_string(),
+    #[test]
+    fn emission_applies_the_vote_cap_of_the_proposal() {
+        let mut deps = mock_dependencies();
+        instantiate_contract(deps.as_mut(), instantiate_msg());
+        let mut env = mock_env();
+        lock(deps.as_mut(), &mut env, "whale", 3000, LockingPeriod::T1);
+        lock(deps.as_mut(), &mut env, "voter", 1000, LockingPeriod::T1);
+        update_app_config(deps.as_mut(), |app_config| {
+            app_config.gauge_vote_cap = Some(Decimal::percent(50));
+        });
+        raise(deps.as_mut(), &env);
+        // the cap is recorded on the proposal when raised
+        update_app_config(deps.as_mut(), |app_config| {
+            app_config.gauge_vote_cap = None;
         });
+        vote(deps.as_mut(), &env, "whale", 1, vec![vault(1)]);
+        vote(deps.as_mut(), &env, "voter", 1, vec![vault(2)]);
+
+        env.block.time = env.block.time.plus_seconds(1001);
+        run_emission(deps.as_mut(), &env, 1);
+        let rewards = EMISSION_REWARD.load(&deps.storage, 1).unwrap();
+        assert_eq!(rewards.rewards[0], rewards.rewards[1]);
+        assert_eq!(
+            rewards.uncapped_rewards[0],
+            rewards.uncapped_rewards[1] * Uint128::new(3)
+        );
     }
-    //check no proposal active for app
-    let current_app_proposal = APPCURRENTPROPOSAL
-        .may_load(deps.storage, app_id)?
-        .unwrap_or(0);
 
-    // if proposal already exist , check if whether it is in voting period
-    // proposal cannot be raised until current proposal voting time is ended
-    if current_app_proposal != 0 {
-        let proposal = PROPOSAL.load(deps.storage, current_app_proposal)?;
-        if proposal.voting_end_time > env.block.time {
-            return Err(ContractError::CustomError {
-                val: "Previous proposal in voting state for the app".to_string(),
-            });
+    #[test]
+    fn killed_gauges_are_excluded_until_revived() {
+        let mut deps = mock_dependencies();
+        instantiate_contract(deps.as_mut(), instantiate_msg());
+        let mut env = mock_env();
+        let pool = GaugeTarget::Pool {
+            app_id: 1,
+            pool_id: 1,
+        };
+        let admin_execute = |deps: DepsMut<ComdexQuery>, env: &Env, msg: ExecuteMsg| {
+            execute(deps, env.clone(), mock_info("admin", &[]), msg).unwrap();
+        };
+        lock(deps.as_mut(), &mut env, "voter", 1000, LockingPeriod::T1);
+        raise(deps.as_mut(), &env);
+        vote(deps.as_mut(), &env, "voter", 1, vec![vault(1), vault(2)]);
+
+        for gauge in [vault(2), pool.clone()] {
+            admin_execute(
+                deps.as_mut(),
+                &env,
+                ExecuteMsg::KillGauge {
+                    proposal_id: 1,
+                    gauge,
+                },
+            );
         }
+        // reviving before the emission restores the gauge on the proposal
+        admin_execute(
+            deps.as_mut(),
+            &env,
+            ExecuteMsg::ReviveGauge {
+                proposal_id: 1,
+                gauge: pool.clone(),
+            },
+        );
+        let proposal = PROPOSAL.load(&deps.storage, 1).unwrap();
+        assert_eq!(proposal.killed_gauges, vec![vault(2)]);
+
+        env.block.time = env.block.time.plus_seconds(1001);
+        run_emission(deps.as_mut(), &env, 1);
+        let rewards = EMISSION_REWARD.load(&deps.storage, 1).unwrap();
+        let reward_of = |gauge: &GaugeTarget| {
+            let index = rewards.gauges.iter().position(|g| g == gauge).unwrap();
+            rewards.rewards[index]
+        };
+        assert!(!reward_of(&vault(1)).is_zero());
+        assert!(reward_of(&vault(2)).is_zero());
+
+        // the killed gauge is left out of the next proposals until revived
+        raise(deps.as_mut(), &env);
+        let proposal = PROPOSAL.load(&deps.storage, 2).unwrap();
+        assert!(!proposal.gauges.contains(&vault(2)));
+        assert!(proposal.gauges.contains(&pool));
+
+        admin_execute(
+            deps.as_mut(),
+            &env,
+            ExecuteMsg::ReviveGauge {
+                proposal_id: 1,
+                gauge: vault(2),
+            },
+        );
+        // the executed emission of the proposal is left untouched
+        assert_eq!(
+            PROPOSAL.load(&deps.storage, 1).unwrap().killed_gauges,
+            vec![vault(2)]
+        );
+        env.block.time = env.block.time.plus_seconds(1001);
+        raise(deps.as_mut(), &env);
+        let proposal = PROPOSAL.load(&deps.storage, 3).unwrap();
+        assert!(proposal.gauges.contains(&vault(2)));
     }
 
-    // set proposal data
-    let state = STATE.load(deps.storage)?;
-    let voting_period = state.voting_period;
-    let app_id_param = app_id;
-    //update proposal maps
-    let proposal = Proposal {
-        app_id: app_id_param,              //app_id for proposal
-        voting_start_time: env.block.time, // Current block timestamp
-        voting_end_time: env.block.time.plus_seconds(voting_period), // end voting timestamp
-        extended_pair: ext_pairs,          // extended pairs for which voting is taking place
-        emission_completed: false,         //initially set emission_completed as false
-        rebase_completed: false,           //initially set rebase_completed as false
-        emission_distributed: 0,           //emission distributed token as 0
-        rebase_distributed: 0,             //rebase distributed token as 0
-        total_voted_weight: 0,             // total_weight of voted vtoken
-        foundation_emission_completed: false, // emission to foundation addresses as false
-        foundation_distributed: 0,         // total distributed tokens as 0
-        total_surplus: Coin {
-            amount: Uint128::from(0_u32),
-            denom: "nodenom".to_string(),
-        }, // initialized dummy token
-        height: env.block.height,          // current block height of token,
-    };
-    let mut current_proposal = PROPOSALCOUNT.load(deps.storage).unwrap_or(0);
-    current_proposal += 1;
-    PROPOSALCOUNT.save(deps.storage, &current_proposal)?;
-    APPCURRENTPROPOSAL.save(deps.storage, app_id, &current_proposal)?;
-    PROPOSAL.save(deps.storage, current_proposal, &proposal)?;
-    Ok(Response::new()
-        .add_attribute("method", "proposal_raised")
-        .add_attribute("proposal_id", current_proposal.to_string()))
-}
+    /// Raises a proposal voted by `voter` only on the first vault, missing a 50 % quorum
+    /// with the voting power of `idle` locked, and executes its emission.
+    fn emission_missing_quorum(deps: &mut TestDeps, fallback: EmissionFallback) {
+        let mut env = mock_env();
+        lock(deps.as_mut(), &mut env, "voter", 1000, LockingPeriod::T1);
+        lock(deps.as_mut(), &mut env, "idle", 3000, LockingPeriod::T1);
+        update_app_config(deps.as_mut(), |app_config| {
+            app_config.quorum = Some(Decimal::percent(50));
+            app_config.emission_fallback = fallback;
+        });
+        raise(deps.as_mut(), &env);
+        vote(deps.as_mut(), &env, "voter", 1, vec![vault(1)]);
+        env.block.time = env.block.time.plus_seconds(1001);
+        run_emission(deps.as_mut(), &env, 1);
+    }
 
-#[entry_point]
-pub fn migrate(deps: DepsMut, _env: Env, msg: MigrateMsg) -> Result<Response, ContractError> {
-    let ver = cw2::get_contract_version(deps.storage)?;
-    // ensure we are migrating from an allowed contract
-    if ver.contract != CONTRACT_NAME {
-        return Err(StdError::generic_err("Can only upgrade from same type").into());
+    #[test]
+    fn quorum_of_the_proposal_applies_to_its_emission() {
+        let mut deps = mock_dependencies();
+        instantiate_contract(deps.as_mut(), instantiate_msg());
+        let mut env = mock_env();
+        lock(deps.as_mut(), &mut env, "voter", 1000, LockingPeriod::T1);
+        lock(deps.as_mut(), &mut env, "idle", 3000, LockingPeriod::T1);
+        raise(deps.as_mut(), &env);
+        vote(deps.as_mut(), &env, "voter", 1, vec![vault(1)]);
+
+        // a quorum set after the raise does not apply to the proposal
+        update_app_config(deps.as_mut(), |app_config| {
+            app_config.quorum = Some(Decimal::percent(50));
+        });
+        env.block.time = env.block.time.plus_seconds(1001);
+        let res = run_emission(deps.as_mut(), &env, 1);
+        assert!(res
+            .attributes
+            .contains(&Attribute::new("quorum_met", "true")));
+        let rewards = EMISSION_REWARD.load(&deps.storage, 1).unwrap().rewards;
+        assert!(!rewards[0].is_zero());
+
+        raise(deps.as_mut(), &env);
+        let proposal = PROPOSAL.load(&deps.storage, 2).unwrap();
+        assert_eq!(proposal.quorum, Some(Decimal::percent(50)));
+        assert_eq!(proposal.emission_fallback, EmissionFallback::CarryForward);
     }
-    // note: better to do proper semver compare, but string compare *usually* works
-    if ver.version.as_str() > CONTRACT_VERSION {
-        return Err(StdError::generic_err("Cannot upgrade from a newer version").into());
+
+    #[test]
+    fn quorum_missed_carries_emission_forward() {
+        let mut deps = mock_dependencies();
+        instantiate_contract(deps.as_mut(), instantiate_msg());
+        let emission = EMISSION.load(&deps.storage, APP_ID).unwrap();
+        emission_missing_quorum(&mut deps, EmissionFallback::CarryForward);
+
+        let emission_reward = EMISSION_REWARD.load(&deps.storage, 1).unwrap();
+        assert!(emission_reward
+            .rewards
+            .iter()
+            .all(|reward| reward.is_zero()));
+        let proposal = PROPOSAL.load(&deps.storage, 1).unwrap();
+        assert_eq!(proposal.emission_distributed, 0);
+
+        // the next emission releases the carried rewards on top of its own
+        let carried = EMISSION.load(&deps.storage, APP_ID).unwrap();
+        assert!(carried.carried_forward > 0);
+        let released = emission.rewards_pending - carried.rewards_pending;
+        assert_eq!(
+            epoch_emission(&carried),
+            Uint128::from(carried.rewards_pending) * carried.emission_rate
+                + Uint128::from(carried.carried_forward)
+        );
+        assert_eq!(
+            released + carried.carried_forward,
+            epoch_emission(&emission).u128()
+        );
     }
-    CSWAP_ID.save(deps.storage, &msg.cswap_id)?;
-    // set the new version
-    cw2::set_contract_version(deps.storage, CONTRACT_NAME, CONTRACT_VERSION)?;
-    //do any desired state migrations...
 
-    Ok(Response::default())
-}
+    #[test]
+    fn quorum_missed_splits_emission_equally() {
+        let mut deps = mock_dependencies();
+        instantiate_contract(deps.as_mut(), instantiate_msg());
+        emission_missing_quorum(&mut deps, EmissionFallback::EqualSplit);
+
+        let proposal = PROPOSAL.load(&deps.storage, 1).unwrap();
+        let rewards = EMISSION_REWARD.load(&deps.storage, 1).unwrap().rewards;
+        assert_eq!(rewards.len(), proposal.gauges.len());
+        let share = Uint128::from(proposal.emission_distributed)
+            .multiply_ratio(1u128, proposal.gauges.len() as u128);
+        assert!(rewards
+            .iter()
+            .all(|reward| reward.abs_diff(share) <= 3u128.into()));
+        let allocated: Uint128 = rewards.iter().sum();
+        assert!(proposal.emission_distributed - allocated.u128() < rewards.len() as u128);
+        assert_eq!(
+            EMISSION
+                .load(&deps.storage, APP_ID)
+                .unwrap()
+                .carried_forward,
+            0
+        );
+    }
 
-#[entry_point]
-pub fn sudo(deps: DepsMut, _env: Env, msg: SudoMsg) -> Result<Response, ContractError> {
-    match msg {
-        SudoMsg::UpdateVestingContract { address } => {
-            let mut state = STATE.load(deps.storage)?;
-            state.vesting_contract = deps.api.addr_validate(&address.into_string())?;
-            STATE.save(deps.storage, &state)?;
-            Ok(Response::new())
-        }
-        SudoMsg::UpdateEmissionRate {
-            emission_rate,
-            app_id,
-        } => {
-            let mut emission = EMISSION.load(deps.storage, app_id)?;
-            if emission_rate > Decimal::one() {
-                return Err(ContractError::CustomError {
-                    val: "Emission rate cannot be greater one".to_string(),
-                });
-            }
-            emission.emission_rate = emission_rate;
-            EMISSION.save(deps.storage, emission.app_id, &emission)?;
-            Ok(Response::new())
-        }
-        SudoMsg::UpdateFoundationInfo {
-            addresses,
-            foundation_percentage,
-        } => {
-            if foundation_percentage > Decimal::one() {
-                return Err(ContractError::CustomError {
-                    val: "Foundation Emission percentage cannot be greater than 100 %".to_string(),
-                });
-            }
-            let mut state = STATE.load(deps.storage)?;
-            map_validate(deps.api, &addresses)?;
-            state.foundation_addr = addresses;
-            state.foundation_addr.sort_unstable();
-            state.foundation_addr.dedup();
-            state.foundation_percentage = foundation_percentage;
-            STATE.save(deps.storage, &state)?;
-            Ok(Response::new())
-        }
-        SudoMsg::UpdateLockingPeriod { t1, t2 } => {
-            let mut state = STATE.load(deps.storage)?;
-            state.t1 = t1;
-            state.t2 = t2;
-            STATE.save(deps.storage, &state)?;
-            Ok(Response::new())
-        }
-        SudoMsg::UpdateAdmin { admin } => {
-            deps.api.addr_validate(&admin.clone().into_string())?;
-            ADMIN.set(deps, Some(admin))?;
-            Ok(Response::new())
-        }
-        SudoMsg::UpdateVotingPeriod { voting_period } => {
-            let mut state = STATE.load(deps.storage)?;
-            state.voting_period = voting_period;
-            STATE.save(deps.storage, &state)?;
-            Ok(Response::new())
-        }
+    #[test]
+    fn quorum_missed_repeats_previous_distribution() {
+        let mut deps = mock_dependencies();
+        instantiate_contract(deps.as_mut(), instantiate_msg());
+
+        // without a previous emission, the emission is carried forward
+        emission_missing_quorum(&mut deps, EmissionFallback::PreviousEpoch);
+        let rewards = EMISSION_REWARD.load(&deps.storage, 1).unwrap().rewards;
+        assert!(rewards.iter().all(|reward| reward.is_zero()));
+
+        // a proposal meeting the quorum sets the distribution
+        let mut env = mock_env();
+        env.block.height += 2;
+        env.block.time = env.block.time.plus_seconds(1001);
+        raise(deps.as_mut(), &env);
+        vote(deps.as_mut(), &env, "voter", 2, vec![vault(2)]);
+        vote(deps.as_mut(), &env, "idle", 2, vec![vault(1), vault(2)]);
+        env.block.time = env.block.time.plus_seconds(1001);
+        run_emission(deps.as_mut(), &env, 2);
+        let previous = EMISSION_REWARD.load(&deps.storage, 2).unwrap().rewards;
+        assert!(!previous[0].is_zero() && previous[1] > previous[0]);
+
+        // the next one missing it splits its emission in the same proportions
+        raise(deps.as_mut(), &env);
+        vote(deps.as_mut(), &env, "voter", 3, vec![vault(1)]);
+        env.block.time = env.block.time.plus_seconds(1001);
+        run_emission(deps.as_mut(), &env, 3);
+        let rewards = EMISSION_REWARD.load(&deps.storage, 3).unwrap().rewards;
+        assert!(
+            rewards[1]
+                .multiply_ratio(previous[0], previous[1])
+                .abs_diff(rewards[0])
+                <= Uint128::one()
+        );
+        assert!(rewards[2..].iter().all(|reward| reward.is_zero()));
     }
 }
@@ -1,11 +1,21 @@
-use comdex_bindings::ComdexQuery;
+use crate::state::{
+    AppConfig, DecayPoint, Delegation, Emission, EmissionFallback, EmissionSchedule, GaugeTarget,
+    Proposal, ProposalStatus, Vote, Vtoken, APP_CONFIG, BRIBES_BY_PROPOSAL, COMPLETEDPROPOSALS,
+    CONTRACT_GAUGES, CSWAP_ID, CW20_GOV_TOKENS, DEFAULT_MIN_SCHEDULED_BRIBE, DELEGATED,
+    DELEGATED_VOTES, EMISSION_REWARD, EPOCH_ALIGNMENT, KEEPER_CONFIG, PROPOSAL, PROPOSALVOTE,
+    SLOPE_CHANGES, STATE, SUPPLY, SUPPLY_DECAY, VOTERSPROPOSAL, VTOKENS,
+};
+use comdex_bindings::{ComdexMessages, ComdexQuery};
 use comdex_bindings::{
     GetAppResponse, GetAssetDataResponse, GetExtendedPairByAppResponse, GetPoolByAppResponse,
     GetSurplusRewardAmount, GetWhitelistedAssetResponse, TotalSupplyResponse,
 };
-use cosmwasm_std::QueryRequest;
-use cosmwasm_std::StdResult;
-use cosmwasm_std::{Coin, Deps};
+use cosmwasm_std::{to_json_binary, Addr, BankMsg, Coin, CosmosMsg, Deps, WasmMsg};
+use cosmwasm_std::{Decimal, Order, QueryRequest, Storage, Timestamp, Uint128};
+use cosmwasm_std::{StdError, StdResult};
+use cw20::{Cw20ExecuteMsg, Cw20QueryMsg, TokenInfoResponse};
+use cw_storage_plus::Bound;
+use std::convert::TryFrom;
 
 pub fn query_app_exists(
     deps: Deps<ComdexQuery>,
@@ -115,3 +125,667 @@ pub fn query_pool_by_app(
 
     Ok(pool_pair.pools)
 }
+
+/// Gauges of the app: the vaults of its extended pairs, the pools of cswap and the
+/// contracts registered as its gauges.
+pub fn query_app_gauges(deps: Deps<ComdexQuery>, app_id: u64) -> StdResult<Vec<GaugeTarget>> {
+    ////get ext pairs vec from app
+    let mut gauges: Vec<GaugeTarget> = query_extended_pair_by_app(deps, app_id)?
+        .into_iter()
+        .map(|ext_pair_id| GaugeTarget::Vault { ext_pair_id })
+        .collect();
+
+    ////get pools of cswap
+    let cswap_id = CSWAP_ID.load(deps.storage)?;
+    let pools = query_pool_by_app(deps, cswap_id)?;
+    gauges.extend(pools.into_iter().map(|pool_id| GaugeTarget::Pool {
+        app_id: cswap_id,
+        pool_id,
+    }));
+
+    ////get contracts registered as gauges
+    let contract_gauges = CONTRACT_GAUGES
+        .may_load(deps.storage, app_id)?
+        .unwrap_or_default();
+    gauges.extend(
+        contract_gauges
+            .into_iter()
+            .map(|address| GaugeTarget::Contract { address }),
+    );
+    Ok(gauges)
+}
+
+/// Locking configuration of the app, defaulting to the one held in `State`.
+pub fn load_app_config(storage: &dyn Storage, app_id: u64) -> StdResult<AppConfig> {
+    match APP_CONFIG.may_load(storage, app_id)? {
+        Some(val) => Ok(val),
+        None => {
+            let state = STATE.load(storage)?;
+            Ok(AppConfig {
+                t1: state.t1,
+                t2: state.t2,
+                min_lock_amount: state.min_lock_amount,
+                voting_period: state.voting_period,
+                surplus_asset_id: state.surplus_asset_id,
+                gauge_vote_cap: None,
+                quorum: None,
+                emission_fallback: EmissionFallback::default(),
+                min_scheduled_bribe: DEFAULT_MIN_SCHEDULED_BRIBE,
+            })
+        }
+    }
+}
+
+/// Rewards released by the emission of the current epoch, as per its schedule, along with
+/// the rewards carried forward from the last one. The release is capped by the pending rewards.
+pub fn epoch_emission(emission: &Emission) -> Uint128 {
+    let amount = match &emission.schedule {
+        EmissionSchedule::Constant => {
+            Uint128::from(emission.rewards_pending) * emission.emission_rate
+        }
+        EmissionSchedule::Halving {
+            initial_amount,
+            epochs,
+        } => {
+            let halvings = emission.epoch / epochs;
+            if halvings >= 128 {
+                Uint128::zero()
+            } else {
+                Uint128::from(initial_amount.u128() >> halvings)
+            }
+        }
+        EmissionSchedule::ExponentialDecay {
+            initial_amount,
+            decay_rate,
+        } => {
+            // the decay factor does not exceed one, its power cannot overflow
+            let epochs = u32::try_from(emission.epoch).unwrap_or(u32::MAX);
+            *initial_amount * (Decimal::one() - *decay_rate).pow(epochs)
+        }
+        EmissionSchedule::Table { amounts } => amounts
+            .get(emission.epoch as usize)
+            .copied()
+            .unwrap_or_default(),
+    };
+    std::cmp::min(
+        amount + Uint128::from(emission.carried_forward),
+        Uint128::from(emission.rewards_pending),
+    )
+}
+
+/// Keeper reward paid out of the given emission budget, zero if keepers are not rewarded.
+pub fn keeper_reward(storage: &dyn Storage, budget: Uint128) -> StdResult<Uint128> {
+    let reward = match KEEPER_CONFIG.may_load(storage)? {
+        Some(keeper_config) => keeper_config.reward,
+        None => Uint128::zero(),
+    };
+    Ok(std::cmp::min(reward, budget))
+}
+
+/// End of the voting period of a proposal raised at `time`, rounded up to the
+/// next epoch boundary if an epoch alignment is configured.
+// `u64::div_ceil` is not available with the toolchain of the optimizer
+#[allow(clippy::manual_div_ceil)]
+pub fn voting_end_time(
+    storage: &dyn Storage,
+    time: Timestamp,
+    voting_period: u64,
+) -> StdResult<Timestamp> {
+    let end_time = time.plus_seconds(voting_period);
+    let alignment = EPOCH_ALIGNMENT.may_load(storage)?.unwrap_or(0);
+    if alignment == 0 {
+        return Ok(end_time);
+    }
+    Ok(Timestamp::from_seconds(
+        (end_time.seconds() + alignment - 1) / alignment * alignment,
+    ))
+}
+
+/// Prefix of the denomination of locked CW20 tokens, followed by the token address.
+pub const CW20_DENOM_PREFIX: &str = "cw20:";
+
+/// Denomination under which the tokens of the CW20 contract are locked.
+pub fn cw20_denom(address: &Addr) -> String {
+    format!("{}{}", CW20_DENOM_PREFIX, address)
+}
+
+/// Denomination under which the governance token of the app is locked: the one of its CW20
+/// governance token if set, else the one of its native governance asset.
+pub fn query_gov_lock_denom(deps: Deps<ComdexQuery>, app_id: u64) -> StdResult<String> {
+    match CW20_GOV_TOKENS.may_load(deps.storage, app_id)? {
+        Some(address) => Ok(cw20_denom(&address)),
+        None => {
+            let app_response = query_app_exists(deps, app_id)?;
+            query_get_asset_data(deps, app_response.gov_token_id)
+        }
+    }
+}
+
+/// Total supply of the governance token of the app: the one of its CW20 governance token
+/// if set, else the one of its native governance asset.
+pub fn query_gov_token_supply(
+    deps: Deps<ComdexQuery>,
+    app_id: u64,
+    gov_token_id: u64,
+) -> StdResult<Uint128> {
+    match CW20_GOV_TOKENS.may_load(deps.storage, app_id)? {
+        Some(address) => {
+            let token_info: TokenInfoResponse = deps
+                .querier
+                .query_wasm_smart(address, &Cw20QueryMsg::TokenInfo {})?;
+            Ok(token_info.total_supply)
+        }
+        None => Ok(Uint128::from(get_token_supply(deps, app_id, gov_token_id)?)),
+    }
+}
+
+/// Message sending the given tokens to the recipient, as a CW20 `Transfer`
+/// for CW20 denominations and a bank transfer otherwise.
+pub fn transfer_msg(coin: Coin, recipient: &Addr) -> StdResult<CosmosMsg<ComdexMessages>> {
+    match coin.denom.strip_prefix(CW20_DENOM_PREFIX) {
+        Some(contract_addr) => Ok(WasmMsg::Execute {
+            contract_addr: contract_addr.to_string(),
+            msg: to_json_binary(&Cw20ExecuteMsg::Transfer {
+                recipient: recipient.to_string(),
+                amount: coin.amount,
+            })?,
+            funds: vec![],
+        }
+        .into()),
+        None => Ok(BankMsg::Send {
+            to_address: recipient.to_string(),
+            amount: vec![coin],
+        }
+        .into()),
+    }
+}
+
+/// Voting power of the vtoken at the given time. Decaying vtokens lose
+/// their voting power linearly, reaching zero at `end_time`.
+pub fn vtoken_voting_power(vtoken: &Vtoken, time: Timestamp) -> Uint128 {
+    if !vtoken.decay || time <= vtoken.start_time {
+        return vtoken.vtoken.amount;
+    }
+    if time >= vtoken.end_time {
+        return Uint128::zero();
+    }
+    vtoken.vtoken.amount
+        * Decimal::from_ratio(
+            vtoken.end_time.seconds() - time.seconds(),
+            vtoken.end_time.seconds() - vtoken.start_time.seconds(),
+        )
+}
+
+/// End time of the vtoken as of the given time. The end time of auto-relock
+/// vtokens keeps rolling forward, a full locking period away.
+pub fn vtoken_end_time(vtoken: &Vtoken, time: Timestamp) -> Timestamp {
+    if !vtoken.auto_relock {
+        return vtoken.end_time;
+    }
+    let duration = vtoken.end_time.seconds() - vtoken.start_time.seconds();
+    std::cmp::max(vtoken.end_time, time.plus_seconds(duration))
+}
+
+/// Weight the vtoken was locked at. Positions locked before the weight was
+/// recorded derive it from their vtoken to token ratio.
+pub fn position_weight(vtoken: &Vtoken) -> Decimal {
+    match vtoken.weight {
+        Some(weight) => weight,
+        None => Decimal::from_ratio(vtoken.vtoken.amount, vtoken.token.amount),
+    }
+}
+
+/// Whether the vtoken has completed its locking period at the given time.
+pub fn is_unlocked(vtoken: &Vtoken, time: Timestamp) -> bool {
+    !vtoken.auto_relock && vtoken.end_time < time
+}
+
+/// Adds the coin to the list, merging it with the coin of the same denom.
+pub fn add_coin(coins: &mut Vec<Coin>, coin: Coin) {
+    match coins.iter_mut().find(|c| c.denom == coin.denom) {
+        Some(existing) => existing.amount += coin.amount,
+        None => coins.push(coin),
+    }
+}
+
+/// Status of the proposal at the given time.
+pub fn proposal_status(proposal: &Proposal, time: Timestamp) -> ProposalStatus {
+    match proposal.status {
+        ProposalStatus::Active if proposal.voting_end_time < time => ProposalStatus::VotingEnded,
+        status => status,
+    }
+}
+
+/// Whether the emission of the proposal has been executed.
+pub fn is_emission_executed(proposal: &Proposal) -> bool {
+    matches!(
+        proposal.status,
+        ProposalStatus::EmissionExecuted | ProposalStatus::Finalized
+    )
+}
+
+/// Voting power of the vtokens of the denom owned by the address when the proposal was raised.
+pub fn proposal_voting_power(
+    storage: &dyn Storage,
+    address: &Addr,
+    denom: &str,
+    proposal: &Proposal,
+) -> StdResult<u128> {
+    let vtokens = VTOKENS
+        .may_load_at_height(storage, (address.clone(), denom), proposal.height)?
+        .unwrap_or_default();
+    Ok(vtokens
+        .iter()
+        .map(|vtoken| vtoken_voting_power(vtoken, proposal.voting_start_time).u128())
+        .sum())
+}
+
+/// Voting power of the address delegated to each delegate for the proposal. Delegations
+/// are scaled down when the voting power of the address decayed below their total.
+pub fn proposal_delegations(
+    storage: &dyn Storage,
+    delegator: &Addr,
+    denom: &str,
+    proposal: &Proposal,
+    voting_power: u128,
+) -> StdResult<Vec<(Addr, u128)>> {
+    let delegations: Vec<Delegation> = DELEGATED
+        .may_load_at_height(storage, (delegator.clone(), denom), proposal.height)?
+        .map(|info| info.delegations)
+        .unwrap_or_default()
+        .into_iter()
+        .filter(|delegation| delegation.delegation_end_at > proposal.voting_start_time)
+        .collect();
+    let total_delegated: u128 = delegations
+        .iter()
+        .map(|delegation| delegation.delegated)
+        .sum();
+
+    Ok(delegations
+        .into_iter()
+        .map(|delegation| {
+            let delegated = if total_delegated > voting_power {
+                Uint128::from(delegation.delegated)
+                    .multiply_ratio(voting_power, total_delegated)
+                    .u128()
+            } else {
+                delegation.delegated
+            };
+            (delegation.delegated_to, delegated)
+        })
+        .collect())
+}
+
+/// Bribes earned by the address on the proposal, from its own vote net of the commission
+/// of its delegators and from the votes of its delegates net of their commission.
+/// Returns `None` if the address took no part in the vote.
+pub fn proposal_bribe_share(
+    storage: &dyn Storage,
+    address: &Addr,
+    proposal_id: u64,
+) -> StdResult<Option<Vec<Coin>>> {
+    let vote = VOTERSPROPOSAL.may_load(storage, (address.clone(), proposal_id))?;
+    let delegated_votes = DELEGATED_VOTES.may_load(storage, (address.clone(), proposal_id))?;
+    if vote.is_none() && delegated_votes.is_none() {
+        return Ok(None);
+    }
+
+    // bribes on killed gauges are refunded to their depositors
+    let killed_gauges = PROPOSAL.load(storage, proposal_id)?.killed_gauges;
+    let mut bribe_coins: Vec<Coin> = vec![];
+    if let Some(vote) = vote {
+        let delegators_share = Decimal::from_ratio(vote.delegated_power, vote.voting_power_total)
+            * (Decimal::one() - vote.commission);
+        add_vote_bribes(
+            storage,
+            proposal_id,
+            &vote,
+            &killed_gauges,
+            Decimal::one() - delegators_share,
+            &mut bribe_coins,
+        )?;
+    }
+    for delegated_vote in delegated_votes.unwrap_or_default() {
+        let vote = VOTERSPROPOSAL.load(storage, (delegated_vote.delegated_to, proposal_id))?;
+        let share = Decimal::from_ratio(delegated_vote.vote_power, vote.voting_power_total)
+            * (Decimal::one() - vote.commission);
+        add_vote_bribes(
+            storage,
+            proposal_id,
+            &vote,
+            &killed_gauges,
+            share,
+            &mut bribe_coins,
+        )?;
+    }
+    Ok(Some(bribe_coins))
+}
+
+/// Adds the given share of the bribes earned by the vote to the list.
+fn add_vote_bribes(
+    storage: &dyn Storage,
+    proposal_id: u64,
+    vote: &Vote,
+    killed_gauges: &[GaugeTarget],
+    share: Decimal,
+    bribe_coins: &mut Vec<Coin>,
+) -> StdResult<()> {
+    for pair in vote.votes.iter() {
+        if killed_gauges.contains(&pair.gauge) {
+            continue;
+        }
+        let total_vote_weight = PROPOSALVOTE.load(storage, (proposal_id, pair.gauge.clone()))?;
+        if total_vote_weight.is_zero() {
+            continue;
+        }
+        let total_bribe = BRIBES_BY_PROPOSAL
+            .may_load(storage, (proposal_id, pair.gauge.clone()))?
+            .unwrap_or_default();
+        for coin in total_bribe {
+            let amount =
+                coin.amount * (Decimal::from_ratio(pair.vote_weight, total_vote_weight) * share);
+            add_coin(
+                bribe_coins,
+                Coin {
+                    denom: coin.denom,
+                    amount,
+                },
+            );
+        }
+    }
+    Ok(())
+}
+
+/// Votes of each gauge of the proposal counted in its emission, killed gauges having none.
+pub fn emission_gauge_votes(
+    storage: &dyn Storage,
+    proposal_id: u64,
+    proposal: &Proposal,
+) -> StdResult<Vec<Uint128>> {
+    let mut gauge_votes: Vec<Uint128> = vec![];
+    for gauge in proposal.gauges.iter() {
+        let vote = if proposal.killed_gauges.contains(gauge) {
+            Uint128::zero()
+        } else {
+            PROPOSALVOTE
+                .may_load(storage, (proposal_id, gauge.clone()))?
+                .unwrap_or_default()
+        };
+        gauge_votes.push(vote);
+    }
+    Ok(gauge_votes)
+}
+
+/// Votes of each gauge the emission of the proposal is allocated by, and whether they met
+/// its quorum. Votes missing the quorum are replaced as per the fallback of the proposal.
+pub fn allocation_gauge_votes(
+    storage: &dyn Storage,
+    proposal_id: u64,
+    proposal: &Proposal,
+    total_voting_power: u128,
+) -> StdResult<(Vec<Uint128>, bool)> {
+    let gauge_votes = emission_gauge_votes(storage, proposal_id, proposal)?;
+    let total_vote: Uint128 = gauge_votes.iter().sum();
+    let quorum_met = !total_vote.is_zero()
+        && match proposal.quorum {
+            Some(quorum) => Uint128::from(total_voting_power) * quorum <= total_vote,
+            None => true,
+        };
+    if quorum_met {
+        return Ok((gauge_votes, true));
+    }
+
+    let fallback_votes = match proposal.emission_fallback {
+        EmissionFallback::CarryForward => vec![Uint128::zero(); proposal.gauges.len()],
+        EmissionFallback::EqualSplit => proposal
+            .gauges
+            .iter()
+            .map(|gauge| {
+                if proposal.killed_gauges.contains(gauge) {
+                    Uint128::zero()
+                } else {
+                    Uint128::one()
+                }
+            })
+            .collect(),
+        EmissionFallback::PreviousEpoch => {
+            // rewards of each gauge in the last emission of the app
+            let previous_rewards = match COMPLETEDPROPOSALS
+                .may_load(storage, proposal.app_id)?
+                .and_then(|proposals| proposals.last().copied())
+            {
+                Some(previous_proposal) => EMISSION_REWARD.may_load(storage, previous_proposal)?,
+                None => None,
+            };
+            proposal
+                .gauges
+                .iter()
+                .map(|gauge| {
+                    previous_rewards
+                        .as_ref()
+                        .filter(|_| !proposal.killed_gauges.contains(gauge))
+                        .and_then(|previous| {
+                            previous
+                                .gauges
+                                .iter()
+                                .position(|previous_gauge| previous_gauge == gauge)
+                                .and_then(|index| previous.rewards.get(index).copied())
+                        })
+                        .unwrap_or_default()
+                })
+                .collect()
+        }
+    };
+    Ok((fallback_votes, false))
+}
+
+/// Caps the votes of each gauge to the given share of the total votes. The excess is
+/// redistributed to the other voted gauges pro rata to their votes, and left out once
+/// every voted gauge reached the cap.
+pub fn cap_gauge_votes(votes: &[Uint128], cap: Option<Decimal>) -> Vec<Uint128> {
+    let total_vote: Uint128 = votes.iter().sum();
+    let max_vote = match cap {
+        Some(cap) if !total_vote.is_zero() => total_vote * cap,
+        _ => return votes.to_vec(),
+    };
+
+    let mut capped_votes = votes.to_vec();
+    loop {
+        let excess: Uint128 = capped_votes
+            .iter()
+            .filter(|vote| **vote > max_vote)
+            .map(|vote| vote - max_vote)
+            .sum();
+        if excess.is_zero() {
+            break;
+        }
+        for vote in capped_votes.iter_mut() {
+            if *vote > max_vote {
+                *vote = max_vote;
+            }
+        }
+        let uncapped_votes: Uint128 = capped_votes
+            .iter()
+            .filter(|vote| !vote.is_zero() && **vote < max_vote)
+            .sum();
+        if uncapped_votes.is_zero() {
+            break;
+        }
+        for vote in capped_votes
+            .iter_mut()
+            .filter(|vote| !vote.is_zero() && **vote < max_vote)
+        {
+            *vote += excess.multiply_ratio(*vote, uncapped_votes);
+        }
+    }
+    capped_votes
+}
+
+/// Split of the emission rewards of a proposal between its gauges.
+pub struct EmissionAllocation {
+    /// Rewards of the vault gauges
+    pub vault_share: Uint128,
+    /// Rewards of the pool gauges
+    pub pools_share: Uint128,
+    /// Rewards of the contract gauges
+    pub contracts_share: Uint128,
+    /// Rewards of the votes left out of the gauge votes
+    pub unallocated: Uint128,
+    /// Rewards of each gauge
+    pub rewards: Vec<Uint128>,
+}
+
+/// Allocates the emission rewards to the gauges by their votes over the total votes.
+pub fn allocate_emission(
+    amount: Uint128,
+    gauges: &[GaugeTarget],
+    votes: &[Uint128],
+    total_vote: Uint128,
+) -> EmissionAllocation {
+    if total_vote.is_zero() {
+        return EmissionAllocation {
+            vault_share: Uint128::zero(),
+            pools_share: Uint128::zero(),
+            contracts_share: Uint128::zero(),
+            unallocated: amount,
+            rewards: vec![Uint128::zero(); gauges.len()],
+        };
+    }
+
+    let mut vault_votes = Uint128::zero();
+    let mut pool_votes = Uint128::zero();
+    let mut contract_votes = Uint128::zero();
+    for (gauge, vote) in gauges.iter().zip(votes.iter()) {
+        match gauge {
+            GaugeTarget::Vault { .. } => vault_votes += vote,
+            GaugeTarget::Pool { .. } => pool_votes += vote,
+            GaugeTarget::Contract { .. } => contract_votes += vote,
+        }
+    }
+    let allocated_votes = vault_votes + pool_votes + contract_votes;
+
+    let pools_share = pool_votes.multiply_ratio(amount, total_vote);
+    let contracts_share = contract_votes.multiply_ratio(amount, total_vote);
+    let unallocated = (total_vote - allocated_votes).multiply_ratio(amount, total_vote);
+    let vault_share = amount - pools_share - contracts_share - unallocated;
+    let rewards = gauges
+        .iter()
+        .zip(votes.iter())
+        .map(|(gauge, vote)| match gauge {
+            GaugeTarget::Vault { .. } if !vault_votes.is_zero() => {
+                vote.multiply_ratio(vault_share, vault_votes)
+            }
+            GaugeTarget::Pool { .. } if !pool_votes.is_zero() => {
+                vote.multiply_ratio(pools_share, pool_votes)
+            }
+            GaugeTarget::Contract { .. } if !contract_votes.is_zero() => {
+                vote.multiply_ratio(contracts_share, contract_votes)
+            }
+            _ => Uint128::zero(),
+        })
+        .collect();
+
+    EmissionAllocation {
+        vault_share,
+        pools_share,
+        contracts_share,
+        unallocated,
+        rewards,
+    }
+}
+
+/// Penalty charged for unlocking the vtoken at the given time. The max penalty
+/// is scaled by the fraction of the locking period still remaining.
+pub fn early_unlock_penalty(vtoken: &Vtoken, max_penalty: Decimal, time: Timestamp) -> Uint128 {
+    if vtoken.auto_relock {
+        return vtoken.token.amount * max_penalty;
+    }
+    if time >= vtoken.end_time {
+        return Uint128::zero();
+    }
+    let start = std::cmp::max(time, vtoken.start_time);
+    vtoken.token.amount
+        * max_penalty
+        * Decimal::from_ratio(
+            vtoken.end_time.seconds() - start.seconds(),
+            vtoken.end_time.seconds() - vtoken.start_time.seconds(),
+        )
+}
+
+/// Voting power lost per second by a decaying vtoken.
+pub fn vtoken_slope(vtoken: &Vtoken) -> Decimal {
+    let duration = vtoken.end_time.seconds() - vtoken.start_time.seconds();
+    if !vtoken.decay || duration == 0 {
+        return Decimal::zero();
+    }
+    Decimal::from_ratio(vtoken.vtoken.amount, duration)
+}
+
+/// Applies the decay of the point up to the given time, removing the slope
+/// of the locks that expired in between.
+pub fn advance_decay_point(
+    storage: &dyn Storage,
+    denom: &str,
+    point: &mut DecayPoint,
+    time: Timestamp,
+) -> StdResult<()> {
+    if time <= point.timestamp {
+        return Ok(());
+    }
+    let slope_changes = SLOPE_CHANGES
+        .prefix(denom)
+        .range(
+            storage,
+            Some(Bound::exclusive(point.timestamp.seconds())),
+            Some(Bound::inclusive(time.seconds())),
+            Order::Ascending,
+        )
+        .collect::<StdResult<Vec<(u64, Decimal)>>>()?;
+
+    for (change_time, slope_change) in slope_changes {
+        let elapsed = change_time - point.timestamp.seconds();
+        point.bias = point
+            .bias
+            .saturating_sub(Uint128::from(elapsed) * point.slope);
+        point.slope = if slope_change < point.slope {
+            point.slope - slope_change
+        } else {
+            Decimal::zero()
+        };
+        point.timestamp = Timestamp::from_seconds(change_time);
+    }
+
+    let elapsed = time.seconds() - point.timestamp.seconds();
+    point.bias = point
+        .bias
+        .saturating_sub(Uint128::from(elapsed) * point.slope);
+    point.timestamp = time;
+
+    Ok(())
+}
+
+/// Total voting power of the denom as of the given height, with the decaying
+/// vtokens evaluated at the given time.
+pub fn total_voting_power(
+    storage: &dyn Storage,
+    denom: &str,
+    height: u64,
+    time: Timestamp,
+) -> StdResult<u128> {
+    let supply = match SUPPLY.may_load_at_height(storage, denom, height)? {
+        Some(val) => val,
+        None => {
+            return Err(StdError::NotFound {
+                kind: format!("No supply found for {:?}", denom),
+            })
+        }
+    };
+
+    match SUPPLY_DECAY.may_load_at_height(storage, denom, height)? {
+        Some(mut point) => {
+            advance_decay_point(storage, denom, &mut point, time)?;
+            Ok(supply.vtoken - point.vtoken + point.bias.u128())
+        }
+        None => Ok(supply.vtoken),
+    }
+}
@@ -1,5 +1,9 @@
-use crate::state::{Emission, LockingPeriod, PeriodWeight, TokenInfo, Vote, Vtoken};
+use crate::state::{
+    AppConfig, EarlyUnlockConfig, Emission, EmissionSchedule, GaugeTarget, KeeperConfig,
+    LockingPeriod, PeriodWeight, Proposal, ProposalStatus, TokenInfo, Vote, Vtoken, WeightCurve,
+};
 use cosmwasm_std::{Addr, Coin, Decimal, Uint128};
+use cw20::Cw20ReceiveMsg;
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 
@@ -16,16 +20,20 @@ pub struct InstantiateMsg {
     pub admin: Addr,
     pub min_lock_amount: Uint128,
     pub cswap_id: u64,
+    pub weight_curve: Option<WeightCurve>,
+    pub voting_decay: bool,
+    pub early_unlock: Option<EarlyUnlockConfig>,
 }
 
-#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema, Eq)]
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
 #[serde(rename_all = "snake_case")]
 pub enum ExecuteMsg {
     VoteProposal {
         app_id: u64,
         proposal_id: u64,
-        extended_pair: Vec<u64>,
+        gauges: Vec<GaugeTarget>,
         ratio: Vec<Decimal>,
+        persistent: Option<bool>,
     },
     RaiseProposal {
         app_id: u64,
@@ -36,7 +44,7 @@ pub enum ExecuteMsg {
     },
     Bribe {
         proposal_id: u64,
-        extended_pair: u64,
+        gauge: GaugeTarget,
     },
     Emission {
         proposal_id: u64,
@@ -48,9 +56,116 @@ pub enum ExecuteMsg {
         app_id: u64,
         locking_period: LockingPeriod,
         recipient: Option<Addr>,
+        auto_relock: Option<bool>,
     },
+    Receive(Cw20ReceiveMsg),
     Withdraw {
         denom: String,
+        amount: Option<Uint128>,
+        position_ids: Option<Vec<u64>>,
+        recipient: Option<String>,
+    },
+    WithdrawAll {
+        recipient: Option<String>,
+    },
+    TransferPosition {
+        position_id: u64,
+        recipient: String,
+        require_acceptance: Option<bool>,
+    },
+    AcceptPosition {
+        position_id: u64,
+    },
+    CancelPositionOffer {
+        position_id: u64,
+    },
+    StartUnlock {
+        position_id: u64,
+    },
+    EarlyWithdraw {
+        position_id: u64,
+    },
+    ExtendLock {
+        position_id: u64,
+        locking_period: LockingPeriod,
+    },
+    SplitPosition {
+        position_id: u64,
+        amount: Uint128,
+    },
+    MergePositions {
+        position_ids: Vec<u64>,
+    },
+    RegisterContractGauge {
+        app_id: u64,
+        address: String,
+    },
+    RemoveContractGauge {
+        app_id: u64,
+        address: String,
+    },
+    CancelProposal {
+        proposal_id: u64,
+    },
+    KillGauge {
+        proposal_id: u64,
+        gauge: GaugeTarget,
+    },
+    ReviveGauge {
+        proposal_id: u64,
+        gauge: GaugeTarget,
+    },
+    ReclaimBribes {
+        proposal_id: u64,
+        roll_over: Option<bool>,
+    },
+    Delegate {
+        app_id: u64,
+        delegate: String,
+        amount: Uint128,
+        delegation_period: u64,
+    },
+    Undelegate {
+        app_id: u64,
+        delegate: String,
+    },
+    UpdateDelegationCommission {
+        commission: Decimal,
+    },
+    ClearStickyVote {
+        app_id: u64,
+    },
+    ScheduleBribe {
+        app_id: u64,
+        gauge: GaugeTarget,
+        epochs: u64,
+    },
+    ReclaimScheduledBribe {
+        app_id: u64,
+        scheduled_bribe_id: u64,
+    },
+}
+
+/// Messages sent along with CW20 tokens to the `Receive` hook.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum Cw20HookMsg {
+    Lock {
+        app_id: u64,
+        locking_period: LockingPeriod,
+        recipient: Option<Addr>,
+        auto_relock: Option<bool>,
+    },
+}
+
+/// Hook executed on contract gauges, along with their share of the emission.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum GaugeHookMsg {
+    ReceiveEmission {
+        app_id: u64,
+        proposal_id: u64,
+        amount: Coin,
     },
 }
 
@@ -73,6 +188,24 @@ pub enum QueryMsg {
         denom: String,
     },
 
+    /// Query a lock position by its id.
+    Position {
+        position_id: u64,
+    },
+
+    /// Query the recipient of the pending transfer offer of a lock position.
+    PositionOffer {
+        position_id: u64,
+    },
+
+    /// Query the lock positions, optionally filtered by owner and/or denom.
+    Positions {
+        address: Option<Addr>,
+        denom: Option<String>,
+        start_after: Option<u64>,
+        limit: Option<u32>,
+    },
+
     Supply {
         denom: String,
     },
@@ -82,9 +215,23 @@ pub enum QueryMsg {
     Proposal {
         proposal_id: u64,
     },
+
+    /// Query the proposals, optionally filtered by app and/or status.
+    Proposals {
+        app_id: Option<u64>,
+        status: Option<ProposalStatus>,
+        start_after: Option<u64>,
+        limit: Option<u32>,
+    },
     BribeByProposal {
         proposal_id: u64,
-        extended_pair_id: u64,
+        gauge: GaugeTarget,
+    },
+
+    /// Query the bribes deposited by an address on a proposal.
+    BribeDeposits {
+        proposal_id: u64,
+        address: Addr,
     },
     HasVoted {
         address: Addr,
@@ -111,12 +258,64 @@ pub enum QueryMsg {
         height: Option<u64>,
     },
     State {},
+    WeightCurve {},
+    EarlyUnlock {},
+    KeeperConfig {},
+    EpochAlignment {},
+
+    /// Query the locking configuration of an app.
+    AppConfig {
+        app_id: u64,
+    },
+    Cw20GovToken {
+        app_id: u64,
+    },
+
+    /// Query the sticky vote of an address on the proposals of an app.
+    StickyVote {
+        app_id: u64,
+        address: Addr,
+    },
+
+    /// Query the voting power delegations of an address.
+    Delegations {
+        address: Addr,
+        denom: String,
+    },
+
+    /// Query the voting power delegated to an address and its commission.
+    DelegationStats {
+        address: Addr,
+        denom: String,
+    },
+
+    /// Query the bribes scheduled over the next proposals of an app.
+    ScheduledBribes {
+        app_id: u64,
+        start_after: Option<u64>,
+        limit: Option<u32>,
+    },
+
+    /// Query the gauges killed by the admin for an app.
+    KilledGauges {
+        app_id: u64,
+    },
+
+    /// Query the contracts registered as gauges of an app.
+    ContractGauges {
+        app_id: u64,
+    },
+
+    /// Query the penalty charged for unlocking a position now.
+    EarlyUnlockPenalty {
+        position_id: u64,
+    },
     Emission {
         app_id: u64,
     },
     ExtendedPairVote {
         proposal_id: u64,
-        extended_pair_id: u64,
+        gauge: GaugeTarget,
     },
     UserProposalAllUp {
         proposal_id: u64,
@@ -163,6 +362,20 @@ pub enum SudoMsg {
         emission_rate: Decimal,
         app_id: u64,
     },
+    RegisterEmission {
+        emission: Emission,
+    },
+    UpdateEmissionSchedule {
+        app_id: u64,
+        schedule: EmissionSchedule,
+    },
+    TopUpEmission {
+        app_id: u64,
+        amount: u128,
+    },
+    RetireEmission {
+        app_id: u64,
+    },
     UpdateFoundationInfo {
         addresses: Vec<String>,
         foundation_percentage: Decimal,
@@ -177,11 +390,43 @@ pub enum SudoMsg {
     UpdateVotingPeriod {
         voting_period: u64,
     },
+    UpdateWeightCurve {
+        weight_curve: Option<WeightCurve>,
+    },
+    UpdateVotingDecay {
+        voting_decay: bool,
+    },
+    UpdateEarlyUnlock {
+        early_unlock: Option<EarlyUnlockConfig>,
+    },
+    UpdateKeeperConfig {
+        keeper_config: Option<KeeperConfig>,
+    },
+    UpdateEpochAlignment {
+        epoch_alignment: Option<u64>,
+    },
+    UpdateAppConfig {
+        app_id: u64,
+        app_config: Option<AppConfig>,
+    },
+    UpdateCw20GovToken {
+        app_id: u64,
+        address: Option<Addr>,
+    },
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema, Eq)]
 pub struct MigrateMsg {
     pub cswap_id: u64,
+    /// App of each locked denom, assigned to the positions locked before they recorded it
+    #[serde(default)]
+    pub denom_apps: Vec<DenomApp>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema, Eq)]
+pub struct DenomApp {
+    pub denom: String,
+    pub app_id: u64,
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema, Eq)]
@@ -195,11 +440,33 @@ pub struct RebaseResponse {
     pub rebase_amount: Uint128,
 }
 
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema, Eq)]
+pub struct ProjectedEmissionResponse {
+    pub emission_distributed: u128,
+    pub gauges: Vec<GaugeTarget>,
+    /// Emission rewards of each gauge, with the votes capped
+    pub rewards: Vec<Uint128>,
+    /// Emission rewards of each gauge without the vote cap
+    pub uncapped_rewards: Vec<Uint128>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema, Eq)]
+pub struct ProposalResponse {
+    pub proposal_id: u64,
+    pub proposal: Proposal,
+}
+
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema, Eq)]
 pub struct WithdrawableResponse {
     pub amount: Coin,
 }
 
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema, Eq)]
+pub struct EarlyUnlockPenaltyResponse {
+    pub penalty: Coin,
+    pub withdrawable: Coin,
+}
+
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema, Eq)]
 pub struct UnlockedTokensResponse {
     pub tokens: Vec<Coin>,
@@ -222,7 +489,7 @@ pub struct ProposalVoteRespons {
 
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema, Eq)]
 pub struct ProposalPairVote {
-    pub extended_pair_id: u64,
+    pub gauge: GaugeTarget,
     pub my_vote: Uint128,
     pub total_vote: Uint128,
     pub bribe: Vec<Coin>,
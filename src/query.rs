@@ -1,17 +1,31 @@
 use crate::error::ContractError;
-use crate::helpers::get_token_supply;
-use crate::msg::{IssuedNftResponse, QueryMsg, WithdrawableResponse};
+use crate::helpers::{
+    allocate_emission, allocation_gauge_votes, cap_gauge_votes, early_unlock_penalty,
+    epoch_emission, is_emission_executed, is_unlocked, keeper_reward, load_app_config,
+    proposal_bribe_share, proposal_status, query_gov_token_supply, total_voting_power,
+    vtoken_voting_power,
+};
+use crate::msg::{
+    EarlyUnlockPenaltyResponse, IssuedNftResponse, ProjectedEmissionResponse, ProposalResponse,
+    QueryMsg, WithdrawableResponse,
+};
 use crate::state::{
-    Emission, EmissionVaultPool, LockingPeriod, Proposal, RebaseAllResponse, RewardAllResponse,
-    State, TokenSupply, Vote, VoteResponse, Vtoken, ADMIN, APPCURRENTPROPOSAL, BRIBES_BY_PROPOSAL,
-    COMPLETEDPROPOSALS, EMISSION, EMISSION_REWARD, PROPOSAL, PROPOSALVOTE, REBASE_CLAIMED, STATE,
-    SUPPLY, TOKENS, VOTERSPROPOSAL, VOTERS_CLAIM, VOTERS_VOTE, VTOKENS,
+    positions, AppConfig, BribeDeposit, DelegationStats, EarlyUnlockConfig, Emission,
+    EmissionVaultPool, GaugeTarget, KeeperConfig, Position, Proposal, ProposalStatus,
+    RebaseAllResponse, RewardAllResponse, ScheduledBribe, State, StickyVote, TokenSupply,
+    UserDelegationInfo, Vote, VoteResponse, Vtoken, WeightCurve, ADMIN, APPCURRENTPROPOSAL,
+    BRIBES_BY_PROPOSAL, BRIBE_DEPOSITS, COMPLETEDPROPOSALS, CONTRACT_GAUGES, CW20_GOV_TOKENS,
+    DELEGATED, DELEGATION_INFO, DELEGATORS, EARLY_UNLOCK, EMISSION, EMISSION_REWARD,
+    EPOCH_ALIGNMENT, KEEPER_CONFIG, KILLED_GAUGES, POSITION_OFFERS, PROPOSAL, PROPOSALVOTE,
+    REBASE_CLAIMED, SCHEDULED_BRIBES, STATE, STICKY_VOTES, SUPPLY, TOKENS, VOTERSPROPOSAL,
+    VOTERS_CLAIM, VOTERS_VOTE, VTOKENS, WEIGHT_CURVE,
 };
 use comdex_bindings::{ComdexQuery, GetPoolByAppResponse};
 use cosmwasm_std::{
-    entry_point, to_binary, Addr, Binary, Coin, Decimal, Deps, Env, QueryRequest, StdError,
+    entry_point, to_binary, Addr, Binary, Coin, Decimal, Deps, Env, Order, QueryRequest, StdError,
     StdResult, Uint128, WasmQuery,
 };
+use cw_storage_plus::Bound;
 use std::ops::{Div, Mul};
 const MAX_LIMIT: u32 = 30;
 const DEFAULT_LIMIT: u32 = 10;
@@ -33,15 +47,69 @@ pub fn query(deps: Deps<ComdexQuery>, env: Env, msg: QueryMsg) -> StdResult<Bina
             start_after,
             limit,
         )?),
+        QueryMsg::Position { position_id } => to_binary(&query_position(deps, env, position_id)?),
+        QueryMsg::PositionOffer { position_id } => {
+            to_binary(&query_position_offer(deps, env, position_id)?)
+        }
+        QueryMsg::Positions {
+            address,
+            denom,
+            start_after,
+            limit,
+        } => to_binary(&query_positions(
+            deps,
+            env,
+            address,
+            denom,
+            start_after,
+            limit,
+        )?),
         QueryMsg::Supply { denom } => to_binary(&query_issued_supply(deps, env, denom)?),
         QueryMsg::CurrentProposal { app_id } => {
             to_binary(&query_current_proposal(deps, env, app_id)?)
         }
         QueryMsg::Proposal { proposal_id } => to_binary(&query_proposal(deps, env, proposal_id)?),
-        QueryMsg::BribeByProposal {
+        QueryMsg::KilledGauges { app_id } => to_binary(&query_killed_gauges(deps, env, app_id)?),
+        QueryMsg::StickyVote { app_id, address } => {
+            to_binary(&query_sticky_vote(deps, env, app_id, address)?)
+        }
+        QueryMsg::Delegations { address, denom } => {
+            to_binary(&query_delegations(deps, env, address, denom)?)
+        }
+        QueryMsg::DelegationStats { address, denom } => {
+            to_binary(&query_delegation_stats(deps, env, address, denom)?)
+        }
+        QueryMsg::ScheduledBribes {
+            app_id,
+            start_after,
+            limit,
+        } => to_binary(&query_scheduled_bribes(
+            deps,
+            env,
+            app_id,
+            start_after,
+            limit,
+        )?),
+        QueryMsg::Proposals {
+            app_id,
+            status,
+            start_after,
+            limit,
+        } => to_binary(&query_proposals(
+            deps,
+            env,
+            app_id,
+            status,
+            start_after,
+            limit,
+        )?),
+        QueryMsg::BribeByProposal { proposal_id, gauge } => {
+            to_binary(&query_bribe(deps, env, proposal_id, gauge)?)
+        }
+        QueryMsg::BribeDeposits {
             proposal_id,
-            extended_pair_id,
-        } => to_binary(&query_bribe(deps, env, proposal_id, extended_pair_id)?),
+            address,
+        } => to_binary(&query_bribe_deposits(deps, env, proposal_id, address)?),
         QueryMsg::Vote {
             proposal_id,
             address,
@@ -58,16 +126,22 @@ pub fn query(deps: Deps<ComdexQuery>, env: Env, msg: QueryMsg) -> StdResult<Bina
             height,
         } => to_binary(&query_vtoken_balance(deps, env, address, denom, height)?),
         QueryMsg::State {} => to_binary(&query_state(deps, env)?),
+        QueryMsg::WeightCurve {} => to_binary(&query_weight_curve(deps, env)?),
+        QueryMsg::Cw20GovToken { app_id } => to_binary(&query_cw20_gov_token(deps, env, app_id)?),
+        QueryMsg::ContractGauges { app_id } => {
+            to_binary(&query_contract_gauges(deps, env, app_id)?)
+        }
+        QueryMsg::AppConfig { app_id } => to_binary(&query_app_config(deps, env, app_id)?),
+        QueryMsg::EarlyUnlock {} => to_binary(&query_early_unlock(deps, env)?),
+        QueryMsg::KeeperConfig {} => to_binary(&query_keeper_config(deps, env)?),
+        QueryMsg::EpochAlignment {} => to_binary(&query_epoch_alignment(deps, env)?),
+        QueryMsg::EarlyUnlockPenalty { position_id } => {
+            to_binary(&query_early_unlock_penalty(deps, env, position_id)?)
+        }
         QueryMsg::Emission { app_id } => to_binary(&query_emission(deps, env, app_id)?),
-        QueryMsg::ExtendedPairVote {
-            proposal_id,
-            extended_pair_id,
-        } => to_binary(&query_extendedpairvote(
-            deps,
-            env,
-            proposal_id,
-            extended_pair_id,
-        )?),
+        QueryMsg::ExtendedPairVote { proposal_id, gauge } => {
+            to_binary(&query_extendedpairvote(deps, env, proposal_id, gauge)?)
+        }
         QueryMsg::HasVoted {
             address,
             proposal_id,
@@ -150,17 +224,32 @@ pub fn query_emission_proposal(
     app_id: u64,
     gov_token_denom: String,
     gov_token_id: u64,
-) -> StdResult<u128> {
+) -> StdResult<ProjectedEmissionResponse> {
     let proposal = PROPOSAL.load(deps.storage, proposal_id)?;
-    if proposal.emission_completed {
-        return Ok(proposal.emission_distributed);
+    if is_emission_executed(&proposal) {
+        let emission_reward = EMISSION_REWARD.may_load(deps.storage, proposal_id)?;
+        let (rewards, uncapped_rewards) = match emission_reward {
+            Some(val) => (val.rewards, val.uncapped_rewards),
+            None => (vec![], vec![]),
+        };
+        return Ok(ProjectedEmissionResponse {
+            emission_distributed: proposal.emission_distributed,
+            gauges: proposal.gauges,
+            rewards,
+            uncapped_rewards,
+        });
     }
     let vtokens = SUPPLY
         .may_load_at_height(deps.storage, &gov_token_denom, proposal.height)?
         .unwrap();
 
-    let total_v_token = vtokens.vtoken;
-    let total_weight = get_token_supply(deps, app_id, gov_token_id)?;
+    let total_v_token = total_voting_power(
+        deps.storage,
+        &gov_token_denom,
+        proposal.height,
+        proposal.voting_start_time,
+    )?;
+    let total_weight = query_gov_token_supply(deps, app_id, gov_token_id)?;
     let state = STATE.load(deps.storage)?;
     let query_msg = QueryMsg::VestedTokens {
         denom: gov_token_denom,
@@ -169,31 +258,46 @@ pub fn query_emission_proposal(
         contract_addr: state.vesting_contract.to_string(),
         msg: to_binary(&query_msg).unwrap(),
     }))?;
-    let circulating_supply =
-        Uint128::from(total_weight) - query_response - Uint128::from(vtokens.token);
+    let circulating_supply = total_weight - query_response - Uint128::from(vtokens.token);
 
     let percentage_locked =
         Decimal::raw(total_v_token).div(Decimal::raw(circulating_supply.u128() + total_v_token));
     let emission = EMISSION.load(deps.storage, proposal.app_id)?;
-    let reward_emission = Uint128::from(emission.rewards_pending) * emission.emission_rate;
+    let epoch_amount = epoch_emission(&emission);
+    let reward_emission = epoch_amount - keeper_reward(deps.storage, epoch_amount)?;
     let effective_emission = reward_emission.mul(Decimal::one() - percentage_locked);
     let emission_distributed =
         effective_emission.u128() - (state.foundation_percentage.mul(effective_emission)).u128();
 
-    Ok(emission_distributed)
+    // allocation of the emission by the current votes
+    let (gauge_votes, _) =
+        allocation_gauge_votes(deps.storage, proposal_id, &proposal, total_v_token)?;
+    let total_vote: Uint128 = gauge_votes.iter().sum();
+    let capped_votes = cap_gauge_votes(&gauge_votes, proposal.gauge_vote_cap);
+    let amount = Uint128::from(emission_distributed);
+    let allocation = allocate_emission(amount, &proposal.gauges, &capped_votes, total_vote);
+    let uncapped = allocate_emission(amount, &proposal.gauges, &gauge_votes, total_vote);
+
+    Ok(ProjectedEmissionResponse {
+        emission_distributed: emission_distributed - allocation.unallocated.u128(),
+        gauges: proposal.gauges,
+        rewards: allocation.rewards,
+        uncapped_rewards: uncapped.rewards,
+    })
 }
 
 pub fn query_extendedpairvote(
     deps: Deps<ComdexQuery>,
     _env: Env,
     proposal_id: u64,
-    extended_pair_id: u64,
+    gauge: GaugeTarget,
 ) -> StdResult<Option<Uint128>> {
-    let supply = PROPOSALVOTE.may_load(deps.storage, (proposal_id, extended_pair_id))?;
+    let supply = PROPOSALVOTE.may_load(deps.storage, (proposal_id, gauge))?;
 
     Ok(supply)
 }
 
+/// Voting power of the address, with decaying vtokens evaluated at the current block time.
 pub fn query_vtoken_balance(
     deps: Deps<ComdexQuery>,
     env: Env,
@@ -211,7 +315,7 @@ pub fn query_vtoken_balance(
     let vtokens = vtokens.unwrap();
     let mut total_vtoken: u128 = 0;
     for vtoken in vtokens {
-        total_vtoken += vtoken.vtoken.amount.u128();
+        total_vtoken += vtoken_voting_power(&vtoken, env.block.time).u128();
     }
 
     Ok(Uint128::from(total_vtoken))
@@ -227,6 +331,89 @@ pub fn query_state(deps: Deps<ComdexQuery>, _env: Env) -> StdResult<State> {
     }
 }
 
+pub fn query_weight_curve(deps: Deps<ComdexQuery>, _env: Env) -> StdResult<Option<WeightCurve>> {
+    let weight_curve = WEIGHT_CURVE.may_load(deps.storage)?;
+    Ok(weight_curve)
+}
+
+pub fn query_cw20_gov_token(
+    deps: Deps<ComdexQuery>,
+    _env: Env,
+    app_id: u64,
+) -> StdResult<Option<Addr>> {
+    let address = CW20_GOV_TOKENS.may_load(deps.storage, app_id)?;
+    Ok(address)
+}
+
+pub fn query_contract_gauges(
+    deps: Deps<ComdexQuery>,
+    _env: Env,
+    app_id: u64,
+) -> StdResult<Vec<Addr>> {
+    let contract_gauges = CONTRACT_GAUGES
+        .may_load(deps.storage, app_id)?
+        .unwrap_or_default();
+    Ok(contract_gauges)
+}
+
+pub fn query_app_config(deps: Deps<ComdexQuery>, _env: Env, app_id: u64) -> StdResult<AppConfig> {
+    load_app_config(deps.storage, app_id)
+}
+
+pub fn query_early_unlock(
+    deps: Deps<ComdexQuery>,
+    _env: Env,
+) -> StdResult<Option<EarlyUnlockConfig>> {
+    let early_unlock = EARLY_UNLOCK.may_load(deps.storage)?;
+    Ok(early_unlock)
+}
+
+pub fn query_keeper_config(deps: Deps<ComdexQuery>, _env: Env) -> StdResult<Option<KeeperConfig>> {
+    let keeper_config = KEEPER_CONFIG.may_load(deps.storage)?;
+    Ok(keeper_config)
+}
+
+pub fn query_epoch_alignment(deps: Deps<ComdexQuery>, _env: Env) -> StdResult<Option<u64>> {
+    let epoch_alignment = EPOCH_ALIGNMENT.may_load(deps.storage)?;
+    Ok(epoch_alignment)
+}
+
+pub fn query_early_unlock_penalty(
+    deps: Deps<ComdexQuery>,
+    env: Env,
+    position_id: u64,
+) -> StdResult<EarlyUnlockPenaltyResponse> {
+    let early_unlock = match EARLY_UNLOCK.may_load(deps.storage)? {
+        Some(val) => val,
+        None => {
+            return Err(StdError::NotFound {
+                kind: String::from("Early unlock is not enabled"),
+            })
+        }
+    };
+    let position = match positions().may_load(deps.storage, position_id)? {
+        Some(val) => val,
+        None => {
+            return Err(StdError::NotFound {
+                kind: format!("No lock position found for id {}", position_id),
+            })
+        }
+    };
+
+    let vtoken = position.vtoken;
+    let penalty = early_unlock_penalty(&vtoken, early_unlock.max_penalty, env.block.time);
+    Ok(EarlyUnlockPenaltyResponse {
+        penalty: Coin {
+            denom: vtoken.token.denom.clone(),
+            amount: penalty,
+        },
+        withdrawable: Coin {
+            denom: vtoken.token.denom,
+            amount: vtoken.token.amount - penalty,
+        },
+    })
+}
+
 pub fn query_issued_nft(
     deps: Deps<ComdexQuery>,
     _env: Env,
@@ -276,6 +463,82 @@ pub fn query_issued_vtokens(
     Ok(state)
 }
 
+pub fn query_position(
+    deps: Deps<ComdexQuery>,
+    _env: Env,
+    position_id: u64,
+) -> StdResult<Option<Position>> {
+    let position = positions().may_load(deps.storage, position_id)?;
+    Ok(position)
+}
+
+pub fn query_position_offer(
+    deps: Deps<ComdexQuery>,
+    _env: Env,
+    position_id: u64,
+) -> StdResult<Option<Addr>> {
+    let recipient = POSITION_OFFERS.may_load(deps.storage, position_id)?;
+    Ok(recipient)
+}
+
+pub fn query_positions(
+    deps: Deps<ComdexQuery>,
+    _env: Env,
+    address: Option<Addr>,
+    denom: Option<String>,
+    start_after: Option<u64>,
+    limit: Option<u32>,
+) -> StdResult<Vec<Position>> {
+    let limit = limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT) as usize;
+    let start = start_after.map(Bound::exclusive);
+
+    let positions: Vec<Position> = match (address, denom) {
+        (Some(address), Some(denom)) => {
+            deps.api.addr_validate(address.as_str())?;
+            positions()
+                .idx
+                .owner
+                .prefix((address, denom))
+                .range(deps.storage, start, None, Order::Ascending)
+                .take(limit)
+                .map(|item| item.map(|(_, position)| position))
+                .collect::<StdResult<_>>()?
+        }
+        (Some(address), None) => {
+            // positions of the owner are ordered by denom, hence filtered by id
+            deps.api.addr_validate(address.as_str())?;
+            let mut owner_positions = positions()
+                .idx
+                .owner
+                .sub_prefix(address)
+                .range(deps.storage, None, None, Order::Ascending)
+                .map(|item| item.map(|(_, position)| position))
+                .collect::<StdResult<Vec<Position>>>()?;
+            if let Some(start_after) = start_after {
+                owner_positions.retain(|position| position.vtoken.id > start_after);
+            }
+            owner_positions.sort_by_key(|position| position.vtoken.id);
+            owner_positions.truncate(limit);
+            owner_positions
+        }
+        (None, Some(denom)) => positions()
+            .idx
+            .denom
+            .prefix(denom)
+            .range(deps.storage, start, None, Order::Ascending)
+            .take(limit)
+            .map(|item| item.map(|(_, position)| position))
+            .collect::<StdResult<_>>()?,
+        (None, None) => positions()
+            .range(deps.storage, start, None, Order::Ascending)
+            .take(limit)
+            .map(|item| item.map(|(_, position)| position))
+            .collect::<StdResult<_>>()?,
+    };
+
+    Ok(positions)
+}
+
 pub fn query_issued_supply(
     deps: Deps<ComdexQuery>,
     _env: Env,
@@ -290,13 +553,140 @@ pub fn query_current_proposal(deps: Deps<ComdexQuery>, _env: Env, app_id: u64) -
     Ok(supply.unwrap_or(0))
 }
 
+/// Proposal with its status at the current block time.
 pub fn query_proposal(
     deps: Deps<ComdexQuery>,
-    _env: Env,
+    env: Env,
     proposal_id: u64,
 ) -> StdResult<Option<Proposal>> {
-    let supply = PROPOSAL.may_load(deps.storage, proposal_id)?;
-    Ok(supply)
+    let proposal = PROPOSAL
+        .may_load(deps.storage, proposal_id)?
+        .map(|mut proposal| {
+            proposal.status = proposal_status(&proposal, env.block.time);
+            proposal
+        });
+    Ok(proposal)
+}
+
+pub fn query_proposals(
+    deps: Deps<ComdexQuery>,
+    env: Env,
+    app_id: Option<u64>,
+    status: Option<ProposalStatus>,
+    start_after: Option<u64>,
+    limit: Option<u32>,
+) -> StdResult<Vec<ProposalResponse>> {
+    let limit = limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT) as usize;
+    let start = start_after.map(Bound::exclusive);
+
+    let mut proposals = vec![];
+    for item in PROPOSAL.range(deps.storage, start, None, Order::Ascending) {
+        let (proposal_id, mut proposal) = item?;
+        proposal.status = proposal_status(&proposal, env.block.time);
+        if matches!(app_id, Some(app_id) if app_id != proposal.app_id)
+            || matches!(status, Some(status) if status != proposal.status)
+        {
+            continue;
+        }
+        proposals.push(ProposalResponse {
+            proposal_id,
+            proposal,
+        });
+        if proposals.len() == limit {
+            break;
+        }
+    }
+    Ok(proposals)
+}
+
+pub fn query_killed_gauges(
+    deps: Deps<ComdexQuery>,
+    _env: Env,
+    app_id: u64,
+) -> StdResult<Vec<GaugeTarget>> {
+    KILLED_GAUGES
+        .prefix(app_id)
+        .keys(deps.storage, None, None, Order::Ascending)
+        .collect()
+}
+
+pub fn query_sticky_vote(
+    deps: Deps<ComdexQuery>,
+    _env: Env,
+    app_id: u64,
+    address: Addr,
+) -> StdResult<Option<StickyVote>> {
+    let sticky_vote = STICKY_VOTES.may_load(deps.storage, (app_id, address))?;
+    Ok(sticky_vote)
+}
+
+pub fn query_delegations(
+    deps: Deps<ComdexQuery>,
+    _env: Env,
+    address: Addr,
+    denom: String,
+) -> StdResult<UserDelegationInfo> {
+    let delegations = DELEGATED
+        .may_load(deps.storage, (address, &denom))?
+        .unwrap_or(UserDelegationInfo {
+            total_casted: 0,
+            delegations: vec![],
+        });
+    Ok(delegations)
+}
+
+pub fn query_delegation_stats(
+    deps: Deps<ComdexQuery>,
+    env: Env,
+    address: Addr,
+    denom: String,
+) -> StdResult<DelegationStats> {
+    let delegators = DELEGATORS
+        .may_load(deps.storage, (address.clone(), &denom))?
+        .unwrap_or_default();
+
+    let mut total_delegated = 0u128;
+    let mut total_delegators = 0u64;
+    for delegator in delegators {
+        let delegations = DELEGATED
+            .may_load(deps.storage, (delegator, &denom))?
+            .map(|info| info.delegations)
+            .unwrap_or_default();
+        if let Some(delegation) = delegations.iter().find(|delegation| {
+            delegation.delegated_to == address && delegation.delegation_end_at > env.block.time
+        }) {
+            total_delegated += delegation.delegated;
+            total_delegators += 1;
+        }
+    }
+    let commission = DELEGATION_INFO
+        .may_load(deps.storage, address)?
+        .map(|info| info.commission)
+        .unwrap_or_default();
+
+    Ok(DelegationStats {
+        total_delegated,
+        total_delegators,
+        commission,
+    })
+}
+
+pub fn query_scheduled_bribes(
+    deps: Deps<ComdexQuery>,
+    _env: Env,
+    app_id: u64,
+    start_after: Option<u64>,
+    limit: Option<u32>,
+) -> StdResult<Vec<ScheduledBribe>> {
+    let limit = limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT) as usize;
+    let start = start_after.map(Bound::exclusive);
+
+    SCHEDULED_BRIBES
+        .prefix(app_id)
+        .range(deps.storage, start, None, Order::Ascending)
+        .take(limit)
+        .map(|item| item.map(|(_, scheduled_bribe)| scheduled_bribe))
+        .collect()
 }
 
 pub fn query_proposal_rewards(
@@ -312,12 +702,24 @@ pub fn query_bribe(
     deps: Deps<ComdexQuery>,
     _env: Env,
     proposal_id: u64,
-    extended_pair_id: u64,
+    gauge: GaugeTarget,
 ) -> StdResult<Option<Vec<Coin>>> {
-    let supply = BRIBES_BY_PROPOSAL.may_load(deps.storage, (proposal_id, extended_pair_id))?;
+    let supply = BRIBES_BY_PROPOSAL.may_load(deps.storage, (proposal_id, gauge))?;
     Ok(supply)
 }
 
+pub fn query_bribe_deposits(
+    deps: Deps<ComdexQuery>,
+    _env: Env,
+    proposal_id: u64,
+    address: Addr,
+) -> StdResult<Vec<BribeDeposit>> {
+    let deposits = BRIBE_DEPOSITS
+        .may_load(deps.storage, (proposal_id, address))?
+        .unwrap_or_default();
+    Ok(deposits)
+}
+
 pub fn query_is_voted(
     deps: Deps<ComdexQuery>,
     _env: Env,
@@ -372,7 +774,7 @@ pub fn query_withdrawable(
     let denom_param = denom.to_owned();
     let withdraw_amount: u128 = vtokens
         .into_iter()
-        .filter(|el| el.token.denom == denom && el.end_time < env.block.time)
+        .filter(|el| el.token.denom == denom && is_unlocked(el, env.block.time))
         .fold(0u128, |acc, el| acc + el.token.amount.u128());
 
     Ok(WithdrawableResponse {
@@ -410,55 +812,11 @@ pub fn calculate_bribe_reward_query(
     all_proposals.sort();
     //check if active proposal
     for proposalid in all_proposals {
-        let mut bribe_coins: Vec<Coin> = vec![];
         let claimed = VOTERS_CLAIM
             .may_load(deps.storage, (address.clone(), proposalid))?
             .unwrap_or_default();
-        let vote = VOTERSPROPOSAL.may_load(deps.storage, (address.to_owned(), proposalid))?;
-
-        if vote.is_some() {
-            let vote = vote.unwrap();
-
-            for pair in vote.votes {
-                let total_vote_weight = PROPOSALVOTE
-                    .load(deps.storage, (proposalid, pair.extended_pair))?
-                    .u128();
-
-                let total_bribe = match BRIBES_BY_PROPOSAL
-                    .may_load(deps.storage, (proposalid, pair.extended_pair))?
-                {
-                    Some(val) => val,
-                    None => vec![],
-                };
-
-                let mut claimable_bribe: Vec<Coin> = vec![];
-                for coin in total_bribe.clone() {
-                    let claimable_amount = (Decimal::new(Uint128::from(pair.vote_weight))
-                        .div(Decimal::new(Uint128::from(total_vote_weight))))
-                    .mul(coin.amount);
-                    let claimable_coin = Coin {
-                        amount: claimable_amount,
-                        denom: coin.denom,
-                    };
-                    claimable_bribe.push(claimable_coin);
-                }
-
-                for bribe_deposited in claimable_bribe.clone() {
-                    match bribe_coins
-                        .iter_mut()
-                        .find(|p| bribe_deposited.denom == p.denom)
-                    {
-                        Some(pivot) => {
-                            pivot.denom = bribe_deposited.denom;
-                            pivot.amount += bribe_deposited.amount;
-                        }
-                        None => {
-                            bribe_coins.push(bribe_deposited);
-                        }
-                    }
-                }
-            }
-        }
+        let mut bribe_coins =
+            proposal_bribe_share(deps.storage, &address, proposalid)?.unwrap_or_default();
         bribe_coins.retain(|coin| !coin.amount.is_zero());
         let response = RewardAllResponse {
             proposal_id: proposalid,
@@ -491,7 +849,7 @@ pub fn query_rebase_eligible(
             .may_load(deps.storage, (address.clone(), proposal_id_param))?
             .unwrap_or_default();
         let proposal = PROPOSAL.load(deps.storage, proposal_id_param)?;
-        if !proposal.emission_completed {
+        if !is_emission_executed(&proposal) {
             continue;
         } else {
             let supply = SUPPLY
@@ -511,14 +869,10 @@ pub fn query_rebase_eligible(
             let rebase_amount_param = if vtokens.is_empty() {
                 Uint128::zero()
             } else {
-                let (locked_t1, locked_t2): (u128, u128) =
-                    vtokens
-                        .iter()
-                        .fold((0, 0), |(acc_t1, acc_t2), vtoken| match vtoken.period {
-                            LockingPeriod::T1 => (acc_t1 + vtoken.token.amount.u128(), acc_t2),
-                            LockingPeriod::T2 => (acc_t1, acc_t2 + vtoken.token.amount.u128()),
-                        });
-                let sum = locked_t1 + locked_t2;
+                let sum: u128 = vtokens
+                    .iter()
+                    .map(|vtoken| vtoken.token.amount.u128())
+                    .sum();
                 (Uint128::from(total_rebase_amount).checked_mul(Uint128::from(sum))?)
                     .checked_div(Uint128::from(total_locked))?
             };
@@ -547,12 +901,13 @@ pub fn query_current_proposal_user(
     let proposal = PROPOSAL.load(deps.storage, current_proposal)?;
 
     let mut resp = vec![];
-    for ext_pair in proposal.extended_pair {
+    for gauge in proposal.gauges {
         let mut user_vote = 0;
         let mut user_vote_ratio = Decimal::zero();
         let mut total_incentive = vec![];
         let mut total_vote = 0;
-        let proposal_vote = PROPOSALVOTE.may_load(deps.storage, (current_proposal, ext_pair))?;
+        let proposal_vote =
+            PROPOSALVOTE.may_load(deps.storage, (current_proposal, gauge.clone()))?;
         if let Some(..) = proposal_vote {
             let proposal_vote = proposal_vote.unwrap();
             total_vote = proposal_vote.u128();
@@ -562,7 +917,7 @@ pub fn query_current_proposal_user(
         if let Some(..) = vote {
             let vote = vote.unwrap();
             let vote = vote.votes;
-            let vote_tmp = vote.into_iter().find(|x| x.extended_pair == ext_pair);
+            let vote_tmp = vote.into_iter().find(|x| x.gauge == gauge);
             if let Some(..) = vote_tmp {
                 let vote_tmp = vote_tmp.unwrap();
                 user_vote = vote_tmp.vote_weight;
@@ -570,13 +925,13 @@ pub fn query_current_proposal_user(
             }
         }
         //// load bribe////
-        let bribe = BRIBES_BY_PROPOSAL.may_load(deps.storage, (current_proposal, ext_pair))?;
+        let bribe = BRIBES_BY_PROPOSAL.may_load(deps.storage, (current_proposal, gauge.clone()))?;
         if let Some(..) = bribe {
             let bribe = bribe.unwrap();
             total_incentive = bribe;
         }
         let vote_response = VoteResponse {
-            pair: ext_pair,
+            gauge,
             user_vote: user_vote,
             user_vote_ratio: user_vote_ratio,
             total_incentive: total_incentive,
@@ -636,6 +991,8 @@ mod tests {
         // Store some test vtokens
         let data = vec![
             Vtoken {
+                id: 0,
+                app_id: 0,
                 token: Coin {
                     denom: DENOM.to_string(),
                     amount: Uint128::from(1000u128),
@@ -648,8 +1005,13 @@ mod tests {
                 start_time: env.block.time,
                 end_time: env.block.time.plus_seconds(100_000),
                 status: Status::Locked,
+                decay: false,
+                auto_relock: false,
+                weight: None,
             },
             Vtoken {
+                id: 0,
+                app_id: 0,
                 token: Coin {
                     denom: DENOM.to_string(),
                     amount: Uint128::from(250u128),
@@ -662,6 +1024,9 @@ mod tests {
                 start_time: Timestamp::from_seconds(0),
                 end_time: Timestamp::from_seconds(20),
                 status: Status::Locked,
+                decay: false,
+                auto_relock: false,
+                weight: None,
             },
         ];
         _ = VTOKENS.save(
@@ -1,9 +1,13 @@
 use cosmwasm_std::{Addr, Timestamp};
-use cosmwasm_std::{Coin, Decimal, Uint128};
+use cosmwasm_std::{Coin, Decimal, StdError, StdResult, Uint128};
 use cw_controllers::Admin;
-use cw_storage_plus::{Item, Map, SnapshotMap, Strategy};
+use cw_storage_plus::{
+    Index, IndexList, IndexedMap, Item, Key, KeyDeserialize, Map, MultiIndex, PrimaryKey,
+    SnapshotMap, Strategy,
+};
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
+use std::convert::TryInto;
 
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema, Eq)]
 #[serde(rename_all = "snake_case")]
@@ -17,6 +21,62 @@ pub struct PeriodWeight {
 pub enum LockingPeriod {
     T1,
     T2,
+    /// Arbitrary locking duration (in seconds), weighted using the `WeightCurve`.
+    Custom {
+        duration: u64,
+    },
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum CurveType {
+    /// Weight is linearly interpolated between the two surrounding points.
+    Linear,
+    /// Weight of the last point whose period does not exceed the duration.
+    Stepped,
+}
+
+/// Weight curve used to calculate the vtoken weight of custom lock durations.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema, Eq)]
+#[serde(rename_all = "snake_case")]
+pub struct WeightCurve {
+    /// Minimum locking duration, in seconds
+    pub min_period: u64,
+    /// Maximum locking duration, in seconds
+    pub max_period: u64,
+    pub curve_type: CurveType,
+    /// Points of the curve, sorted by period
+    pub points: Vec<PeriodWeight>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum PenaltyDestination {
+    /// Sent to the foundation addresses.
+    Foundation,
+    /// Added to the rebase of the next emission of the app, for the remaining lockers.
+    Redistribute,
+    /// Burnt from the contract.
+    Burn,
+}
+
+/// Configuration of the permissionless rollover of proposals and emissions.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema, Eq)]
+#[serde(rename_all = "snake_case")]
+pub struct KeeperConfig {
+    /// Governance tokens paid from the emission budget of the app to the caller
+    /// of `RaiseProposal` and `Emission`
+    pub reward: Uint128,
+}
+
+/// Configuration of the withdrawal of locked tokens before the end of their locking period.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema, Eq)]
+#[serde(rename_all = "snake_case")]
+pub struct EarlyUnlockConfig {
+    /// Penalty charged when unlocking right after locking, decreasing
+    /// linearly to zero at the end of the locking period
+    pub max_penalty: Decimal,
+    pub destination: PenaltyDestination,
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema, Eq)]
@@ -32,18 +92,43 @@ pub enum Status {
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema, Eq)]
 #[serde(rename_all = "snake_case")]
 pub struct Vtoken {
+    /// Unique id of the lock position
+    #[serde(default)]
+    pub id: u64,
+    /// App for which the tokens are locked
+    #[serde(default)]
+    pub app_id: u64,
     /// amount of token being locked
     pub token: Coin,
     /// amount of vtoken created
     pub vtoken: Coin,
-    /// Locking period i.e. T1 or T2
+    /// Locking period i.e. T1, T2 or a custom duration
     pub period: LockingPeriod,
     /// Time at which the tokens were locked
     pub start_time: Timestamp,
-    /// Point in time after which the tokens can be unlocked
+    /// Point in time after which the tokens can be unlocked. For auto-relock
+    /// positions, it rolls forward until the unlock is started.
     pub end_time: Timestamp,
     /// Current status of the tokens
     pub status: Status,
+    /// Whether the voting power decays linearly to zero at `end_time`
+    #[serde(default)]
+    pub decay: bool,
+    /// Whether the position is relocked perpetually, until `StartUnlock` is called
+    #[serde(default)]
+    pub auto_relock: bool,
+    /// Weight the tokens were locked at, unset for positions locked before it was recorded
+    #[serde(default)]
+    pub weight: Option<Decimal>,
+}
+
+/// Lock position, addressable by the vtoken id
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema, Eq)]
+#[serde(rename_all = "snake_case")]
+pub struct Position {
+    /// Owner of the locked tokens
+    pub owner: Addr,
+    pub vtoken: Vtoken,
 }
 
 /// NFT struct for holding the token info
@@ -71,6 +156,56 @@ pub struct State {
     pub min_lock_amount: Uint128,
 }
 
+/// Locking configuration of an app, overriding the defaults held in `State`.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema, Eq)]
+#[serde(rename_all = "snake_case")]
+pub struct AppConfig {
+    pub t1: PeriodWeight,
+    pub t2: PeriodWeight,
+    pub min_lock_amount: Uint128,
+    pub voting_period: u64,
+    pub surplus_asset_id: u64,
+    /// Maximum share of the votes of a proposal counted for a single gauge
+    #[serde(default)]
+    pub gauge_vote_cap: Option<Decimal>,
+    /// Minimum share of the total voting power voting on a proposal
+    #[serde(default)]
+    pub quorum: Option<Decimal>,
+    /// Distribution of the emission of the proposals missing the quorum
+    #[serde(default)]
+    pub emission_fallback: EmissionFallback,
+    /// Minimum amount of a scheduled bribe released in each epoch
+    #[serde(default = "default_min_scheduled_bribe")]
+    pub min_scheduled_bribe: Uint128,
+}
+
+/// Minimum amount of a scheduled bribe released in each epoch, for apps not configuring it
+pub const DEFAULT_MIN_SCHEDULED_BRIBE: Uint128 = Uint128::new(1_000_000);
+
+fn default_min_scheduled_bribe() -> Uint128 {
+    DEFAULT_MIN_SCHEDULED_BRIBE
+}
+
+/// Distribution of the emission of a proposal whose votes missed the quorum.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, JsonSchema, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum EmissionFallback {
+    /// The emission of the gauges returns to the pending rewards of the app
+    CarryForward,
+    /// The emission is split equally across the gauges
+    EqualSplit,
+    /// The emission is split as the one of the previous proposal of the app
+    PreviousEpoch,
+}
+
+// `#[default]` enum variants are not available with the toolchain of the optimizer
+#[allow(clippy::derivable_impls)]
+impl Default for EmissionFallback {
+    fn default() -> Self {
+        EmissionFallback::CarryForward
+    }
+}
+
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema, Eq)]
 #[serde(rename_all = "snake_case")]
 pub struct TokenSupply {
@@ -86,6 +221,28 @@ pub const STATE: Item<State> = Item::new("state");
 // Owner to NFT
 pub const ADMIN: Admin = Admin::new("admin_address");
 
+// Locking configuration of each app, the State defaults apply to apps without one
+pub const APP_CONFIG: Map<u64, AppConfig> = Map::new("app_config");
+
+// Weight curve for custom locking durations
+pub const WEIGHT_CURVE: Item<WeightCurve> = Item::new("weight_curve");
+
+// CW20 governance token accepted for locking, for each app
+pub const CW20_GOV_TOKENS: Map<u64, Addr> = Map::new("cw20_gov_tokens");
+
+// Early unlock configuration, early unlocks are disabled when not set
+pub const EARLY_UNLOCK: Item<EarlyUnlockConfig> = Item::new("early_unlock");
+
+// Keeper configuration, keepers are not rewarded when not set
+pub const KEEPER_CONFIG: Item<KeeperConfig> = Item::new("keeper_config");
+
+// Epoch length (in seconds) voting end times are rounded up to a multiple of,
+// e.g. 604800 for weekly epochs, voting end times are not aligned when not set
+pub const EPOCH_ALIGNMENT: Item<u64> = Item::new("epoch_alignment");
+
+// Early unlock penalties of each app pending redistribution with the next rebase
+pub const PENDING_PENALTIES: Map<u64, u128> = Map::new("pending_penalties");
+
 pub const TOKENS: Map<Addr, TokenInfo> = Map::new("tokens");
 // Total supply of each (vtoken supplied, token deposited)
 pub const SUPPLY: SnapshotMap<&str, TokenSupply> = SnapshotMap::new(
@@ -95,6 +252,34 @@ pub const SUPPLY: SnapshotMap<&str, TokenSupply> = SnapshotMap::new(
     Strategy::EveryBlock,
 );
 
+/// Aggregated linear decay of the decaying vtokens of a denom.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema, Eq)]
+#[serde(rename_all = "snake_case")]
+pub struct DecayPoint {
+    /// Decayed voting power at `timestamp`
+    pub bias: Uint128,
+    /// Voting power lost per second
+    pub slope: Decimal,
+    /// Vtokens, as issued, of the decaying locks included in SUPPLY
+    pub vtoken: u128,
+    /// Time up to which the decay has been applied
+    pub timestamp: Timestamp,
+}
+
+// Whether newly created locks have a decaying voting power
+pub const VOTING_DECAY: Item<bool> = Item::new("voting_decay");
+
+// Decay of the total voting power of each denom
+pub const SUPPLY_DECAY: SnapshotMap<&str, DecayPoint> = SnapshotMap::new(
+    "supply_decay",
+    "supply_decay_checkpoints",
+    "supply_decay_changelogs",
+    Strategy::EveryBlock,
+);
+
+// Slope to be removed from the SUPPLY_DECAY of a denom at the given time (in seconds)
+pub const SLOPE_CHANGES: Map<(&str, u64), Decimal> = Map::new("slope_changes");
+
 // Vtoken owned by an address for a specific denom
 pub const VTOKENS: SnapshotMap<(Addr, &str), Vec<Vtoken>> = SnapshotMap::new(
     "owner_vtoken",
@@ -103,21 +288,183 @@ pub const VTOKENS: SnapshotMap<(Addr, &str), Vec<Vtoken>> = SnapshotMap::new(
     Strategy::EveryBlock,
 );
 
+// Number of lock positions created, used as the id of the next position
+pub const POSITIONCOUNT: Item<u64> = Item::new("position_count");
+
+// Pending transfer offers, recipient of each offered position
+pub const POSITION_OFFERS: Map<u64, Addr> = Map::new("position_offers");
+
+pub struct PositionIndexes<'a> {
+    // Positions by (owner, denom)
+    pub owner: MultiIndex<'a, (Addr, String), Position, u64>,
+    // Positions by denom
+    pub denom: MultiIndex<'a, String, Position, u64>,
+}
+
+impl<'a> IndexList<Position> for PositionIndexes<'a> {
+    fn get_indexes(&'_ self) -> Box<dyn Iterator<Item = &'_ dyn Index<Position>> + '_> {
+        let v: Vec<&dyn Index<Position>> = vec![&self.owner, &self.denom];
+        Box::new(v.into_iter())
+    }
+}
+
+// Lock positions by id. VTOKENS holds the snapshot of the positions of each (owner, denom).
+pub fn positions<'a>() -> IndexedMap<'a, u64, Position, PositionIndexes<'a>> {
+    let indexes = PositionIndexes {
+        owner: MultiIndex::new(
+            |position: &Position| (position.owner.clone(), position.vtoken.token.denom.clone()),
+            "positions",
+            "positions__owner",
+        ),
+        denom: MultiIndex::new(
+            |position: &Position| position.vtoken.token.denom.clone(),
+            "positions",
+            "positions__denom",
+        ),
+    };
+    IndexedMap::new("positions", indexes)
+}
+
+/// Target of the emission, voted upon in proposals.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema, Eq, Hash)]
+#[serde(rename_all = "snake_case")]
+pub enum GaugeTarget {
+    /// Vaults of an extended pair of the app
+    Vault { ext_pair_id: u64 },
+    /// Liquidity pool of a cswap app
+    Pool { app_id: u64, pool_id: u64 },
+    /// Contract registered by the admin, receiving its emission through the
+    /// `ReceiveEmission` hook
+    Contract { address: Addr },
+}
+
+const GAUGE_VAULT: u8 = 0;
+const GAUGE_POOL: u8 = 1;
+const GAUGE_CONTRACT: u8 = 2;
+
+impl<'a> PrimaryKey<'a> for GaugeTarget {
+    type Prefix = ();
+    type SubPrefix = ();
+    type Suffix = Self;
+    type SuperSuffix = Self;
+
+    fn key(&self) -> Vec<Key<'_>> {
+        match self {
+            GaugeTarget::Vault { ext_pair_id } => vec![
+                Key::Val8([GAUGE_VAULT]),
+                Key::Val64(ext_pair_id.to_be_bytes()),
+            ],
+            GaugeTarget::Pool { app_id, pool_id } => vec![
+                Key::Val8([GAUGE_POOL]),
+                Key::Val64(app_id.to_be_bytes()),
+                Key::Val64(pool_id.to_be_bytes()),
+            ],
+            GaugeTarget::Contract { address } => {
+                vec![Key::Val8([GAUGE_CONTRACT]), Key::Ref(address.as_bytes())]
+            }
+        }
+    }
+}
+
+impl KeyDeserialize for GaugeTarget {
+    type Output = Self;
+
+    fn from_vec(value: Vec<u8>) -> StdResult<Self> {
+        let id = |bytes: &[u8]| -> StdResult<u64> {
+            Ok(u64::from_be_bytes(bytes.try_into().map_err(|_| {
+                StdError::generic_err("Invalid gauge target key")
+            })?))
+        };
+        // the length prefixed kind, followed by the ids (all but the last one length prefixed)
+        match value.as_slice() {
+            [0, 1, GAUGE_VAULT, ext_pair_id @ ..] => Ok(GaugeTarget::Vault {
+                ext_pair_id: id(ext_pair_id)?,
+            }),
+            [0, 1, GAUGE_POOL, 0, 8, ids @ ..] if ids.len() == 16 => Ok(GaugeTarget::Pool {
+                app_id: id(&ids[..8])?,
+                pool_id: id(&ids[8..])?,
+            }),
+            [0, 1, GAUGE_CONTRACT, address @ ..] => Ok(GaugeTarget::Contract {
+                address: Addr::unchecked(
+                    String::from_utf8(address.to_vec())
+                        .map_err(|_| StdError::generic_err("Invalid gauge target key"))?,
+                ),
+            }),
+            _ => Err(StdError::generic_err("Invalid gauge target key")),
+        }
+    }
+}
+
+/// Lifecycle of a proposal.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, JsonSchema, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ProposalStatus {
+    /// Open for voting and bribes
+    Active,
+    /// Voting period ended, awaiting the emission
+    VotingEnded,
+    /// Emission, foundation share and rebase minted
+    EmissionExecuted,
+    /// Emission executed and the next proposal of the app raised
+    Finalized,
+    /// Cancelled before its emission
+    Cancelled,
+}
+
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema, Eq)]
 pub struct Proposal {
     pub app_id: u64,
     pub voting_start_time: Timestamp,
     pub voting_end_time: Timestamp,
-    pub extended_pair: Vec<u64>,
-    pub emission_completed: bool,
-    pub rebase_completed: bool,
-    pub foundation_emission_completed: bool,
+    pub gauges: Vec<GaugeTarget>,
+    /// Stored status, `Active` proposals are `VotingEnded` once `voting_end_time` has passed
+    pub status: ProposalStatus,
     pub emission_distributed: u128,
     pub rebase_distributed: u128,
     pub foundation_distributed: u128,
     pub total_voted_weight: u128,
     pub total_surplus: Coin,
     pub height: u64,
+    /// Maximum share of the votes counted for a single gauge in the emission
+    #[serde(default)]
+    pub gauge_vote_cap: Option<Decimal>,
+    /// Gauges killed by the admin, excluded from the emission
+    #[serde(default)]
+    pub killed_gauges: Vec<GaugeTarget>,
+    /// Minimum share of the total voting power voting on the proposal
+    #[serde(default)]
+    pub quorum: Option<Decimal>,
+    /// Distribution of the emission if the votes miss the quorum
+    #[serde(default)]
+    pub emission_fallback: EmissionFallback,
+}
+
+/// Amount of rewards released by the emission of each epoch (proposal).
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum EmissionSchedule {
+    /// `emission_rate` of the pending rewards
+    Constant,
+    /// `initial_amount`, halved every `epochs` epochs
+    Halving {
+        initial_amount: Uint128,
+        epochs: u64,
+    },
+    /// `initial_amount`, reduced by `decay_rate` every epoch
+    ExponentialDecay {
+        initial_amount: Uint128,
+        decay_rate: Decimal,
+    },
+    /// Explicit amount of each epoch, nothing is released after the last one
+    Table { amounts: Vec<Uint128> },
+}
+
+// `#[default]` enum variants are not available with the toolchain of the optimizer
+#[allow(clippy::derivable_impls)]
+impl Default for EmissionSchedule {
+    fn default() -> Self {
+        EmissionSchedule::Constant
+    }
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema, Eq)]
@@ -127,6 +474,14 @@ pub struct Emission {
     pub rewards_pending: u128,
     pub emission_rate: Decimal,
     pub distributed_rewards: u128,
+    #[serde(default)]
+    pub schedule: EmissionSchedule,
+    /// Number of emissions completed, i.e. the current epoch of the schedule
+    #[serde(default)]
+    pub epoch: u64,
+    /// Rewards of the last emission left unallocated, released on top of the next one
+    #[serde(default)]
+    pub carried_forward: u128,
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema, Eq)]
@@ -137,11 +492,32 @@ pub struct Reward {
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema, Eq)]
 pub struct EmissionVaultPool {
     pub app_id: u64,
-    pub pool_ids: Vec<u64>,
-    pub vault_ids: Vec<u64>,
+    pub gauges: Vec<GaugeTarget>,
     pub total_emission_rewards: Uint128,
-    pub pool_rewards: Vec<Uint128>,
-    pub vault_rewards: Vec<Uint128>,
+    /// Emission rewards of each gauge, in the order of `gauges`
+    pub rewards: Vec<Uint128>,
+    /// Emission rewards each gauge would have received without the vote cap
+    #[serde(default)]
+    pub uncapped_rewards: Vec<Uint128>,
+}
+
+/// Bribes deposited by a user on a gauge of a proposal.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema, Eq)]
+pub struct BribeDeposit {
+    pub gauge: GaugeTarget,
+    pub coins: Vec<Coin>,
+}
+
+/// Bribe released in equal parts to a gauge of the next proposals of an app.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema, Eq)]
+pub struct ScheduledBribe {
+    pub id: u64,
+    pub depositor: Addr,
+    pub gauge: GaugeTarget,
+    /// Amount not yet released to a proposal
+    pub remaining: Coin,
+    /// Number of proposals the remaining amount is released over
+    pub remaining_epochs: u64,
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema, Eq)]
@@ -149,11 +525,17 @@ pub struct Vote {
     pub voting_power_total: u128,
     pub total_voted_ratio: Decimal,
     pub votes: Vec<VotePair>,
+    /// Voting power delegated to the voter, included in `voting_power_total`
+    #[serde(default)]
+    pub delegated_power: u128,
+    /// Commission of the voter on the bribes earned by the delegated voting power
+    #[serde(default)]
+    pub commission: Decimal,
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema, Eq)]
 pub struct VotePair {
-    pub extended_pair: u64,
+    pub gauge: GaugeTarget,
     pub vote_ratio: Decimal,
     pub vote_weight: u128,
 }
@@ -168,7 +550,7 @@ pub struct Delegation {
 
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema, Eq)]
 pub struct VoteResponse {
-    pub pair: u64,
+    pub gauge: GaugeTarget,
     pub total_incentive: Vec<Coin>,
     pub user_vote: u128,
     pub user_vote_ratio: Decimal,
@@ -189,10 +571,34 @@ pub struct RebaseAllResponse {
     pub claimed: bool,
 }
 
+/// Vote allocation re-applied to each new proposal of an app.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema, Eq)]
+pub struct StickyVote {
+    pub gauges: Vec<GaugeTarget>,
+    pub ratio: Vec<Decimal>,
+    /// Voting power the vote was last cast with
+    #[serde(default)]
+    pub voting_power: u128,
+}
+
+/// Commission set by a delegate on the bribes earned by the voting power delegated to it.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema, Eq)]
+pub struct DelegationInfo {
+    pub commission: Decimal,
+}
+
+/// Voting power of a delegator counted in the vote of a delegate.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema, Eq)]
+pub struct DelegatedVote {
+    pub delegated_to: Addr,
+    pub vote_power: u128,
+}
+
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema, Eq)]
 pub struct DelegationStats {
     pub total_delegated: u128,
     pub total_delegators: u64,
+    pub commission: Decimal,
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema, Eq)]
@@ -207,16 +613,62 @@ pub const CSWAP_ID: Item<u64> = Item::new("cswap_id");
 
 pub const APPCURRENTPROPOSAL: Map<u64, u64> = Map::new("app_current_proposal");
 
-pub const PROPOSALVOTE: Map<(u64, u64), Uint128> = Map::new("proposal_vote");
+pub const PROPOSALVOTE: Map<(u64, GaugeTarget), Uint128> = Map::new("proposal_gauge_vote");
 
 pub const PROPOSAL: Map<u64, Proposal> = Map::new("proposal");
 
-pub const BRIBES_BY_PROPOSAL: Map<(u64, u64), Vec<Coin>> = Map::new("bribes_by_proposal");
+pub const BRIBES_BY_PROPOSAL: Map<(u64, GaugeTarget), Vec<Coin>> =
+    Map::new("proposal_gauge_bribes");
 
 pub const EMISSION: Map<u64, Emission> = Map::new("emission");
 
 pub const EMISSION_REWARD: Map<u64, EmissionVaultPool> = Map::new("emission_rewards");
 
+// Bribes deposited by each (proposal, depositor), reclaimable if the proposal is cancelled
+// or on gauges without votes
+pub const BRIBE_DEPOSITS: Map<(u64, Addr), Vec<BribeDeposit>> = Map::new("bribe_deposits");
+
+// Bribes scheduled over the next proposals of each (app, scheduled bribe id)
+pub const SCHEDULED_BRIBES: Map<(u64, u64), ScheduledBribe> = Map::new("scheduled_bribes");
+
+pub const SCHEDULED_BRIBE_COUNT: Item<u64> = Item::new("scheduled_bribe_count");
+
+// Sticky vote of each (app, voter)
+pub const STICKY_VOTES: Map<(u64, Addr), StickyVote> = Map::new("sticky_votes");
+
+// Voting power delegations of each (delegator, denom)
+pub const DELEGATED: SnapshotMap<(Addr, &str), UserDelegationInfo> = SnapshotMap::new(
+    "delegated",
+    "delegated_checkpoints",
+    "delegated_changelogs",
+    Strategy::EveryBlock,
+);
+
+// Addresses delegating voting power to each (delegate, denom)
+pub const DELEGATORS: SnapshotMap<(Addr, &str), Vec<Addr>> = SnapshotMap::new(
+    "delegators",
+    "delegators_checkpoints",
+    "delegators_changelogs",
+    Strategy::EveryBlock,
+);
+
+// Commission of each delegate, set to accept delegations
+pub const DELEGATION_INFO: SnapshotMap<Addr, DelegationInfo> = SnapshotMap::new(
+    "delegation_info",
+    "delegation_info_checkpoints",
+    "delegation_info_changelogs",
+    Strategy::EveryBlock,
+);
+
+// Voting power of each (delegator, proposal) cast by its delegates
+pub const DELEGATED_VOTES: Map<(Addr, u64), Vec<DelegatedVote>> = Map::new("delegated_votes");
+
+// Gauges killed by the admin for each (app, gauge), left out of the new proposals of the app
+pub const KILLED_GAUGES: Map<(u64, GaugeTarget), bool> = Map::new("killed_gauges");
+
+// Contracts registered as gauges of each app
+pub const CONTRACT_GAUGES: Map<u64, Vec<Addr>> = Map::new("contract_gauges");
+
 pub const VOTERS_VOTE: Map<(Addr, u64), bool> = Map::new("voters_vote");
 
 pub const VOTERS_CLAIM: Map<(Addr, u64), bool> = Map::new("voters_claim");
@@ -230,3 +682,58 @@ pub const MAXPROPOSALCLAIMED: Map<(u64, Addr), u64> = Map::new("max_proposal_cla
 pub const COMPLETEDPROPOSALS: Map<u64, Vec<u64>> = Map::new("completed_proposals");
 
 pub const REBASE_CLAIMED: Map<(Addr, u64), bool> = Map::new("rebase_claimed");
+
+// Storage layout before gauge targets, where cswap pools were voted upon as extended
+// pairs offset by `LEGACY_POOL_OFFSET`. Only read by the migration.
+pub const LEGACY_POOL_OFFSET: u64 = 1000000;
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema, Eq)]
+pub struct LegacyProposal {
+    pub app_id: u64,
+    pub voting_start_time: Timestamp,
+    pub voting_end_time: Timestamp,
+    pub extended_pair: Vec<u64>,
+    pub emission_completed: bool,
+    pub rebase_completed: bool,
+    pub foundation_emission_completed: bool,
+    pub emission_distributed: u128,
+    pub rebase_distributed: u128,
+    pub foundation_distributed: u128,
+    pub total_voted_weight: u128,
+    pub total_surplus: Coin,
+    pub height: u64,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema, Eq)]
+pub struct LegacyVotePair {
+    pub extended_pair: u64,
+    pub vote_ratio: Decimal,
+    pub vote_weight: u128,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema, Eq)]
+pub struct LegacyVote {
+    pub voting_power_total: u128,
+    pub total_voted_ratio: Decimal,
+    pub votes: Vec<LegacyVotePair>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema, Eq)]
+pub struct LegacyEmissionVaultPool {
+    pub app_id: u64,
+    pub pool_ids: Vec<u64>,
+    pub vault_ids: Vec<u64>,
+    pub total_emission_rewards: Uint128,
+    pub pool_rewards: Vec<Uint128>,
+    pub vault_rewards: Vec<Uint128>,
+}
+
+pub const LEGACY_PROPOSAL: Map<u64, LegacyProposal> = Map::new("proposal");
+
+pub const LEGACY_PROPOSALVOTE: Map<(u64, u64), Uint128> = Map::new("proposal_vote");
+
+pub const LEGACY_BRIBES_BY_PROPOSAL: Map<(u64, u64), Vec<Coin>> = Map::new("bribes_by_proposal");
+
+pub const LEGACY_EMISSION_REWARD: Map<u64, LegacyEmissionVaultPool> = Map::new("emission_rewards");
+
+pub const LEGACY_VOTERSPROPOSAL: Map<(Addr, u64), LegacyVote> = Map::new("voters_proposal");